@@ -0,0 +1,317 @@
+// Every command is an action on the application, so menus, toolbar buttons, shortcuts and
+// the command palette all trigger it the same way
+use crate::preferences::Preferences;
+use gtk::gdk::ModifierType;
+use gtk::gio::SimpleAction;
+use gtk::glib::{ToVariant, Variant};
+use gtk::prelude::*;
+use gtk::{
+    AccelLabel, Application, ApplicationWindow, CellRendererAccel, CellRendererAccelMode,
+    CellRendererText, Container, ListStore, MenuItem, ScrolledWindow, ShadowType, TreeView,
+    TreeViewColumn, Widget,
+};
+use std::collections::BTreeMap;
+
+pub struct ActionInfo {
+    // Without the "app." prefix
+    pub name: &'static str,
+    pub label: &'static str,
+    // The first is shown in menus; the rest are alternatives, e.g. on the keypad
+    pub default_accels: &'static [&'static str],
+}
+
+pub const ACTIONS: &[ActionInfo] = &[
+    action("open", "Open File...", &["<Primary>o"]),
+    action("open-url", "Open URL...", &["<Primary>l"]),
+    action("save", "Save", &["<Primary>s"]),
+    action("save-as", "Save As...", &["<Primary><Shift>s"]),
+    action("quit", "Quit", &["<Primary>q"]),
+    action("undo", "Undo", &["<Primary>z"]),
+    action("redo", "Redo", &["<Primary><Shift>z", "<Primary>y"]),
+    action("find", "Find...", &["<Primary>f"]),
+    action("replace", "Replace...", &["<Primary>h"]),
+    action("preferences", "Preferences...", &["<Primary>comma"]),
+    action(
+        "command-palette",
+        "Command Palette...",
+        &["<Primary><Shift>p"],
+    ),
+    action("canonical-hash", "Show SHA-256 of Canonical Form", &[]),
+    action("toggle-tree", "Toggle Tree", &["F9"]),
+    action("expand-all", "Expand All", &[]),
+    action("collapse-all", "Collapse All", &[]),
+    action("expand-to-depth", "Expand to Depth...", &[]),
+    action(
+        "zoom-in",
+        "Zoom In",
+        &["<Primary>equal", "<Primary>plus", "<Primary>KP_Add"],
+    ),
+    action(
+        "zoom-out",
+        "Zoom Out",
+        &["<Primary>minus", "<Primary>KP_Subtract"],
+    ),
+    action("reset-zoom", "Reset Zoom", &["<Primary>0", "<Primary>KP_0"]),
+    action("pretty", "Pretty Print", &["<Primary><Shift>f"]),
+    action("minify", "Minify", &["<Primary><Shift>m"]),
+    action("remove-double-newlines", "Remove Double Newlines", &[]),
+    action("unescape", "Unescape", &[]),
+    action("escape", "Escape", &[]),
+    action("lenient", "Lenient Parsing", &[]),
+    action("normalize", "Normalize to Strict JSON", &[]),
+    action("sort-keys", "Sort Keys...", &[]),
+    action("canonicalize", "Canonicalize (RFC 8785)", &[]),
+    action("extract-json", "Extract JSON...", &[]),
+    action("deep-unescape", "Deep Unescape", &[]),
+    action("re-encode", "Re-encode Selected as String", &[]),
+    action("jwt-inspector", "JWT Inspector...", &[]),
+    action("statistics", "Statistics...", &[]),
+    action("github-issues", "GitHub Issues", &[]),
+    action("about", "About", &[]),
+];
+
+const fn action(
+    name: &'static str,
+    label: &'static str,
+    default_accels: &'static [&'static str],
+) -> ActionInfo {
+    ActionInfo {
+        name,
+        label,
+        default_accels,
+    }
+}
+
+pub fn add_action<F: Fn() + 'static>(app: &Application, name: &str, activate: F) {
+    let action = SimpleAction::new(name, None);
+    action.connect_activate(move |_, _| activate());
+    app.add_action(&action);
+}
+
+// An on/off action, shown as a check menu item or toggle button. Activating it flips the
+// state, and `change_state` is called with the new one.
+pub fn add_toggle_action<F: Fn(bool) + 'static>(
+    app: &Application,
+    name: &str,
+    initial: bool,
+    change_state: F,
+) -> SimpleAction {
+    let action = SimpleAction::new_stateful(name, None, &initial.to_variant());
+    action.connect_change_state(move |action, state| {
+        if let Some(state) = state.and_then(Variant::get::<bool>) {
+            action.set_state(&state.to_variant());
+            change_state(state);
+        }
+    });
+    app.add_action(&action);
+    action
+}
+
+// The shortcuts for an action, taking the user's choice over the defaults
+pub fn accels_for(preferences: &Preferences, info: &ActionInfo) -> Vec<String> {
+    match preferences.shortcuts.get(info.name) {
+        Some(accel) if accel.is_empty() => vec![],
+        Some(accel) => vec![accel.clone()],
+        None => info
+            .default_accels
+            .iter()
+            .map(|accel| accel.to_string())
+            .collect(),
+    }
+}
+
+pub fn apply_shortcuts(win: &ApplicationWindow, preferences: &Preferences) {
+    let Some(app) = win.application() else {
+        return;
+    };
+    for info in ACTIONS {
+        let accels = accels_for(preferences, info);
+        app.set_accels_for_action(
+            &format!("app.{}", info.name),
+            &accels.iter().map(String::as_str).collect::<Vec<_>>(),
+        );
+    }
+
+    if let Some(child) = win.child() {
+        update_accel_labels(&app, &child);
+    }
+}
+
+// Menu items only show the shortcut of their action if told it, so find each one in the
+// window, including inside submenus
+fn update_accel_labels(app: &Application, widget: &Widget) {
+    if let Some(menu_item) = widget.downcast_ref::<MenuItem>() {
+        if let Some(action_name) = menu_item.action_name() {
+            let (key, modifiers) = app
+                .accels_for_action(&action_name)
+                .first()
+                .map_or((0, ModifierType::empty()), |accel| {
+                    gtk::accelerator_parse(accel)
+                });
+            if let Some(accel_label) = menu_item
+                .child()
+                .and_then(|child| child.downcast::<AccelLabel>().ok())
+            {
+                accel_label.set_accel(key, modifiers);
+            }
+        }
+        if let Some(submenu) = menu_item.submenu() {
+            update_accel_labels(app, &submenu);
+        }
+    } else if let Some(container) = widget.downcast_ref::<Container>() {
+        for child in container.children() {
+            update_accel_labels(app, &child);
+        }
+    }
+}
+
+const COL_NAME: u32 = 0;
+const COL_LABEL: u32 = 1;
+const COL_KEY: u32 = 2;
+const COL_MODS: u32 = 3;
+
+fn parse_accel(accel: Option<&str>) -> (u32, ModifierType) {
+    accel.map_or((0, ModifierType::empty()), gtk::accelerator_parse)
+}
+
+// A list of every action and its shortcut, where clicking a shortcut and pressing keys
+// rebinds it and Backspace clears it
+pub fn shortcut_editor(preferences: &Preferences) -> (ScrolledWindow, ListStore) {
+    let model = ListStore::new(&[
+        String::static_type(),
+        String::static_type(),
+        u32::static_type(),
+        u32::static_type(),
+    ]);
+    for info in ACTIONS {
+        let accels = accels_for(preferences, info);
+        let (key, modifiers) = parse_accel(accels.first().map(String::as_str));
+        model.set(
+            &model.append(),
+            &[
+                (COL_NAME, &info.name),
+                (COL_LABEL, &info.label),
+                (COL_KEY, &key),
+                (COL_MODS, &modifiers.bits()),
+            ],
+        );
+    }
+
+    let list = TreeView::builder().model(&model).build();
+
+    let label_column = TreeViewColumn::new();
+    label_column.set_title("Action");
+    label_column.set_expand(true);
+    let label_cell = CellRendererText::new();
+    gtk::prelude::CellLayoutExt::pack_start(&label_column, &label_cell, true);
+    gtk::prelude::TreeViewColumnExt::add_attribute(
+        &label_column,
+        &label_cell,
+        "text",
+        COL_LABEL as i32,
+    );
+    list.append_column(&label_column);
+
+    let accel_column = TreeViewColumn::new();
+    accel_column.set_title("Shortcut");
+    let accel_cell = CellRendererAccel::builder()
+        .editable(true)
+        .accel_mode(CellRendererAccelMode::Gtk)
+        .build();
+    gtk::prelude::CellLayoutExt::pack_start(&accel_column, &accel_cell, true);
+    gtk::prelude::TreeViewColumnExt::add_attribute(
+        &accel_column,
+        &accel_cell,
+        "accel-key",
+        COL_KEY as i32,
+    );
+    gtk::prelude::TreeViewColumnExt::add_attribute(
+        &accel_column,
+        &accel_cell,
+        "accel-mods",
+        COL_MODS as i32,
+    );
+    list.append_column(&accel_column);
+
+    accel_cell.connect_accel_edited({
+        let model = model.clone();
+        move |_, path, key, modifiers, _| {
+            // A shortcut can only trigger one action, so take it from any other
+            model.foreach(|_, _, iter| {
+                let other_key = model.value(iter, COL_KEY as i32).get::<u32>().unwrap();
+                let other_mods = model.value(iter, COL_MODS as i32).get::<u32>().unwrap();
+                if other_key == key && other_mods == modifiers.bits() {
+                    model.set(iter, &[(COL_KEY, &0u32), (COL_MODS, &0u32)]);
+                }
+                false
+            });
+            if let Some(iter) = model.iter(&path) {
+                model.set(&iter, &[(COL_KEY, &key), (COL_MODS, &modifiers.bits())]);
+            }
+        }
+    });
+    accel_cell.connect_accel_cleared({
+        let model = model.clone();
+        move |_, path| {
+            if let Some(iter) = model.iter(&path) {
+                model.set(&iter, &[(COL_KEY, &0u32), (COL_MODS, &0u32)]);
+            }
+        }
+    });
+
+    let scroller = ScrolledWindow::builder()
+        .shadow_type(ShadowType::In)
+        .min_content_height(200)
+        .expand(true)
+        .build();
+    scroller.add(&list);
+
+    (scroller, model)
+}
+
+pub fn reset_shortcuts(model: &ListStore) {
+    model.foreach(|_, _, iter| {
+        let name = model.value(iter, COL_NAME as i32).get::<String>().unwrap();
+        if let Some(info) = ACTIONS.iter().find(|info| info.name == name) {
+            let (key, modifiers) = parse_accel(info.default_accels.first().copied());
+            model.set(iter, &[(COL_KEY, &key), (COL_MODS, &modifiers.bits())]);
+        }
+        false
+    });
+}
+
+// Only shortcuts changed from the default are kept, so defaults added later still apply
+pub fn shortcuts_from_editor(model: &ListStore) -> BTreeMap<String, String> {
+    let mut shortcuts = BTreeMap::new();
+    model.foreach(|_, _, iter| {
+        let name = model.value(iter, COL_NAME as i32).get::<String>().unwrap();
+        let key = model.value(iter, COL_KEY as i32).get::<u32>().unwrap();
+        let modifiers = ModifierType::from_bits_truncate(
+            model.value(iter, COL_MODS as i32).get::<u32>().unwrap(),
+        );
+        let accel = if key == 0 {
+            String::new()
+        } else {
+            gtk::accelerator_name(key, modifiers)
+                .map(|name| name.to_string())
+                .unwrap_or_default()
+        };
+
+        let default = ACTIONS
+            .iter()
+            .find(|info| info.name == name)
+            .and_then(|info| info.default_accels.first())
+            .map(|accel| {
+                let (key, modifiers) = gtk::accelerator_parse(accel);
+                gtk::accelerator_name(key, modifiers)
+                    .map(|name| name.to_string())
+                    .unwrap_or_default()
+            })
+            .unwrap_or_default();
+        if accel != default {
+            shortcuts.insert(name, accel);
+        }
+        false
+    });
+    shortcuts
+}
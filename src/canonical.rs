@@ -0,0 +1,202 @@
+// RFC 8785 JSON Canonicalization Scheme (JCS)
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+
+pub fn canonicalize(v: &Value) -> String {
+    let mut out = String::new();
+    write_canonical(&mut out, v);
+    out
+}
+
+pub fn sha256_hex(text: &str) -> String {
+    Sha256::digest(text.as_bytes())
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+fn write_canonical(out: &mut String, v: &Value) {
+    match v {
+        Value::Object(map) => {
+            // Keys are sorted by their UTF-16 code units, not by UTF-8 bytes
+            let mut entries: Vec<(&String, &Value)> = map.iter().collect();
+            entries.sort_by_cached_key(|(k, _)| k.encode_utf16().collect::<Vec<u16>>());
+
+            out.push('{');
+            for (i, (k, val)) in entries.into_iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                out.push_str(&serde_json::to_string(k).unwrap());
+                out.push(':');
+                write_canonical(out, val);
+            }
+            out.push('}');
+        }
+        Value::Array(arr) => {
+            out.push('[');
+            for (i, val) in arr.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                write_canonical(out, val);
+            }
+            out.push(']');
+        }
+        // serde_json already escapes strings exactly as JCS requires
+        Value::String(s) => out.push_str(&serde_json::to_string(s).unwrap()),
+        Value::Number(n) => out.push_str(&format_es_number(n.as_f64().unwrap_or(0.0))),
+        Value::Bool(b) => out.push_str(if *b { "true" } else { "false" }),
+        Value::Null => out.push_str("null"),
+    }
+}
+
+// Number serialization as done by ECMAScript's Number.prototype.toString
+fn format_es_number(n: f64) -> String {
+    if n == 0.0 {
+        // Covers -0 too
+        return "0".to_string();
+    }
+
+    // Rust's `{:e}` gives the shortest digits that round-trip, e.g. `1.2345e-7`. When two are
+    // as short, ECMAScript wants the one closest to the exact value, which is what rounding
+    // the exact value to that many digits gives.
+    let shortest = format!("{:e}", n.abs());
+    let digit_count = shortest.split('e').next().unwrap().replace('.', "").len();
+    let rounded = format!("{:.*e}", digit_count - 1, n.abs());
+    let scientific = if rounded.parse::<f64>() == Ok(n.abs()) {
+        rounded
+    } else {
+        shortest
+    };
+    let (mantissa, exponent) = scientific.split_once('e').unwrap();
+    let digits: String = mantissa.chars().filter(|c| *c != '.').collect();
+    let k = digits.len() as i32;
+    let n_exp = exponent.parse::<i32>().unwrap() + 1;
+    let sign = if n < 0.0 { "-" } else { "" };
+
+    let body = if k <= n_exp && n_exp <= 21 {
+        format!("{}{}", digits, "0".repeat((n_exp - k) as usize))
+    } else if 0 < n_exp && n_exp <= 21 {
+        format!(
+            "{}.{}",
+            &digits[..n_exp as usize],
+            &digits[n_exp as usize..]
+        )
+    } else if -6 < n_exp && n_exp <= 0 {
+        format!("0.{}{}", "0".repeat((-n_exp) as usize), digits)
+    } else {
+        let e = n_exp - 1;
+        let e_sign = if e < 0 { "-" } else { "+" };
+        let fraction = if k > 1 {
+            format!(".{}", &digits[1..])
+        } else {
+            String::new()
+        };
+        format!("{}{}e{}{}", &digits[..1], fraction, e_sign, e.abs())
+    };
+
+    format!("{}{}", sign, body)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // RFC 8785 Appendix B, IEEE 754 bit patterns and how they are serialized
+    const NUMBERS: [(u64, &str); 24] = [
+        (0x0000000000000000, "0"),
+        (0x8000000000000000, "0"),
+        (0x0000000000000001, "5e-324"),
+        (0x8000000000000001, "-5e-324"),
+        (0x7fefffffffffffff, "1.7976931348623157e+308"),
+        (0xffefffffffffffff, "-1.7976931348623157e+308"),
+        (0x4340000000000000, "9007199254740992"),
+        (0xc340000000000000, "-9007199254740992"),
+        (0x4430000000000000, "295147905179352830000"),
+        (0x44b52d02c7e14af5, "9.999999999999997e+22"),
+        (0x44b52d02c7e14af6, "1e+23"),
+        (0x44b52d02c7e14af7, "1.0000000000000001e+23"),
+        (0x444b1ae4d6e2ef4e, "999999999999999700000"),
+        (0x444b1ae4d6e2ef4f, "999999999999999900000"),
+        (0x444b1ae4d6e2ef50, "1e+21"),
+        (0x3eb0c6f7a0b5ed8c, "9.999999999999997e-7"),
+        (0x3eb0c6f7a0b5ed8d, "0.000001"),
+        (0x41b3de4355555553, "333333333.3333332"),
+        (0x41b3de4355555554, "333333333.33333325"),
+        (0x41b3de4355555555, "333333333.3333333"),
+        (0x41b3de4355555556, "333333333.3333334"),
+        (0x41b3de4355555557, "333333333.33333343"),
+        (0xbecbf647612f3696, "-0.0000033333333333333333"),
+        (0x43143ff3c1cb0959, "1424953923781206.2"),
+    ];
+
+    #[test]
+    fn numbers() {
+        for (bits, expected) in NUMBERS {
+            assert_eq!(
+                format_es_number(f64::from_bits(bits)),
+                expected,
+                "{:#x}",
+                bits
+            );
+        }
+    }
+
+    // RFC 8785 section 3.2.2
+    #[test]
+    fn primitives() {
+        let input = r#"{
+            "numbers": [333333333.33333329, 1E30, 4.50, 2e-3, 0.000000000000000000000000001],
+            "string": "\u20ac$\u000F\u000aA'\u0042\u0022\u005c\\\"\/",
+            "literals": [null, true, false]
+        }"#;
+        let v: Value = serde_json::from_str(input).unwrap();
+        assert_eq!(
+            canonicalize(&v),
+            r#"{"literals":[null,true,false],"numbers":[333333333.3333333,1e+30,4.5,0.002,1e-27],"string":"€$\u000f\nA'B\"\\\\\"/"}"#
+        );
+    }
+
+    // RFC 8785 section 3.2.3
+    #[test]
+    fn keys_sorted_by_utf16() {
+        let input = r#"{
+            "€": "Euro Sign",
+            "\r": "Carriage Return",
+            "דּ": "Hebrew Letter Dalet With Dagesh",
+            "1": "One",
+            "😀": "Emoji: Grinning Face",
+            "\u0080": "Control",
+            "ö": "Latin Small Letter O With Diaeresis"
+        }"#;
+        let v: Value = serde_json::from_str(input).unwrap();
+        let sorted: Value = serde_json::from_str(&canonicalize(&v)).unwrap();
+        let keys: Vec<&str> = sorted
+            .as_object()
+            .unwrap()
+            .keys()
+            .map(String::as_str)
+            .collect();
+        assert_eq!(keys, ["\r", "1", "\u{80}", "ö", "€", "😀", "\u{fb33}"]);
+    }
+
+    #[test]
+    fn nested_and_empty() {
+        let v: Value =
+            serde_json::from_str(r#"{"b": [{}, [], {"d": 1, "c": 2}], "a": ""}"#).unwrap();
+        assert_eq!(canonicalize(&v), r#"{"a":"","b":[{},[],{"c":2,"d":1}]}"#);
+    }
+
+    #[test]
+    fn sha256() {
+        assert_eq!(
+            sha256_hex(""),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+        assert_eq!(
+            sha256_hex("abc"),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+    }
+}
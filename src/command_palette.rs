@@ -0,0 +1,209 @@
+// Ctrl+Shift+P: find any action by typing part of its name, then run it with Enter
+use crate::actions::{ActionInfo, ACTIONS};
+use gtk::gdk::keys::constants as keys;
+use gtk::gdk::WindowTypeHint;
+use gtk::glib::Propagation;
+use gtk::prelude::*;
+use gtk::{
+    Align, ApplicationWindow, Label, ListBox, ListBoxRow, Orientation, ScrolledWindow, SearchEntry,
+    SelectionMode, ShadowType, WindowPosition,
+};
+use std::cell::RefCell;
+use std::rc::Rc;
+
+// Score how well the query matches the label, with the query's characters appearing in
+// order, or None if they don't. Matches at the start of words and runs of consecutive
+// characters score higher.
+fn fuzzy_score(query: &str, label: &str) -> Option<i32> {
+    let label: Vec<char> = label.to_lowercase().chars().collect();
+    let mut score = 0;
+    let mut position = 0;
+    let mut previous_match: Option<usize> = None;
+
+    for query_char in query.to_lowercase().chars().filter(|c| !c.is_whitespace()) {
+        let found = (position..label.len()).find(|&i| label[i] == query_char)?;
+        score += 1;
+        if found == 0 || !label[found - 1].is_alphanumeric() {
+            score += 8;
+        }
+        if previous_match.is_some_and(|previous| previous + 1 == found) {
+            score += 4;
+        }
+        // Skipping characters counts against the match
+        score -= (found - position).min(3) as i32;
+        previous_match = Some(found);
+        position = found + 1;
+    }
+
+    Some(score)
+}
+
+fn shortcut_label(app: &gtk::Application, info: &ActionInfo) -> String {
+    app.accels_for_action(&format!("app.{}", info.name))
+        .first()
+        .and_then(|accel| {
+            let (key, modifiers) = gtk::accelerator_parse(accel);
+            gtk::accelerator_get_label(key, modifiers)
+        })
+        .map(|label| label.to_string())
+        .unwrap_or_default()
+}
+
+// Show the enabled actions matching the query, best match first
+fn fill_list(list: &ListBox, app: &gtk::Application, query: &str) -> Vec<&'static str> {
+    for child in list.children() {
+        list.remove(&child);
+    }
+
+    let mut matches: Vec<(i32, &ActionInfo)> = ACTIONS
+        .iter()
+        .filter(|info| info.name != "command-palette")
+        .filter(|info| {
+            app.lookup_action(info.name)
+                .is_some_and(|action| action.is_enabled())
+        })
+        .filter_map(|info| fuzzy_score(query, info.label).map(|score| (score, info)))
+        .collect();
+    // Stable, so equal scores keep the order of the menus
+    matches.sort_by(|(a, _), (b, _)| b.cmp(a));
+
+    for (_, info) in &matches {
+        let row_box = gtk::Box::builder()
+            .orientation(Orientation::Horizontal)
+            .spacing(12)
+            .margin(6)
+            .build();
+        let label = Label::builder()
+            .label(info.label.trim_end_matches("..."))
+            .halign(Align::Start)
+            .hexpand(true)
+            .build();
+        let shortcut = Label::builder()
+            .label(shortcut_label(app, info))
+            .halign(Align::End)
+            .build();
+        shortcut.style_context().add_class("dim-label");
+        row_box.pack_start(&label, true, true, 0);
+        row_box.pack_end(&shortcut, false, false, 0);
+
+        let row = ListBoxRow::new();
+        row.add(&row_box);
+        list.add(&row);
+    }
+    list.show_all();
+    list.select_row(list.row_at_index(0).as_ref());
+
+    matches.iter().map(|(_, info)| info.name).collect()
+}
+
+pub fn command_palette_action(win: ApplicationWindow) {
+    let Some(app) = win.application() else {
+        return;
+    };
+
+    let palette = gtk::Window::builder()
+        .transient_for(&win)
+        .modal(true)
+        .decorated(false)
+        .type_hint(WindowTypeHint::Dialog)
+        .window_position(WindowPosition::CenterOnParent)
+        .default_width(480)
+        .default_height(360)
+        .build();
+
+    let v_box = gtk::Box::builder()
+        .orientation(Orientation::Vertical)
+        .spacing(6)
+        .margin(6)
+        .build();
+    palette.add(&v_box);
+
+    let entry = SearchEntry::builder()
+        .placeholder_text("Type a command")
+        .build();
+    v_box.pack_start(&entry, false, false, 0);
+
+    let list = ListBox::builder()
+        .selection_mode(SelectionMode::Browse)
+        .activate_on_single_click(true)
+        .build();
+    let scroller = ScrolledWindow::builder()
+        .shadow_type(ShadowType::In)
+        .expand(true)
+        .build();
+    scroller.add(&list);
+    v_box.pack_start(&scroller, true, true, 0);
+
+    let names = Rc::new(RefCell::new(fill_list(&list, &app, "")));
+
+    entry.connect_search_changed({
+        let list = list.clone();
+        let app = app.clone();
+        let names = names.clone();
+        move |entry| {
+            names.replace(fill_list(&list, &app, entry.text().as_str()));
+        }
+    });
+
+    // Close before running the action, so any dialog it opens isn't behind the palette
+    let run = Rc::new({
+        let palette = palette.clone();
+        move |index: i32| {
+            let name = usize::try_from(index)
+                .ok()
+                .and_then(|index| names.borrow().get(index).copied());
+            palette.close();
+            if let Some(name) = name {
+                app.activate_action(name, None);
+            }
+        }
+    });
+
+    list.connect_row_activated({
+        let run = run.clone();
+        move |_, row| run(row.index())
+    });
+
+    entry.connect_activate({
+        let list = list.clone();
+        move |_| {
+            if let Some(row) = list.selected_row() {
+                run(row.index());
+            }
+        }
+    });
+
+    // Arrow keys move through the list while typing continues in the entry
+    entry.connect_key_press_event({
+        let list = list.clone();
+        let scroller = scroller.clone();
+        move |_, event| {
+            let step = match event.keyval() {
+                keys::Down => 1,
+                keys::Up => -1,
+                _ => return Propagation::Proceed,
+            };
+            let index = list.selected_row().map_or(0, |row| row.index() + step);
+            if let Some(row) = list.row_at_index(index) {
+                list.select_row(Some(&row));
+                let allocation = row.allocation();
+                scroller.vadjustment().clamp_page(
+                    allocation.y() as f64,
+                    (allocation.y() + allocation.height()) as f64,
+                );
+            }
+            Propagation::Stop
+        }
+    });
+
+    palette.connect_key_press_event(|palette, event| {
+        if event.keyval() == keys::Escape {
+            palette.close();
+            return Propagation::Stop;
+        }
+        Propagation::Proceed
+    });
+
+    palette.show_all();
+    entry.grab_focus();
+}
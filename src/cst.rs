@@ -0,0 +1,1301 @@
+// A lossless concrete syntax tree for JSON/JSON5/JSONC.
+//
+// Every byte of the source, including whitespace and comments, is kept in a token so that
+// concatenating all tokens gives back the original text. Formatting only ever regenerates
+// whitespace, and edits only touch the tokens they replace.
+use crate::json_parser;
+use crate::json_parser::{ParseMode, TokenKind};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Number, Value};
+use std::cmp::Ordering;
+use std::fmt;
+use std::ops::Range;
+
+#[derive(Clone, Debug)]
+pub struct Token {
+    pub kind: TokenKind,
+    pub text: String,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum NodeKind {
+    Document,
+    Object,
+    Array,
+    // `key: value` inside an object, with its comma and comments
+    Member,
+    // A value inside an array, with its comma and comments
+    Item,
+}
+
+#[derive(Clone, Debug)]
+pub struct Node {
+    pub kind: NodeKind,
+    pub children: Vec<Element>,
+}
+
+#[derive(Clone, Debug)]
+pub enum Element {
+    Token(Token),
+    Node(Node),
+}
+
+impl Element {
+    fn len(&self) -> usize {
+        match self {
+            Element::Token(token) => token.text.len(),
+            Element::Node(node) => node.children.iter().map(Element::len).sum(),
+        }
+    }
+
+    fn is_trivia(&self) -> bool {
+        matches!(self, Element::Token(token) if token.kind.is_trivia())
+    }
+
+    fn is_comment(&self) -> bool {
+        matches!(
+            self,
+            Element::Token(Token {
+                kind: TokenKind::LineComment | TokenKind::BlockComment,
+                ..
+            })
+        )
+    }
+
+    fn as_node(&self) -> Option<&Node> {
+        match self {
+            Element::Node(node) => Some(node),
+            Element::Token(_) => None,
+        }
+    }
+}
+
+impl fmt::Display for Element {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Element::Token(token) => f.write_str(&token.text),
+            Element::Node(node) => node.children.iter().try_for_each(|c| c.fmt(f)),
+        }
+    }
+}
+
+impl Node {
+    // The members of an object or the items of an array
+    pub fn entries(&self) -> impl Iterator<Item = &Node> {
+        self.children
+            .iter()
+            .filter_map(Element::as_node)
+            .filter(|n| matches!(n.kind, NodeKind::Member | NodeKind::Item))
+    }
+
+    fn value_index(&self) -> Option<usize> {
+        let mut seen_colon = false;
+        self.children.iter().position(|c| match c {
+            Element::Node(_) => true,
+            Element::Token(token) => match token.kind {
+                TokenKind::Colon => {
+                    seen_colon = true;
+                    false
+                }
+                TokenKind::String | TokenKind::Number | TokenKind::Ident => {
+                    self.kind != NodeKind::Member || seen_colon
+                }
+                _ => false,
+            },
+        })
+    }
+
+    pub fn key_token(&self) -> Option<&Token> {
+        if self.kind != NodeKind::Member {
+            return None;
+        }
+        self.children.iter().find_map(|c| match c {
+            Element::Token(token) if matches!(token.kind, TokenKind::String | TokenKind::Ident) => {
+                Some(token)
+            }
+            _ => None,
+        })
+    }
+
+    pub fn key(&self) -> Option<String> {
+        let token = self.key_token()?;
+        match token.kind {
+            TokenKind::String => json_parser::decode_string(&token.text).ok(),
+            _ => json_parser::decode_identifier(&token.text).ok(),
+        }
+    }
+}
+
+pub struct Document {
+    pub root: Node,
+}
+
+impl fmt::Display for Document {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.root.children.iter().try_for_each(|c| c.fmt(f))
+    }
+}
+
+impl Document {
+    // Byte range of the value found at the given JSON Pointer segments
+    pub fn value_span(&self, segments: &[String]) -> Option<Range<usize>> {
+        let mut node = &self.root;
+        let mut offset = 0;
+        let mut segments = segments.iter();
+
+        loop {
+            let value_index = node.value_index()?;
+            offset += node.children[..value_index]
+                .iter()
+                .map(Element::len)
+                .sum::<usize>();
+            let value = &node.children[value_index];
+
+            let Some(segment) = segments.next() else {
+                return Some(offset..offset + value.len());
+            };
+            let container = value.as_node()?;
+
+            // Walk the entries, remembering the offset of the match. For duplicate keys the
+            // last one wins, as it does when parsing into a serde_json::Value.
+            let mut found = None;
+            let mut entry_offset = offset;
+            let mut index = 0;
+            for child in &container.children {
+                if let Element::Node(entry) = child {
+                    let matches = match entry.kind {
+                        NodeKind::Member => entry.key().as_deref() == Some(segment.as_str()),
+                        _ => segment.parse() == Ok(index),
+                    };
+                    if matches {
+                        found = Some((entry, entry_offset));
+                    }
+                    index += 1;
+                }
+                entry_offset += child.len();
+            }
+            (node, offset) = found?;
+        }
+    }
+
+    // Byte span and decoded text of every string value, leaving out keys
+    pub fn string_values(&self) -> Vec<(Range<usize>, String)> {
+        let mut strings = vec![];
+        collect_string_values(&self.root, 0, &mut strings);
+        strings
+    }
+
+    // JSON Pointer segments of the innermost member or item containing the byte offset
+    pub fn segments_at(&self, offset: usize) -> Vec<String> {
+        let mut segments = vec![];
+        let mut node = &self.root;
+        let mut node_offset = 0;
+
+        loop {
+            let Some(value_index) = node.value_index() else {
+                return segments;
+            };
+            let value_offset = node_offset
+                + node.children[..value_index]
+                    .iter()
+                    .map(Element::len)
+                    .sum::<usize>();
+            let Some(container) = node.children[value_index].as_node() else {
+                return segments;
+            };
+
+            let mut entry_offset = value_offset;
+            let mut index = 0;
+            let mut found = None;
+            for child in &container.children {
+                let len = child.len();
+                if let Element::Node(entry) = child {
+                    if (entry_offset..entry_offset + len).contains(&offset) {
+                        found = Some((entry, entry_offset, index));
+                        break;
+                    }
+                    index += 1;
+                }
+                entry_offset += len;
+            }
+            let Some((entry, entry_offset, index)) = found else {
+                return segments;
+            };
+
+            segments.push(match entry.kind {
+                NodeKind::Member => entry.key().unwrap_or_default(),
+                _ => index.to_string(),
+            });
+            (node, node_offset) = (entry, entry_offset);
+        }
+    }
+
+    // Reorder object members by key, either just for the object found at the given JSON
+    // Pointer segments or for every object nested within it. Comments move with the member
+    // they belong to, and commas are fixed up afterwards. Returns false if nothing was found.
+    pub fn sort_keys(
+        &mut self,
+        segments: &[String],
+        compare: &dyn Fn(&str, &str) -> Ordering,
+        recursive: bool,
+    ) -> bool {
+        match find_value_mut(&mut self.root, segments) {
+            Some(value) => {
+                sort_element(value, compare, recursive);
+                true
+            }
+            None => false,
+        }
+    }
+
+    // The value of the document, so that one parse gives both it and the syntax tree. None
+    // if the text isn't valid in the mode, e.g. has comments in strict mode.
+    pub fn to_value(&self, mode: ParseMode) -> Option<Value> {
+        entry_value(&self.root, mode == ParseMode::Strict)
+    }
+}
+
+// The value of the document, a member or an item
+fn entry_value(node: &Node, strict: bool) -> Option<Value> {
+    if strict && !node.children.iter().all(is_strict_trivia) {
+        return None;
+    }
+    element_value(&node.children[node.value_index()?], strict)
+}
+
+fn element_value(element: &Element, strict: bool) -> Option<Value> {
+    let container = match element {
+        Element::Token(token) => return token_value(token, strict),
+        Element::Node(node) => node,
+    };
+    if strict && !container.children.iter().all(is_strict_trivia) {
+        return None;
+    }
+    let entries: Vec<&Node> = container.entries().collect();
+    // Strict JSON has no trailing comma
+    if strict
+        && entries
+            .last()
+            .is_some_and(|last| last.children.iter().any(is_comma))
+    {
+        return None;
+    }
+
+    match container.kind {
+        NodeKind::Object => {
+            let mut members = Map::new();
+            for entry in entries {
+                let key = match entry.key_token()? {
+                    token if strict && token.kind != TokenKind::String => return None,
+                    token if strict => serde_json::from_str(&token.text).ok()?,
+                    _ => entry.key()?,
+                };
+                // Later duplicates replace earlier ones, as serde_json does
+                members.insert(key, entry_value(entry, strict)?);
+            }
+            Some(Value::Object(members))
+        }
+        _ => entries
+            .into_iter()
+            .map(|entry| entry_value(entry, strict))
+            .collect::<Option<_>>()
+            .map(Value::Array),
+    }
+}
+
+// Strict values are checked by serde_json, and JSON5 ones are converted like to_strict does
+fn token_value(token: &Token, strict: bool) -> Option<Value> {
+    match token.kind {
+        TokenKind::String if strict => serde_json::from_str::<String>(&token.text)
+            .ok()
+            .map(Value::String),
+        TokenKind::String => json_parser::decode_string(&token.text)
+            .ok()
+            .map(Value::String),
+        TokenKind::Number if strict => serde_json::from_str::<Number>(&token.text)
+            .ok()
+            .map(Value::Number),
+        TokenKind::Number => {
+            serde_json::from_str(&json_parser::normalize_number(&token.text).ok()?).ok()
+        }
+        TokenKind::Ident => match token.text.as_str() {
+            "true" => Some(Value::Bool(true)),
+            "false" => Some(Value::Bool(false)),
+            "null" => Some(Value::Null),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+// Whitespace that strict JSON allows, or anything that isn't trivia
+fn is_strict_trivia(element: &Element) -> bool {
+    match element {
+        Element::Token(token) if token.kind == TokenKind::Whitespace => token
+            .text
+            .chars()
+            .all(|c| matches!(c, ' ' | '\t' | '\n' | '\r')),
+        _ => !element.is_trivia(),
+    }
+}
+
+fn collect_string_values(
+    node: &Node,
+    mut offset: usize,
+    strings: &mut Vec<(Range<usize>, String)>,
+) {
+    let value_index = node.value_index();
+    for (i, child) in node.children.iter().enumerate() {
+        match child {
+            Element::Token(token) if token.kind == TokenKind::String && value_index == Some(i) => {
+                if let Ok(text) = json_parser::decode_string(&token.text) {
+                    strings.push((offset..offset + token.text.len(), text));
+                }
+            }
+            Element::Node(child) => collect_string_values(child, offset, strings),
+            Element::Token(_) => {}
+        }
+        offset += child.len();
+    }
+}
+
+fn find_value_mut<'a>(node: &'a mut Node, segments: &[String]) -> Option<&'a mut Element> {
+    let value_index = node.value_index()?;
+    let value = &mut node.children[value_index];
+    let Some((segment, rest)) = segments.split_first() else {
+        return Some(value);
+    };
+    let Element::Node(container) = value else {
+        return None;
+    };
+
+    let is_object = container.kind == NodeKind::Object;
+    let (_, entry) = container
+        .children
+        .iter_mut()
+        .filter_map(|c| match c {
+            Element::Node(entry) => Some(entry),
+            Element::Token(_) => None,
+        })
+        .enumerate()
+        .filter(|(index, entry)| match is_object {
+            true => entry.key().as_deref() == Some(segment.as_str()),
+            false => segment.parse() == Ok(*index),
+        })
+        .last()?;
+    find_value_mut(entry, rest)
+}
+
+fn sort_element(element: &mut Element, compare: &dyn Fn(&str, &str) -> Ordering, recursive: bool) {
+    let Element::Node(node) = element else {
+        return;
+    };
+    if node.kind == NodeKind::Object {
+        sort_members(node, compare);
+    }
+    if recursive {
+        for child in node.children.iter_mut() {
+            if let Element::Node(entry) = child {
+                if let Some(value_index) = entry.value_index() {
+                    sort_element(&mut entry.children[value_index], compare, recursive);
+                }
+            }
+        }
+    }
+}
+
+fn sort_members(object: &mut Node, compare: &dyn Fn(&str, &str) -> Ordering) {
+    let close = object.children.pop().unwrap();
+    let mut children = std::mem::take(&mut object.children).into_iter();
+    let open = children.next().unwrap();
+
+    // Only trivia before the closing brace is left once the members are taken out
+    let (members, trailing): (Vec<Element>, Vec<Element>) =
+        children.partition(|c| matches!(c, Element::Node(_)));
+    let mut members: Vec<Node> = members
+        .into_iter()
+        .filter_map(|c| match c {
+            Element::Node(member) => Some(member),
+            Element::Token(_) => None,
+        })
+        .collect();
+    let had_trailing_comma = members
+        .last()
+        .is_some_and(|last| last.children.iter().any(is_comma));
+
+    // The whitespace around each member stays where it is, so only the members' contents
+    // (comments included) move. This keeps both one-per-line and single-line layouts intact.
+    let mut layout = Vec::with_capacity(members.len());
+    for member in members.iter_mut() {
+        member.children.retain(|c| !is_comma(c));
+        let leading = member
+            .children
+            .iter()
+            .take_while(|c| is_whitespace(c))
+            .count();
+        let trailing_start = member.children.len()
+            - member
+                .children
+                .iter()
+                .rev()
+                .take_while(|c| is_whitespace(c))
+                .count();
+        let trailing_ws = member.children.split_off(trailing_start.max(leading));
+        let leading_ws: Vec<Element> = member.children.drain(..leading).collect();
+        layout.push((leading_ws, trailing_ws));
+    }
+
+    members.sort_by_cached_key(|member| SortKey(member.key().unwrap_or_default(), compare));
+
+    let member_count = members.len();
+    for (index, (member, (leading_ws, trailing_ws))) in members.iter_mut().zip(layout).enumerate() {
+        if index + 1 < member_count || had_trailing_comma {
+            let comma_index = member
+                .value_index()
+                .map_or(member.children.len(), |i| i + 1);
+            member.children.insert(
+                comma_index,
+                Element::Token(Token {
+                    kind: TokenKind::Comma,
+                    text: ",".to_string(),
+                }),
+            );
+        }
+        member.children.splice(0..0, leading_ws);
+        member.children.extend(trailing_ws);
+    }
+
+    object.children.push(open);
+    object
+        .children
+        .extend(members.into_iter().map(Element::Node));
+    object.children.extend(trailing);
+    object.children.push(close);
+}
+
+fn is_whitespace(element: &Element) -> bool {
+    matches!(
+        element,
+        Element::Token(Token {
+            kind: TokenKind::Whitespace,
+            ..
+        })
+    )
+}
+
+fn is_comma(element: &Element) -> bool {
+    matches!(
+        element,
+        Element::Token(Token {
+            kind: TokenKind::Comma,
+            ..
+        })
+    )
+}
+
+// Lets sort_by_cached_key use a comparison function rather than Ord on the key itself
+struct SortKey<'a>(String, &'a dyn Fn(&str, &str) -> Ordering);
+
+impl PartialEq for SortKey<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for SortKey<'_> {}
+
+impl PartialOrd for SortKey<'_> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for SortKey<'_> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (self.1)(&self.0, &other.0)
+    }
+}
+
+pub fn parse(text: &str) -> Result<Document, String> {
+    let tokens = json_parser::tokenize(text)?;
+    let mut parser = Parser {
+        text,
+        tokens,
+        pos: 0,
+        depth: 0,
+    };
+
+    let mut children = parser.take_trivia();
+    children.push(parser.parse_value()?);
+    children.extend(parser.take_trivia());
+    if parser.pos < parser.tokens.len() {
+        return Err(parser.error("trailing characters"));
+    }
+
+    Ok(Document {
+        root: Node {
+            kind: NodeKind::Document,
+            children,
+        },
+    })
+}
+
+// Arrays and objects nested deeper than this are rejected, as serde_json does. Everything
+// that walks the tree recurses, so this also keeps it from running out of stack.
+const MAX_DEPTH: usize = 127;
+
+struct Parser<'a> {
+    text: &'a str,
+    tokens: Vec<json_parser::Token>,
+    pos: usize,
+    // Arrays and objects currently open
+    depth: usize,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<TokenKind> {
+        self.tokens.get(self.pos).map(|t| t.kind)
+    }
+
+    fn bump(&mut self) -> Element {
+        let token = &self.tokens[self.pos];
+        self.pos += 1;
+        Element::Token(Token {
+            kind: token.kind,
+            text: self.text[token.span.clone()].to_string(),
+        })
+    }
+
+    fn error(&self, message: &str) -> String {
+        match self.tokens.get(self.pos) {
+            Some(token) => format!(
+                "{} at {}",
+                message,
+                json_parser::describe_offset(self.text, token.span.start)
+            ),
+            None => format!("{} at end of input", message),
+        }
+    }
+
+    fn expect(&mut self, kind: TokenKind, message: &str) -> Result<Element, String> {
+        if self.peek() == Some(kind) {
+            Ok(self.bump())
+        } else {
+            Err(self.error(message))
+        }
+    }
+
+    fn take_trivia(&mut self) -> Vec<Element> {
+        let mut trivia = Vec::new();
+        while self.peek().is_some_and(TokenKind::is_trivia) {
+            trivia.push(self.bump());
+        }
+        trivia
+    }
+
+    // Comments on the same line as the end of an entry belong to that entry; anything from
+    // the next newline onwards belongs to the following entry (or the closing bracket).
+    fn take_same_line_trivia(&mut self) -> Vec<Element> {
+        let mut trivia = Vec::new();
+        while let Some(token) = self.tokens.get(self.pos) {
+            let same_line = match token.kind {
+                TokenKind::Whitespace => !self.text[token.span.clone()].contains('\n'),
+                kind => kind.is_trivia(),
+            };
+            if !same_line {
+                break;
+            }
+            trivia.push(self.bump());
+        }
+        trivia
+    }
+
+    fn parse_value(&mut self) -> Result<Element, String> {
+        match self.peek() {
+            Some(TokenKind::LBrace) => self.parse_container(NodeKind::Object),
+            Some(TokenKind::LBracket) => self.parse_container(NodeKind::Array),
+            Some(TokenKind::String | TokenKind::Number) => Ok(self.bump()),
+            Some(TokenKind::Ident)
+                if matches!(
+                    &self.text[self.tokens[self.pos].span.clone()],
+                    "true" | "false" | "null"
+                ) =>
+            {
+                Ok(self.bump())
+            }
+            _ => Err(self.error("expected value")),
+        }
+    }
+
+    fn parse_container(&mut self, kind: NodeKind) -> Result<Element, String> {
+        let (close, entry_kind) = match kind {
+            NodeKind::Object => (TokenKind::RBrace, NodeKind::Member),
+            _ => (TokenKind::RBracket, NodeKind::Item),
+        };
+        if self.depth == MAX_DEPTH {
+            return Err(self.error(&format!("exceeds maximum nesting of {}", MAX_DEPTH)));
+        }
+        self.depth += 1;
+        let mut children = vec![self.bump()];
+        let mut expect_entry = true;
+
+        loop {
+            let leading = self.take_trivia();
+            if self.peek() == Some(close) {
+                children.extend(leading);
+                children.push(self.bump());
+                break;
+            }
+            if !expect_entry {
+                return Err(self.error("expected comma or closing bracket"));
+            }
+
+            let mut entry = leading;
+            if entry_kind == NodeKind::Member {
+                match self.peek() {
+                    Some(TokenKind::String | TokenKind::Ident) => entry.push(self.bump()),
+                    _ => return Err(self.error("expected key")),
+                }
+                entry.extend(self.take_trivia());
+                entry.push(self.expect(TokenKind::Colon, "expected colon")?);
+                entry.extend(self.take_trivia());
+            }
+            entry.push(self.parse_value()?);
+
+            let before_comma = self.pos;
+            let trivia = self.take_trivia();
+            if self.peek() == Some(TokenKind::Comma) {
+                entry.extend(trivia);
+                entry.push(self.bump());
+                entry.extend(self.take_same_line_trivia());
+            } else {
+                self.pos = before_comma;
+                entry.extend(self.take_same_line_trivia());
+                expect_entry = false;
+            }
+
+            children.push(Element::Node(Node {
+                kind: entry_kind,
+                children: entry,
+            }));
+        }
+
+        self.depth -= 1;
+        Ok(Element::Node(Node { kind, children }))
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
+pub enum IndentStyle {
+    #[default]
+    Spaces,
+    Tabs,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FormatOptions {
+    pub indent_style: IndentStyle,
+    // Number of spaces per level; ignored when indenting with tabs
+    pub indent_width: u32,
+    // Arrays and objects that fit within this many columns are kept on one line; 0 disables
+    pub max_line_width: u32,
+    pub space_after_colon: bool,
+    pub trailing_newline: bool,
+    pub sort_keys: bool,
+}
+
+impl Default for FormatOptions {
+    fn default() -> Self {
+        FormatOptions {
+            indent_style: IndentStyle::Spaces,
+            indent_width: 2,
+            max_line_width: 0,
+            space_after_colon: true,
+            trailing_newline: false,
+            sort_keys: false,
+        }
+    }
+}
+
+pub fn prettify(document: &Document, options: &FormatOptions) -> String {
+    let indent = match options.indent_style {
+        IndentStyle::Spaces => " ".repeat(options.indent_width as usize),
+        IndentStyle::Tabs => "\t".to_string(),
+    };
+    let mut printer = Printer {
+        out: String::new(),
+        layout: Layout::Pretty,
+        indent,
+        max_line_width: options.max_line_width as usize,
+        space_after_colon: options.space_after_colon,
+        sort_keys: options.sort_keys,
+    };
+    printer.write_document(document);
+    if options.trailing_newline {
+        printer.out.push('\n');
+    }
+    printer.out
+}
+
+pub fn minify(document: &Document) -> String {
+    let mut printer = Printer {
+        out: String::new(),
+        layout: Layout::Compact,
+        indent: String::new(),
+        max_line_width: 0,
+        space_after_colon: false,
+        sort_keys: false,
+    };
+    printer.write_document(document);
+    printer.out
+}
+
+// A value laid out like prettify, to replace the text at `at`: lines after the first are
+// indented to match the line it's on, and there is no trailing newline
+pub fn prettify_in_place(text: &str, at: usize, v: &Value, options: &FormatOptions) -> String {
+    let line_start = text[..at].rfind('\n').map_or(0, |i| i + 1);
+    let indent: String = text[line_start..]
+        .chars()
+        .take_while(|c| *c == ' ' || *c == '\t')
+        .collect();
+    // Values nested deeper than a document may be, e.g. after deep unescaping, get the default
+    // layout
+    let pretty = match parse(&v.to_string()) {
+        Ok(document) => prettify(&document, options),
+        Err(_) => serde_json::to_string_pretty(v).unwrap(),
+    };
+    pretty.trim_end().replace('\n', &format!("\n{}", indent))
+}
+
+// Whether an element without comments could fit in the width on one line. Only its tokens
+// are counted, taking them from the width, and it stops as soon as they don't fit so that
+// checking every nested container doesn't cost more than the width each time.
+fn may_fit_inline(element: &Element, width: &mut usize) -> bool {
+    match element {
+        Element::Token(_) if element.is_comment() => false,
+        Element::Token(token) if token.kind.is_trivia() || token.kind == TokenKind::Comma => true,
+        Element::Token(token) => {
+            let len = token.text.chars().take(*width + 1).count();
+            match width.checked_sub(len) {
+                Some(rest) => {
+                    *width = rest;
+                    true
+                }
+                None => false,
+            }
+        }
+        Element::Node(node) => node
+            .children
+            .iter()
+            .all(|child| may_fit_inline(child, width)),
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Layout {
+    // One entry per line
+    Pretty,
+    // A short container on a single line, with spaces after commas
+    Inline,
+    // No whitespace at all
+    Compact,
+}
+
+// Re-emits the tree with regenerated whitespace. Comments, keys and scalar values are
+// copied verbatim, so number literals like `1e2` and string escapes are left alone.
+struct Printer {
+    out: String,
+    layout: Layout,
+    indent: String,
+    max_line_width: usize,
+    space_after_colon: bool,
+    sort_keys: bool,
+}
+
+impl Printer {
+    fn newline(&mut self, depth: usize) {
+        if self.layout == Layout::Pretty {
+            self.out.push('\n');
+            self.out.push_str(&self.indent.repeat(depth));
+        }
+    }
+
+    fn column(&self) -> usize {
+        let line_start = self.out.rfind('\n').map_or(0, |n| n + 1);
+        self.out[line_start..]
+            .chars()
+            .map(|c| if c == '\t' { 4 } else { 1 })
+            .sum()
+    }
+
+    fn push_comment(&mut self, token: &Token) {
+        if self.layout == Layout::Compact && token.kind == TokenKind::LineComment {
+            // A line comment would swallow the rest of a minified document
+            self.out.push_str("/*");
+            self.out.push_str(&token.text[2..].replace("*/", "* /"));
+            self.out.push_str("*/");
+        } else {
+            self.out.push_str(&token.text);
+        }
+    }
+
+    // A comment in the middle of a line, which must not swallow what follows it
+    fn write_inline_comment(&mut self, token: &Token, depth: usize) {
+        if self.layout == Layout::Compact {
+            self.push_comment(token);
+            return;
+        }
+        if !self.out.ends_with(' ') {
+            self.out.push(' ');
+        }
+        self.push_comment(token);
+        match token.kind {
+            TokenKind::LineComment => self.newline(depth),
+            _ => self.out.push(' '),
+        }
+    }
+
+    fn write_document(&mut self, document: &Document) {
+        let mut seen_value = false;
+        for child in &document.root.children {
+            match child {
+                Element::Token(token) if token.kind.is_trivia() => {
+                    if child.is_comment() {
+                        if seen_value {
+                            self.newline(0);
+                        }
+                        self.push_comment(token);
+                        if !seen_value {
+                            self.newline(0);
+                        }
+                    }
+                }
+                value => {
+                    self.write_value(value, 0);
+                    seen_value = true;
+                }
+            }
+        }
+    }
+
+    fn write_value(&mut self, value: &Element, depth: usize) {
+        match value {
+            Element::Token(token) => self.out.push_str(&token.text),
+            Element::Node(node) => {
+                if !self.try_write_inline(value, depth) {
+                    self.write_container(node, depth);
+                }
+            }
+        }
+    }
+
+    // Keep short containers without comments on a single line, if they fit
+    fn try_write_inline(&mut self, value: &Element, depth: usize) -> bool {
+        if self.layout != Layout::Pretty || self.max_line_width == 0 {
+            return false;
+        }
+        let Element::Node(node) = value else {
+            return false;
+        };
+        // Leave room for a trailing comma
+        let mut width = self
+            .max_line_width
+            .saturating_sub(self.column())
+            .saturating_sub(1);
+        if !may_fit_inline(value, &mut width) {
+            return false;
+        }
+
+        let mut inline_printer = Printer {
+            out: String::new(),
+            layout: Layout::Inline,
+            indent: String::new(),
+            max_line_width: 0,
+            space_after_colon: self.space_after_colon,
+            sort_keys: self.sort_keys,
+        };
+        inline_printer.write_container(node, depth);
+
+        if self.column() + inline_printer.out.chars().count() < self.max_line_width {
+            self.out.push_str(&inline_printer.out);
+            true
+        } else {
+            false
+        }
+    }
+
+    fn write_container(&mut self, node: &Node, depth: usize) {
+        let (open, close) = match node.kind {
+            NodeKind::Object => ("{", "}"),
+            _ => ("[", "]"),
+        };
+        let mut entries: Vec<&Node> = node.entries().collect();
+        let has_comments = node.children.iter().any(Element::is_comment);
+
+        self.out.push_str(open);
+        if entries.is_empty() && !has_comments {
+            self.out.push_str(close);
+            return;
+        }
+
+        if self.sort_keys && node.kind == NodeKind::Object {
+            entries.sort_by_cached_key(|entry| entry.key());
+        }
+        let entry_count = entries.len();
+        for (index, entry) in entries.into_iter().enumerate() {
+            self.write_entry(entry, depth + 1, index == 0, index + 1 < entry_count);
+        }
+
+        // Comments between the last entry and the closing bracket
+        for child in &node.children {
+            if let Element::Token(token) = child {
+                if child.is_comment() {
+                    self.newline(depth + 1);
+                    self.push_comment(token);
+                }
+            }
+        }
+
+        self.newline(depth);
+        self.out.push_str(close);
+    }
+
+    fn write_entry(&mut self, entry: &Node, depth: usize, first: bool, needs_comma: bool) {
+        let value_index = entry.value_index().unwrap_or(entry.children.len());
+        let key_index = match entry.key_token() {
+            Some(_) => entry
+                .children
+                .iter()
+                .position(|c| !c.is_trivia())
+                .unwrap_or(value_index),
+            None => value_index,
+        };
+
+        // Comments before the entry get their own lines
+        for child in &entry.children[..key_index] {
+            if let Element::Token(token) = child {
+                if child.is_comment() {
+                    self.newline(depth);
+                    self.push_comment(token);
+                }
+            }
+        }
+        self.newline(depth);
+        if self.layout == Layout::Inline && !first {
+            self.out.push(' ');
+        }
+
+        // Key, colon and any comments squeezed in between them and the value
+        for child in &entry.children[key_index..value_index] {
+            if let Element::Token(token) = child {
+                match token.kind {
+                    TokenKind::Colon => {
+                        self.out.push(':');
+                        if self.space_after_colon {
+                            self.out.push(' ');
+                        }
+                    }
+                    _ if child.is_comment() => self.write_inline_comment(token, depth),
+                    _ if !child.is_trivia() => self.out.push_str(&token.text),
+                    _ => {}
+                }
+            }
+        }
+
+        if let Some(value) = entry.children.get(value_index) {
+            self.write_value(value, depth);
+        }
+        if needs_comma {
+            self.out.push(',');
+        }
+
+        // Comments after the value stay on the same line
+        for child in entry.children.iter().skip(value_index + 1) {
+            if let Element::Token(token) = child {
+                if child.is_comment() {
+                    if self.layout != Layout::Compact {
+                        self.out.push(' ');
+                    }
+                    self.push_comment(token);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn segments(pointer: &[&str]) -> Vec<String> {
+        pointer.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn round_trips_byte_for_byte() {
+        let texts = [
+            "{\"a\": 1, \"b\": [true, false, null]}",
+            // JSONC
+            "// config\n{\n  /* block */ \"a\": 1, // after\n  \"b\": [1, 2,],\n}\n",
+            // JSON5
+            "{unquoted: 'single', hex: 0xFF, pos: +1, half: .5, big: Infinity, nan: NaN,\n  \
+             'esc\\'aped': \"line\\\ncontinued\", \u{a0}nbsp: [1e3, -0.0,],}",
+            "  \"just a string\"  ",
+            "\r\n[\r\n\t1 /* one */,\r\n\t2\r\n]\r\n// trailing",
+        ];
+        for text in texts {
+            assert_eq!(parse(text).unwrap().to_string(), text);
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_text() {
+        for text in [
+            "",
+            "[1 2]",
+            "{\"a\" 1}",
+            "[1,,2]",
+            "[,]",
+            "{1: 2}",
+            "[undefined]",
+            "[1] 2",
+        ] {
+            assert!(parse(text).is_err(), "{:?} was accepted", text);
+        }
+    }
+
+    #[test]
+    fn nesting_limit() {
+        let nested = |depth| "[".repeat(depth) + &"]".repeat(depth);
+        assert!(parse(&nested(MAX_DEPTH)).is_ok());
+        assert_eq!(
+            parse(&nested(MAX_DEPTH + 1)).err().as_deref(),
+            Some("exceeds maximum nesting of 127 at line 1 column 128")
+        );
+        // Far deeper than would fit on the stack
+        assert!(parse(&nested(100_000)).is_err());
+        assert!(parse(&"{\"a\":".repeat(100_000)).is_err());
+    }
+
+    #[test]
+    fn value_spans() {
+        let text = "{\"a\": 1, /* c */ \"b\": {\"c\": [10, \"x\"]}, \"a\": 2}";
+        let document = parse(text).unwrap();
+        let span_text = |pointer: &[&str]| {
+            document
+                .value_span(&segments(pointer))
+                .map(|span| &text[span])
+        };
+        assert_eq!(span_text(&[]), Some(text));
+        assert_eq!(span_text(&["b", "c", "1"]), Some("\"x\""));
+        assert_eq!(span_text(&["b", "c"]), Some("[10, \"x\"]"));
+        // The last duplicate wins, as it does in a serde_json::Value
+        assert_eq!(span_text(&["a"]), Some("2"));
+        assert_eq!(span_text(&["b", "c", "2"]), None);
+        assert_eq!(span_text(&["missing"]), None);
+    }
+
+    fn pretty(text: &str, options: &FormatOptions) -> String {
+        prettify(&parse(text).unwrap(), options)
+    }
+
+    #[test]
+    fn prettify_defaults() {
+        let text = "{\"b\":[1,2],\"a\":{},\"c\":[]}";
+        assert_eq!(
+            pretty(text, &FormatOptions::default()),
+            "{\n  \"b\": [\n    1,\n    2\n  ],\n  \"a\": {},\n  \"c\": []\n}"
+        );
+    }
+
+    #[test]
+    fn prettify_indent_style_and_width() {
+        let text = "{\"a\":[1]}";
+        let tabs = FormatOptions {
+            indent_style: IndentStyle::Tabs,
+            // Ignored with tabs
+            indent_width: 8,
+            ..FormatOptions::default()
+        };
+        assert_eq!(pretty(text, &tabs), "{\n\t\"a\": [\n\t\t1\n\t]\n}");
+        let four = FormatOptions {
+            indent_width: 4,
+            ..FormatOptions::default()
+        };
+        assert_eq!(pretty(text, &four), "{\n    \"a\": [\n        1\n    ]\n}");
+        let none = FormatOptions {
+            indent_width: 0,
+            ..FormatOptions::default()
+        };
+        assert_eq!(pretty(text, &none), "{\n\"a\": [\n1\n]\n}");
+    }
+
+    #[test]
+    fn prettify_colon_and_trailing_newline() {
+        let options = FormatOptions {
+            space_after_colon: false,
+            trailing_newline: true,
+            ..FormatOptions::default()
+        };
+        assert_eq!(pretty("{\"a\" : 1}", &options), "{\n  \"a\":1\n}\n");
+        assert_eq!(pretty("1", &options), "1\n");
+    }
+
+    #[test]
+    fn prettify_sort_keys() {
+        let options = FormatOptions {
+            sort_keys: true,
+            ..FormatOptions::default()
+        };
+        assert_eq!(
+            pretty("{\"b\": {\"d\": 1, \"c\": 2}, \"a\": [{\"f\": 1, \"e\": 2}]}", &options),
+            "{\n  \"a\": [\n    {\n      \"e\": 2,\n      \"f\": 1\n    }\n  ],\n  \"b\": {\n    \"c\": 2,\n    \"d\": 1\n  }\n}"
+        );
+    }
+
+    #[test]
+    fn prettify_max_line_width() {
+        let width = |max_line_width| FormatOptions {
+            max_line_width,
+            ..FormatOptions::default()
+        };
+        // Room is left for a trailing comma, so a line must be shorter than the width
+        assert_eq!(pretty("[1,2]", &width(7)), "[1, 2]");
+        assert_eq!(pretty("[1,2]", &width(6)), "[\n  1,\n  2\n]");
+
+        // Nested containers count from the column they start at
+        let text = "{\"a\":[1,2],\"bb\":{\"c\":true}}";
+        assert_eq!(
+            pretty(text, &width(20)),
+            "{\n  \"a\": [1, 2],\n  \"bb\": {\"c\": true}\n}"
+        );
+        assert_eq!(
+            pretty(text, &width(19)),
+            "{\n  \"a\": [1, 2],\n  \"bb\": {\n    \"c\": true\n  }\n}"
+        );
+        assert_eq!(
+            pretty(text, &width(13)),
+            "{\n  \"a\": [\n    1,\n    2\n  ],\n  \"bb\": {\n    \"c\": true\n  }\n}"
+        );
+
+        // Tabs count as 4 columns
+        let tabs = FormatOptions {
+            indent_style: IndentStyle::Tabs,
+            ..width(16)
+        };
+        assert_eq!(
+            pretty("{\"a\":[1,2],\"b\":0}", &tabs),
+            "{\n\t\"a\": [1, 2],\n\t\"b\": 0\n}"
+        );
+        let tabs = FormatOptions {
+            indent_style: IndentStyle::Tabs,
+            ..width(15)
+        };
+        assert_eq!(
+            pretty("{\"a\":[1,2],\"b\":0}", &tabs),
+            "{\n\t\"a\": [\n\t\t1,\n\t\t2\n\t],\n\t\"b\": 0\n}"
+        );
+
+        // Comments keep a container on several lines, however short
+        assert_eq!(
+            pretty("[1, /* one */ 2]", &width(80)),
+            "[\n  1, /* one */\n  2\n]"
+        );
+        // A long string stops the check early, and doesn't fit whatever comes after it
+        let long = format!("[\"{}\", []]", "x".repeat(1000));
+        assert_eq!(
+            pretty(&long, &width(80)),
+            format!("[\n  \"{}\",\n  []\n]", "x".repeat(1000))
+        );
+    }
+
+    #[test]
+    fn prettify_in_place_indents_to_the_line() {
+        let text = "{\n\t  \"a\": \"{}\",\n  \"b\": 1\n}";
+        let at = text.find("\"{}\"").unwrap();
+        let v = serde_json::json!({"x": [1], "y": {}});
+        assert_eq!(
+            prettify_in_place(text, at, &v, &FormatOptions::default()),
+            "{\n\t    \"x\": [\n\t      1\n\t    ],\n\t    \"y\": {}\n\t  }"
+        );
+        assert_eq!(
+            prettify_in_place("[1]", 1, &v, &FormatOptions::default()),
+            "{\n  \"x\": [\n    1\n  ],\n  \"y\": {}\n}"
+        );
+
+        // Too deep to be a document, so laid out the default way
+        let mut deep = serde_json::json!(1);
+        for _ in 0..200 {
+            deep = serde_json::json!([deep]);
+        }
+        let expected = serde_json::to_string_pretty(&deep).unwrap();
+        assert_eq!(
+            prettify_in_place("", 0, &deep, &FormatOptions::default()),
+            expected
+        );
+    }
+
+    fn sorted(text: &str, pointer: &[&str], recursive: bool) -> Option<String> {
+        let mut document = parse(text).unwrap();
+        document
+            .sort_keys(&segments(pointer), &|a, b| a.cmp(b), recursive)
+            .then(|| document.to_string())
+    }
+
+    #[test]
+    fn sort_keys_moves_comments_with_members() {
+        let text = "{\n  // about b\n  \"b\": 1, // after b\n  /* about a */ \"a\": [3, 1],\n}";
+        assert_eq!(
+            sorted(text, &[], false).as_deref(),
+            Some("{\n  /* about a */ \"a\": [3, 1],\n  // about b\n  \"b\": 1, // after b\n}")
+        );
+    }
+
+    #[test]
+    fn sort_keys_keeps_commas_where_they_were() {
+        // No trailing comma stays without one, and a single line stays a single line
+        assert_eq!(
+            sorted("{\"c\": 1, \"a\": 2, \"b\": 3}", &[], false).as_deref(),
+            Some("{\"a\": 2, \"b\": 3, \"c\": 1}")
+        );
+        assert_eq!(
+            sorted("{b: 1, a: 2,}", &[], false).as_deref(),
+            Some("{a: 2, b: 1,}")
+        );
+    }
+
+    #[test]
+    fn sort_keys_only_where_asked() {
+        let text = "{\"z\": {\"y\": 1, \"x\": [{\"b\": 1, \"a\": 2}]}, \"m\": 0}";
+        assert_eq!(
+            sorted(text, &["z"], false).as_deref(),
+            Some("{\"z\": {\"x\": [{\"b\": 1, \"a\": 2}], \"y\": 1}, \"m\": 0}")
+        );
+        assert_eq!(
+            sorted(text, &[], true).as_deref(),
+            Some("{\"m\": 0, \"z\": {\"x\": [{\"a\": 2, \"b\": 1}], \"y\": 1}}")
+        );
+        assert_eq!(sorted(text, &["missing"], true), None);
+        // Arrays are left in order
+        assert_eq!(sorted("[3, 1, 2]", &[], true).as_deref(), Some("[3, 1, 2]"));
+    }
+
+    #[test]
+    fn minify_keeps_comments_as_block_comments() {
+        let document = parse("{\n  // line\n  \"a\": 1, /* block */\n  \"b\": [1, 2,]\n}").unwrap();
+        assert_eq!(
+            minify(&document),
+            "{/* line*/\"a\":1,/* block */\"b\":[1,2]}"
+        );
+
+        // The `*/` inside a line comment would otherwise end the block comment early
+        let document = parse("[1, // a */ b\n2]").unwrap();
+        let minified = minify(&document);
+        assert_eq!(minified, "[1,/* a * / b*/2]");
+        assert_eq!(
+            json_parser::parse(&minified, ParseMode::Lenient),
+            Ok(serde_json::json!([1, 2]))
+        );
+    }
+}
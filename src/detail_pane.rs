@@ -0,0 +1,399 @@
+// Shows what the value selected in the tree contains, when it is more than plain text:
+// nested JSON, base64, timestamps, colours and URLs
+use crate::json_editor::{self, JsonEditorState};
+use crate::nested_json::parse_nested_json;
+use crate::preferences::PreferencesState;
+use crate::tree_view::{self, TreeViewState};
+use base64::engine::general_purpose::{STANDARD, STANDARD_NO_PAD, URL_SAFE, URL_SAFE_NO_PAD};
+use base64::Engine;
+use gtk::gdk::RGBA;
+use gtk::glib::{DateTime, Propagation, TimeZone};
+use gtk::prelude::*;
+use gtk::{
+    Align, ApplicationWindow, Button, CellRendererPixbuf, CellRendererText, DrawingArea, Label,
+    LinkButton, Orientation, PolicyType, ScrolledWindow, ShadowType, TreePath, TreeStore, TreeView,
+    TreeViewColumn,
+};
+use serde_json::Value;
+
+// Bytes shown of base64 that does not decode to text
+const MAX_HEX_BYTES: usize = 64;
+
+#[derive(Clone)]
+pub struct DetailPaneState {
+    pub container: ScrolledWindow,
+    sections: gtk::Box,
+    // Replaces the selected string with the JSON it holds
+    drill_in: Button,
+}
+
+pub fn factory_detail_pane() -> DetailPaneState {
+    let sections = gtk::Box::builder()
+        .orientation(Orientation::Vertical)
+        .spacing(6)
+        .margin(8)
+        .build();
+
+    // Hidden until something worth showing is selected
+    let container = ScrolledWindow::builder()
+        .no_show_all(true)
+        .shadow_type(ShadowType::In)
+        .hscrollbar_policy(PolicyType::Never)
+        .build();
+    container.add(&sections);
+
+    let drill_in = Button::builder()
+        .label("Replace the string with this JSON")
+        .halign(Align::Start)
+        .build();
+
+    DetailPaneState {
+        container,
+        sections,
+        drill_in,
+    }
+}
+
+pub fn attach_listeners(
+    detail_pane: DetailPaneState,
+    win: &ApplicationWindow,
+    json_editor: JsonEditorState,
+    tree_view: TreeViewState,
+    preferences: PreferencesState,
+) {
+    detail_pane.drill_in.connect_clicked({
+        let win = win.clone();
+        let tree_view = tree_view.clone();
+        move |_| {
+            drill_in_action(
+                win.clone(),
+                json_editor.clone(),
+                tree_view.clone(),
+                preferences.clone(),
+            )
+        }
+    });
+
+    tree_view
+        .tree_view
+        .selection()
+        .connect_changed(move |_| update_detail_pane(detail_pane.clone(), tree_view.clone()));
+}
+
+fn update_detail_pane(detail_pane: DetailPaneState, tree_view: TreeViewState) {
+    for child in detail_pane.sections.children() {
+        detail_pane.sections.remove(&child);
+    }
+
+    if let Some((value, value_type)) = tree_view::selected_value(tree_view) {
+        if value_type == "string" {
+            add_string_sections(&detail_pane, &value);
+        }
+        if value_type == "string" || value_type == "number" {
+            if let Some(date_time) = parse_timestamp(&value) {
+                add_section(
+                    &detail_pane.sections,
+                    "Timestamp",
+                    &text_label(&describe_timestamp(&date_time)),
+                );
+            }
+        }
+    }
+
+    if detail_pane.sections.children().is_empty() {
+        detail_pane.container.hide();
+    } else {
+        detail_pane.sections.show_all();
+        detail_pane.container.show_all();
+    }
+}
+
+fn add_string_sections(detail_pane: &DetailPaneState, value: &str) {
+    let sections = &detail_pane.sections;
+    if let Some(nested) = parse_nested_json(value) {
+        add_section(sections, "Nested JSON", &nested_json_tree(&nested));
+        sections.add(&detail_pane.drill_in);
+    }
+
+    if let Some(bytes) = decode_base64(value) {
+        match String::from_utf8(bytes) {
+            Ok(text) if is_printable(&text) => match parse_nested_json(&text) {
+                Some(nested) => add_section(sections, "Base64 (JSON)", &nested_json_tree(&nested)),
+                None => add_section(sections, "Base64", &text_label(&text)),
+            },
+            Ok(text) => describe_binary(sections, text.as_bytes(), value),
+            Err(e) => describe_binary(sections, e.as_bytes(), value),
+        }
+    }
+
+    if let Some(colour) = parse_colour(value) {
+        add_section(sections, "Colour", &colour_swatch(colour));
+    }
+
+    if (value.starts_with("http://") || value.starts_with("https://"))
+        && !value.contains(char::is_whitespace)
+    {
+        let link = LinkButton::with_label(value, "Open in browser");
+        link.set_halign(Align::Start);
+        add_section(sections, "URL", &link);
+    }
+}
+
+fn add_section(sections: &gtk::Box, title: &str, content: &impl IsA<gtk::Widget>) {
+    let heading = Label::builder()
+        .label(format!("<b>{}</b>", title))
+        .use_markup(true)
+        .halign(Align::Start)
+        .build();
+    sections.add(&heading);
+    sections.add(content);
+}
+
+fn text_label(text: &str) -> Label {
+    let label = Label::builder()
+        .label(text)
+        .selectable(true)
+        .wrap(true)
+        .xalign(0.0)
+        .build();
+    label.style_context().add_class("monospace");
+    label
+}
+
+// Laid out like the main tree, with the same counts of children and types
+pub fn nested_json_tree(nested: &Value) -> TreeView {
+    let model = TreeStore::new(&[
+        String::static_type(),
+        String::static_type(),
+        String::static_type(),
+    ]);
+    append_nested_value(&model, None, "🔥", nested);
+
+    let tree_view = TreeView::builder()
+        .model(&model)
+        .height_request(160)
+        .build();
+
+    let column = TreeViewColumn::new();
+    column.set_title("Key");
+    column.set_resizable(true);
+    let cell = CellRendererText::new();
+    gtk::prelude::CellLayoutExt::pack_start(&column, &cell, true);
+    gtk::prelude::TreeViewColumnExt::add_attribute(&column, &cell, "text", 0);
+    tree_view.append_column(&column);
+
+    let column = TreeViewColumn::new();
+    column.set_title("Type");
+    let icon_cell = CellRendererPixbuf::new();
+    let type_cell = CellRendererText::new();
+    gtk::prelude::CellLayoutExt::pack_start(&column, &icon_cell, false);
+    gtk::prelude::CellLayoutExt::pack_start(&column, &type_cell, true);
+    gtk::prelude::TreeViewColumnExt::add_attribute(&column, &type_cell, "text", 1);
+    gtk::prelude::TreeViewColumnExt::set_cell_data_func(
+        &column,
+        &icon_cell,
+        Some(Box::new(|_, cell, model, iter| {
+            let value_type = model.value(iter, 1).get::<String>().ok();
+            cell.set_property(
+                "icon-name",
+                value_type.as_deref().and_then(tree_view::type_icon),
+            );
+        })),
+    );
+    tree_view.append_column(&column);
+
+    let column = TreeViewColumn::new();
+    column.set_title("Value");
+    column.set_resizable(true);
+    let cell = CellRendererText::new();
+    gtk::prelude::CellLayoutExt::pack_start(&column, &cell, true);
+    gtk::prelude::TreeViewColumnExt::add_attribute(&column, &cell, "text", 2);
+    tree_view.append_column(&column);
+
+    tree_view.expand_row(&TreePath::new_first(), false);
+
+    tree_view
+}
+
+fn append_nested_value(model: &TreeStore, parent: Option<&gtk::TreeIter>, key: &str, v: &Value) {
+    let iter = model.append(parent);
+    let (value, value_type) = tree_view::describe_value(v);
+    model.set_value(&iter, 0, &key.to_value());
+    model.set_value(&iter, 1, &value_type.to_value());
+    model.set_value(
+        &iter,
+        2,
+        &tree_view::truncate_for_display(&value).to_value(),
+    );
+
+    match v {
+        Value::Object(map) => {
+            for (k, val) in map {
+                append_nested_value(model, Some(&iter), k, val);
+            }
+        }
+        Value::Array(arr) => {
+            for (i, val) in arr.iter().enumerate() {
+                append_nested_value(model, Some(&iter), &format!("[{}]", i), val);
+            }
+        }
+        _ => {}
+    }
+}
+
+// Replace the selected string with the JSON it holds, and expand it in the tree
+fn drill_in_action(
+    win: ApplicationWindow,
+    json_editor: JsonEditorState,
+    tree_view: TreeViewState,
+    preferences: PreferencesState,
+) {
+    let Some(pointer) = tree_view::selected_pointer(tree_view.clone()) else {
+        return;
+    };
+    let nested = tree_view::selected_value(tree_view.clone())
+        .filter(|(_, value_type)| value_type == "string")
+        .and_then(|(value, _)| parse_nested_json(&value));
+    let Some(nested) = nested else {
+        return;
+    };
+
+    tree_view::expand_when_parsed(tree_view, &pointer);
+    json_editor::replace_with_nested_action(
+        win,
+        json_editor,
+        &pointer,
+        &nested,
+        &preferences.borrow().format,
+    );
+}
+
+fn decode_base64(value: &str) -> Option<Vec<u8>> {
+    // Short strings and plain numbers decode by accident too often to be worth showing
+    if value.len() < 8 || value.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    if !value
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || "+/-_=".contains(c))
+    {
+        return None;
+    }
+
+    [STANDARD, STANDARD_NO_PAD, URL_SAFE, URL_SAFE_NO_PAD]
+        .iter()
+        .find_map(|engine| engine.decode(value).ok())
+}
+
+fn is_printable(text: &str) -> bool {
+    text.chars().all(|c| !c.is_control() || c.is_whitespace())
+}
+
+fn describe_binary(sections: &gtk::Box, bytes: &[u8], value: &str) {
+    // Any word of the right length decodes to something, so only show longer values
+    if value.len() < 16 {
+        return;
+    }
+
+    let hex: Vec<String> = bytes
+        .iter()
+        .take(MAX_HEX_BYTES)
+        .map(|b| format!("{:02x}", b))
+        .collect();
+    let ellipsis = if bytes.len() > MAX_HEX_BYTES {
+        " …"
+    } else {
+        ""
+    };
+    add_section(
+        sections,
+        "Base64",
+        &text_label(&format!(
+            "{} bytes of binary data\n{}{}",
+            bytes.len(),
+            hex.join(" "),
+            ellipsis
+        )),
+    );
+}
+
+// Unix timestamps in seconds or milliseconds, or ISO 8601 dates with a time
+fn parse_timestamp(value: &str) -> Option<DateTime> {
+    let (whole, fraction) = value.split_once('.').unwrap_or((value, ""));
+    if !whole.is_empty() && whole.chars().all(|c| c.is_ascii_digit()) {
+        if !fraction.chars().all(|c| c.is_ascii_digit()) {
+            return None;
+        }
+        let n: i64 = whole.parse().ok()?;
+        let fraction: f64 = format!("0.{}", fraction).parse().unwrap_or(0.0);
+        // Only lengths that are plausible for dates between 1973 and 2286
+        return match whole.len() {
+            9 | 10 => DateTime::from_unix_utc(n).ok()?.add_seconds(fraction).ok(),
+            12 | 13 => DateTime::from_unix_utc(n / 1000)
+                .ok()?
+                .add_seconds((n % 1000) as f64 / 1000.0)
+                .ok(),
+            _ => None,
+        };
+    }
+
+    // Times without an offset are taken to be UTC
+    DateTime::from_iso8601(value, Some(&TimeZone::utc())).ok()
+}
+
+fn describe_timestamp(date_time: &DateTime) -> String {
+    let format = |date_time: Result<DateTime, _>| {
+        date_time
+            .and_then(|d| d.format("%Y-%m-%d %H:%M:%S %Z"))
+            .map(|s| s.to_string())
+            .unwrap_or_default()
+    };
+    format!(
+        "Local: {}\nUTC:   {}\nUnix:  {}",
+        format(date_time.to_local()),
+        format(date_time.to_utc()),
+        date_time.to_unix()
+    )
+}
+
+// #rgb, #rgba, #rrggbb or #rrggbbaa
+fn parse_colour(value: &str) -> Option<RGBA> {
+    let hex = value.strip_prefix('#')?;
+    if !matches!(hex.len(), 3 | 4 | 6 | 8) || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+
+    let digits: Vec<u32> = hex.chars().filter_map(|c| c.to_digit(16)).collect();
+    let channels: Vec<f64> = if hex.len() <= 4 {
+        digits.iter().map(|d| (d * 17) as f64 / 255.0).collect()
+    } else {
+        digits
+            .chunks(2)
+            .map(|pair| (pair[0] * 16 + pair[1]) as f64 / 255.0)
+            .collect()
+    };
+
+    Some(RGBA::new(
+        channels[0],
+        channels[1],
+        channels[2],
+        channels.get(3).copied().unwrap_or(1.0),
+    ))
+}
+
+fn colour_swatch(colour: RGBA) -> gtk::Box {
+    let swatch = DrawingArea::builder()
+        .width_request(48)
+        .height_request(24)
+        .build();
+    swatch.connect_draw(move |_, cr| {
+        cr.set_source_rgba(colour.red(), colour.green(), colour.blue(), colour.alpha());
+        let _ = cr.paint();
+        Propagation::Stop
+    });
+
+    let h_box = gtk::Box::new(Orientation::Horizontal, 8);
+    h_box.add(&swatch);
+    h_box.add(&text_label(&colour.to_str()));
+    h_box
+}
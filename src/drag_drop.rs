@@ -0,0 +1,147 @@
+// Files, links and text dropped onto the editor or tree are opened, and rows of the tree can
+// be dragged out as JSON
+use crate::json_editor::JsonEditorState;
+use crate::recent::RecentState;
+use crate::tree_view::TreeViewState;
+use crate::{file_mgt, json_editor, tree_view};
+use gtk::gdk::{self, DragAction, ModifierType};
+use gtk::glib;
+use gtk::prelude::*;
+use gtk::{ApplicationWindow, DestDefaults, TargetFlags, TargetList, TextView};
+
+// Info of each drop target, telling the received data apart
+const TARGET_TEXT: u32 = 0;
+const TARGET_URIS: u32 = 1;
+
+// The editor keeps its own targets, so text is still dropped where the pointer is, but
+// file managers offer paths as text too so URIs must come first
+fn set_editor_targets(text_view: &TextView) {
+    let Some(buffer) = text_view.buffer() else {
+        return;
+    };
+    let targets = TargetList::new(&[]);
+    targets.add_uri_targets(TARGET_URIS);
+    targets.add(
+        &gdk::Atom::intern("GTK_TEXT_BUFFER_CONTENTS"),
+        TargetFlags::SAME_APP.bits(),
+        TARGET_TEXT,
+    );
+    targets.add_rich_text_targets(TARGET_TEXT, true, &buffer);
+    targets.add_text_targets(TARGET_TEXT);
+    text_view.drag_dest_set_target_list(Some(&targets));
+}
+
+// Only the first of several files or links is opened, as there is one document
+fn open_uris(
+    win: ApplicationWindow,
+    json_editor: JsonEditorState,
+    recent: RecentState,
+    uris: Vec<glib::GString>,
+) {
+    let Some(uri) = uris.into_iter().next() else {
+        return;
+    };
+    // After the drop has finished, so the source isn't kept waiting by any error dialog
+    glib::idle_add_local_once(move || match glib::filename_from_uri(&uri) {
+        Ok((path, _)) => file_mgt::open_file(win, json_editor, recent, &path),
+        Err(_) => file_mgt::open_url(win, json_editor, recent, &uri, ""),
+    });
+}
+
+// Text dropped anywhere but the editor replaces the document, as it can't be placed
+fn open_text(json_editor: JsonEditorState, text: &str) {
+    let buffer = json_editor::retrieve_buffer(json_editor.clone());
+    json_editor::replace_buffer_text(&buffer, text);
+    // So saving doesn't overwrite the file the previous text came from
+    json_editor::set_file_path(json_editor, None);
+}
+
+pub fn attach_listeners(
+    win: &ApplicationWindow,
+    json_editor: JsonEditorState,
+    tree_view: TreeViewState,
+    recent: RecentState,
+) {
+    let text_view = json_editor::text_view(json_editor.clone());
+    set_editor_targets(&text_view);
+    // Replacing the buffer, as large document mode does, resets the targets
+    text_view.connect_buffer_notify(set_editor_targets);
+    text_view.connect_drag_data_received({
+        let win = win.clone();
+        let json_editor = json_editor.clone();
+        let recent = recent.clone();
+        move |text_view, context, _, _, data, info, time| {
+            if info != TARGET_URIS {
+                return;
+            }
+            // Otherwise the TextView would insert the URIs as text
+            text_view.stop_signal_emission_by_name("drag-data-received");
+            context.drag_finish(true, false, time);
+            open_uris(
+                win.clone(),
+                json_editor.clone(),
+                recent.clone(),
+                data.uris(),
+            );
+        }
+    });
+
+    // The read-only view of large documents and the tree aren't drop targets themselves, so
+    // their containers are
+    let targets = TargetList::new(&[]);
+    targets.add_uri_targets(TARGET_URIS);
+    targets.add_text_targets(TARGET_TEXT);
+    let drop_areas: [gtk::Widget; 2] = [
+        json_editor.stack.clone().upcast(),
+        tree_view.overlay.clone().upcast(),
+    ];
+    for drop_area in drop_areas {
+        drop_area.drag_dest_set(DestDefaults::ALL, &[], DragAction::COPY);
+        drop_area.drag_dest_set_target_list(Some(&targets));
+        drop_area.connect_drag_data_received({
+            let win = win.clone();
+            let json_editor = json_editor.clone();
+            let recent = recent.clone();
+            let tree = tree_view.tree_view.clone();
+            move |_, context, _, _, data, info, _| {
+                // A row dragged back onto the tree would replace the document with itself
+                if context.drag_get_source_widget().as_ref() == Some(tree.upcast_ref()) {
+                    return;
+                }
+                if info == TARGET_URIS {
+                    open_uris(
+                        win.clone(),
+                        json_editor.clone(),
+                        recent.clone(),
+                        data.uris(),
+                    );
+                } else if let Some(text) = data.text() {
+                    open_text(json_editor.clone(), &text);
+                }
+            }
+        });
+    }
+
+    let tree = tree_view.tree_view.clone();
+    tree.drag_source_set(ModifierType::BUTTON1_MASK, &[], DragAction::COPY);
+    tree.drag_source_add_text_targets();
+
+    // Rows are selected as they're pressed, so the selected row is the one being dragged
+    tree.connect_drag_begin(|tree, context| {
+        let Some((model, iter)) = tree.selection().selected() else {
+            return;
+        };
+        if let Some(icon) = model
+            .path(&iter)
+            .and_then(|path| tree.create_row_drag_icon(&path))
+        {
+            context.drag_set_icon_surface(&icon);
+        }
+    });
+
+    tree.connect_drag_data_get(move |_, _, data, _, _| {
+        if let Some(json) = tree_view::selected_json(tree_view.clone()) {
+            data.set_text(&json);
+        }
+    });
+}
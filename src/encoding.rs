@@ -0,0 +1,182 @@
+// Decoding files that aren't plain UTF-8: UTF-16 and UTF-32 are recognised by their byte
+// order mark, and anything else that isn't valid UTF-8 has to be decoded as chosen
+#[derive(Clone, Copy)]
+pub enum Encoding {
+    Utf8,
+    // Invalid sequences become U+FFFD rather than failing
+    Utf8Lossy,
+    Utf16Le,
+    Utf16Be,
+    Utf32Le,
+    Utf32Be,
+    Latin1,
+    Windows1252,
+}
+
+// Offered when a file isn't valid UTF-8 and has no byte order mark
+pub const FALLBACK_ENCODINGS: [(Encoding, &str); 3] = [
+    (Encoding::Latin1, "Latin-1 (ISO-8859-1)"),
+    (Encoding::Windows1252, "Windows-1252"),
+    (Encoding::Utf8Lossy, "UTF-8, replacing invalid characters"),
+];
+
+// Windows-1252 differs from Latin-1 only in 0x80 to 0x9F, where the gaps are unassigned
+const WINDOWS_1252_HIGH: [char; 32] = [
+    '€', '\u{81}', '‚', 'ƒ', '„', '…', '†', '‡', 'ˆ', '‰', 'Š', '‹', 'Œ', '\u{8D}', 'Ž', '\u{8F}',
+    '\u{90}', '‘', '’', '“', '”', '•', '–', '—', '˜', '™', 'š', '›', 'œ', '\u{9D}', 'ž', 'Ÿ',
+];
+
+// The encoding given by a byte order mark, and the length of the mark
+pub fn detect_bom(bytes: &[u8]) -> Option<(Encoding, usize)> {
+    // UTF-32 LE first, as its mark starts with the UTF-16 LE one
+    match bytes {
+        [0xFF, 0xFE, 0x00, 0x00, ..] => Some((Encoding::Utf32Le, 4)),
+        [0x00, 0x00, 0xFE, 0xFF, ..] => Some((Encoding::Utf32Be, 4)),
+        [0xEF, 0xBB, 0xBF, ..] => Some((Encoding::Utf8, 3)),
+        [0xFF, 0xFE, ..] => Some((Encoding::Utf16Le, 2)),
+        [0xFE, 0xFF, ..] => Some((Encoding::Utf16Be, 2)),
+        _ => None,
+    }
+}
+
+// Decode bytes, without any byte order mark
+pub fn decode(bytes: &[u8], encoding: Encoding) -> Result<String, String> {
+    match encoding {
+        Encoding::Utf8 => String::from_utf8(bytes.to_vec()).map_err(|e| e.to_string()),
+        Encoding::Utf8Lossy => Ok(String::from_utf8_lossy(bytes).into_owned()),
+        Encoding::Utf16Le | Encoding::Utf16Be => {
+            if !bytes.len().is_multiple_of(2) {
+                return Err("The UTF-16 text has an odd number of bytes".to_string());
+            }
+            let units = bytes.chunks_exact(2).map(|pair| match encoding {
+                Encoding::Utf16Le => u16::from_le_bytes([pair[0], pair[1]]),
+                _ => u16::from_be_bytes([pair[0], pair[1]]),
+            });
+            char::decode_utf16(units)
+                .collect::<Result<String, _>>()
+                .map_err(|e| e.to_string())
+        }
+        Encoding::Utf32Le | Encoding::Utf32Be => {
+            if !bytes.len().is_multiple_of(4) {
+                return Err("The UTF-32 text has a number of bytes not divisible by 4".to_string());
+            }
+            bytes
+                .chunks_exact(4)
+                .map(|quad| {
+                    let quad = [quad[0], quad[1], quad[2], quad[3]];
+                    let code_point = match encoding {
+                        Encoding::Utf32Le => u32::from_le_bytes(quad),
+                        _ => u32::from_be_bytes(quad),
+                    };
+                    char::from_u32(code_point)
+                        .ok_or_else(|| format!("Invalid UTF-32 code point {:#X}", code_point))
+                })
+                .collect()
+        }
+        Encoding::Latin1 => Ok(bytes.iter().map(|&b| b as char).collect()),
+        Encoding::Windows1252 => Ok(bytes
+            .iter()
+            .map(|&b| match b {
+                0x80..=0x9F => WINDOWS_1252_HIGH[(b - 0x80) as usize],
+                _ => b as char,
+            })
+            .collect()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decode_with_bom(bytes: &[u8]) -> Result<String, String> {
+        let (encoding, bom_len) = detect_bom(bytes).unwrap_or((Encoding::Utf8, 0));
+        decode(&bytes[bom_len..], encoding)
+    }
+
+    #[test]
+    fn byte_order_marks() {
+        let cases: [(&[u8], &str); 5] = [
+            (b"\xEF\xBB\xBF{\"\xC3\xA9\": 1}", "{\"é\": 1}"),
+            (b"\xFF\xFE[\x001\x00]\x00", "[1]"),
+            (b"\xFE\xFF\x00[\x001\x00]", "[1]"),
+            (b"\xFF\xFE\x00\x00[\x00\x00\x00]\x00\x00\x00", "[]"),
+            (b"\x00\x00\xFE\xFF\x00\x00\x00[\x00\x00\x00]", "[]"),
+        ];
+        for (bytes, expected) in cases {
+            assert_eq!(
+                decode_with_bom(bytes).as_deref(),
+                Ok(expected),
+                "{:?}",
+                bytes
+            );
+        }
+        assert!(detect_bom(b"[1]").is_none());
+        assert!(detect_bom(b"\xFF").is_none());
+    }
+
+    #[test]
+    fn utf32_le_is_not_taken_for_utf16() {
+        assert!(matches!(
+            detect_bom(b"\xFF\xFE\x00\x00"),
+            Some((Encoding::Utf32Le, 4))
+        ));
+        // Only UTF-16 LE text starting with U+0000 would look the same
+        assert!(matches!(
+            detect_bom(b"\xFF\xFE\x00\x01"),
+            Some((Encoding::Utf16Le, 2))
+        ));
+    }
+
+    #[test]
+    fn surrogate_pairs() {
+        assert_eq!(
+            decode(&[0x3D, 0xD8, 0x00, 0xDE], Encoding::Utf16Le).as_deref(),
+            Ok("😀")
+        );
+        assert_eq!(
+            decode(&[0xD8, 0x3D, 0xDE, 0x00], Encoding::Utf16Be).as_deref(),
+            Ok("😀")
+        );
+        assert!(decode(&[0x3D, 0xD8, 0x41, 0x00], Encoding::Utf16Le).is_err());
+    }
+
+    #[test]
+    fn odd_lengths() {
+        assert!(decode(b"[\x00]", Encoding::Utf16Le).is_err());
+        assert!(decode(b"\x00[\x00", Encoding::Utf16Be).is_err());
+        for len in 1..4 {
+            assert!(decode(&[0; 7][..4 + len], Encoding::Utf32Le).is_err());
+        }
+        assert_eq!(decode(b"", Encoding::Utf16Le).as_deref(), Ok(""));
+        assert_eq!(decode(b"", Encoding::Utf32Be).as_deref(), Ok(""));
+    }
+
+    #[test]
+    fn invalid_utf32_code_points() {
+        // Past U+10FFFF, and a surrogate
+        assert!(decode(&[0x00, 0x00, 0x11, 0x00], Encoding::Utf32Le).is_err());
+        assert!(decode(&[0x00, 0x00, 0xD8, 0x00], Encoding::Utf32Be).is_err());
+    }
+
+    #[test]
+    fn single_byte_encodings() {
+        let bytes = b"\x80\x81\x9F\xA0\xE9";
+        assert_eq!(
+            decode(bytes, Encoding::Latin1).as_deref(),
+            Ok("\u{80}\u{81}\u{9F}\u{A0}é")
+        );
+        assert_eq!(
+            decode(bytes, Encoding::Windows1252).as_deref(),
+            Ok("€\u{81}Ÿ\u{A0}é")
+        );
+    }
+
+    #[test]
+    fn utf8() {
+        assert!(decode(b"\xC3", Encoding::Utf8).is_err());
+        assert_eq!(
+            decode(b"a\xC3b", Encoding::Utf8Lossy).as_deref(),
+            Ok("a\u{FFFD}b")
+        );
+    }
+}
@@ -0,0 +1,345 @@
+// Pulls the JSON objects and arrays out of arbitrary text, such as log lines with a
+// timestamp and level before the payload
+use crate::json_editor;
+use crate::json_editor::JsonEditorState;
+use gtk::pango::EllipsizeMode;
+use gtk::prelude::*;
+use gtk::{
+    ApplicationWindow, ButtonsType, CellRendererText, ListStore, MessageDialog, MessageType,
+    ResponseType, ScrolledWindow, ShadowType, TreeView, TreeViewColumn, WindowPosition,
+};
+use serde_json::{Deserializer, Value};
+
+// Characters of each document shown in the list
+const PREVIEW_CHARS: usize = 200;
+
+// Every balanced object or array in the text, in order. Ones nested inside another are
+// part of that document rather than listed separately.
+pub fn extract_documents(text: &str) -> Vec<Value> {
+    let mut documents = Vec::new();
+    let bytes = text.as_bytes();
+    let mut i = 0;
+
+    // Only ASCII positions are sliced at, so these are always character boundaries
+    while i < bytes.len() {
+        match bytes[i] {
+            b'{' | b'[' => match parse_document_at(&text[i..]) {
+                Ok((document, length)) => {
+                    documents.push(document);
+                    i += length;
+                    continue;
+                }
+                // Carry on from where it broke, which keeps long invalid lines linear.
+                // Documents nested inside the broken one are skipped along with it.
+                Err(error_index) => {
+                    i += next_char_boundary(&text[i..], error_index.max(1));
+                    continue;
+                }
+            },
+            b'"' => {
+                // A JSON string literal holding escaped JSON, e.g. payload="{\"id\":1}"
+                let mut strings = Deserializer::from_str(&text[i..]).into_iter::<String>();
+                if let Some(Ok(content)) = strings.next() {
+                    let nested = extract_documents(&content);
+                    if !nested.is_empty() {
+                        documents.extend(nested);
+                        i += strings.byte_offset();
+                        continue;
+                    }
+                }
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+
+    documents
+}
+
+// Byte index in the text of where a parse error was found
+fn error_index(text: &str, e: &serde_json::Error) -> usize {
+    let line_start = if e.line() <= 1 {
+        0
+    } else {
+        text.match_indices('\n')
+            .nth(e.line() - 2)
+            .map_or(text.len(), |(i, _)| i + 1)
+    };
+    (line_start + e.column().saturating_sub(1)).min(text.len())
+}
+
+// The index, or the start of the next character if it's within one
+fn next_char_boundary(text: &str, index: usize) -> usize {
+    (index..=text.len())
+        .find(|&i| text.is_char_boundary(i))
+        .unwrap_or(text.len())
+}
+
+// The document starting at the beginning of the text and how many bytes it took up, or the
+// byte index at which it stopped being valid
+fn parse_document_at(text: &str) -> Result<(Value, usize), usize> {
+    let mut values = Deserializer::from_str(text).into_iter::<Value>();
+    let error_at = match values.next() {
+        Some(Ok(document)) => return Ok((document, values.byte_offset())),
+        Some(Err(e)) => error_index(text, &e),
+        None => text.len(),
+    };
+
+    // Escaped JSON without its surrounding quotes, e.g. {\"id\":1}, which fails at the
+    // first backslash
+    if text.as_bytes().get(error_at) != Some(&b'\\') {
+        return Err(error_at);
+    }
+    let length = balanced_length(text).ok_or(error_at)?;
+    let unescaped = json_editor::unescape_json_text(&text[..length]).map_err(|_| error_at)?;
+    match serde_json::from_str::<Value>(&unescaped) {
+        Ok(document) if document.is_object() || document.is_array() => Ok((document, length)),
+        _ => Err(error_at),
+    }
+}
+
+// Bytes of escaped JSON up to and including the bracket that closes the first one. Brackets
+// are counted as they would be once unescaped, so ones inside string literals are ignored.
+fn balanced_length(text: &str) -> Option<usize> {
+    let bytes = text.as_bytes();
+    let mut depth = 0;
+    let mut in_string = false;
+    // Whether the last unescaped character was a backslash inside a string literal
+    let mut escaping = false;
+    let mut i = 0;
+
+    while i < bytes.len() {
+        // Undo one level of escaping to get the character of the JSON itself
+        let (unescaped, raw_length) = match bytes[i] {
+            b'\\' => match bytes.get(i + 1)? {
+                b'"' => (b'"', 2),
+                b'\\' => (b'\\', 2),
+                b'/' => (b'/', 2),
+                b'n' | b't' | b'r' | b'b' | b'f' => (b' ', 2),
+                // Never a bracket or quote that matters, as those are ASCII
+                b'u' => (b'_', 6),
+                _ => return None,
+            },
+            // Escaped JSON is always on a single line
+            b'\n' => return None,
+            b => (b, 1),
+        };
+        i += raw_length;
+
+        if in_string {
+            match unescaped {
+                _ if escaping => escaping = false,
+                b'\\' => escaping = true,
+                b'"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+        match unescaped {
+            b'"' => in_string = true,
+            b'{' | b'[' => depth += 1,
+            b'}' | b']' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(i.min(text.len()));
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+fn to_ndjson(documents: &[Value]) -> String {
+    documents
+        .iter()
+        .map(|document| document.to_string())
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+pub fn extract_json_action(win: ApplicationWindow, json_editor: JsonEditorState) {
+    let buffer = json_editor::retrieve_buffer(json_editor.clone());
+    let (start, end) = buffer.bounds();
+    let text = buffer.text(&start, &end, true).unwrap();
+    let documents = extract_documents(text.as_str());
+
+    if documents.is_empty() {
+        let error_dialog = MessageDialog::builder()
+            .transient_for(&win)
+            .window_position(WindowPosition::CenterOnParent)
+            .message_type(MessageType::Info)
+            .buttons(ButtonsType::Ok)
+            .title("No JSON found")
+            .text("The current text does not contain any JSON objects or arrays.")
+            .build();
+        error_dialog.connect_response(move |error_dialog, _| {
+            error_dialog.close();
+        });
+        error_dialog.run();
+        return;
+    }
+
+    let dialog = gtk::Dialog::builder()
+        .transient_for(&win)
+        .window_position(WindowPosition::CenterOnParent)
+        .title(format!("Extract JSON: {} documents found", documents.len()))
+        .default_width(720)
+        .default_height(400)
+        .build();
+    dialog.add_button("Cancel", ResponseType::Cancel);
+    dialog.add_button("Open as NDJSON", ResponseType::Other(0));
+    dialog.add_button("Open as Array", ResponseType::Other(1));
+    dialog.add_button("Open Selected", ResponseType::Ok);
+
+    // Position in the order found and a preview of each document
+    let model = ListStore::new(&[u32::static_type(), String::static_type()]);
+    for (i, document) in documents.iter().enumerate() {
+        let preview: String = document.to_string().chars().take(PREVIEW_CHARS).collect();
+        model.set(&model.append(), &[(0, &(i as u32 + 1)), (1, &preview)]);
+    }
+
+    let list = TreeView::builder().model(&model).build();
+    for (title, column_id) in [("#", 0), ("Document", 1)] {
+        let column = TreeViewColumn::new();
+        column.set_title(title);
+        let cell = CellRendererText::new();
+        if column_id == 1 {
+            cell.set_ellipsize(EllipsizeMode::End);
+            column.set_expand(true);
+        }
+        gtk::prelude::CellLayoutExt::pack_start(&column, &cell, true);
+        gtk::prelude::TreeViewColumnExt::add_attribute(&column, &cell, "text", column_id);
+        list.append_column(&column);
+    }
+    list.selection().select_path(&gtk::TreePath::new_first());
+    list.connect_row_activated({
+        let dialog = dialog.clone();
+        move |_, _, _| dialog.response(ResponseType::Ok)
+    });
+
+    let scroller = ScrolledWindow::builder()
+        .shadow_type(ShadowType::In)
+        .expand(true)
+        .build();
+    scroller.add(&list);
+    dialog.content_area().add(&scroller);
+
+    dialog.connect_response(move |dialog, response| {
+        let new_text = match response {
+            ResponseType::Ok => list.selection().selected().and_then(|(model, iter)| {
+                let index = model.value(&iter, 0).get::<u32>().unwrap() as usize - 1;
+                serde_json::to_string_pretty(&documents[index]).ok()
+            }),
+            ResponseType::Other(0) => Some(to_ndjson(&documents)),
+            ResponseType::Other(1) => serde_json::to_string_pretty(&documents).ok(),
+            _ => None,
+        };
+        if let Some(new_text) = new_text {
+            json_editor::replace_buffer_text(&buffer, &new_text);
+        }
+        dialog.close();
+    });
+
+    dialog.show_all();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn log_lines() {
+        let text = concat!(
+            "2024-01-02T03:04:05Z INFO [worker-1] done {\"id\":1,\"ok\":true}\n",
+            "2024-01-02T03:04:06Z WARN [api] retrying [1, 2] in {5s}\n",
+            "2024-01-02T03:04:07Z DEBUG no payload\n",
+        );
+        assert_eq!(
+            extract_documents(text),
+            vec![json!({"id": 1, "ok": true}), json!([1, 2])]
+        );
+        assert_eq!(extract_documents("no JSON here"), Vec::<Value>::new());
+    }
+
+    #[test]
+    fn nested_documents_are_part_of_theirs() {
+        assert_eq!(
+            extract_documents(r#"a {"b":{"c":[1]}} d [[2],{"e":3}]"#),
+            vec![json!({"b": {"c": [1]}}), json!([[2], {"e": 3}])]
+        );
+    }
+
+    #[test]
+    fn escaped_json() {
+        // Inside a string literal
+        assert_eq!(
+            extract_documents(r#"event payload="{\"id\":1,\"tags\":[\"a\"]}" sent"#),
+            vec![json!({"id": 1, "tags": ["a"]})]
+        );
+        // Without the quotes
+        assert_eq!(
+            extract_documents(r#"event payload={\"id\":2} sent"#),
+            vec![json!({"id": 2})]
+        );
+        // Escaped twice over
+        assert_eq!(
+            extract_documents(r#"{"message":"{\"body\":\"[3]\"}"}"#),
+            vec![json!({"message": "{\"body\":\"[3]\"}"})]
+        );
+        assert_eq!(
+            extract_documents(r#"message="{\"body\":\"[3]\"}""#),
+            vec![json!({"body": "[3]"})]
+        );
+    }
+
+    #[test]
+    fn brackets_inside_strings() {
+        assert_eq!(
+            extract_documents(r#"x {"text":"a ] } [ b"} y"#),
+            vec![json!({"text": "a ] } [ b"})]
+        );
+        assert_eq!(
+            extract_documents(r#"x {\"text\":\"a ] } [ \\\" b\"} y"#),
+            vec![json!({"text": "a ] } [ \" b"})]
+        );
+    }
+
+    #[test]
+    fn unbalanced_brackets() {
+        assert_eq!(
+            extract_documents(r#"]} {"a":1}} [1, 2 {"b":2}"#),
+            vec![json!({"a": 1}), json!({"b": 2})]
+        );
+        // Never closed, so nothing in it is found
+        assert_eq!(extract_documents(r#"{"a": [1, 2"#), Vec::<Value>::new());
+        assert_eq!(extract_documents(r#"x={\"a\":[1"#), Vec::<Value>::new());
+        assert_eq!(extract_documents("[\n{\\\"a\\\":1}"), Vec::<Value>::new());
+    }
+
+    #[test]
+    fn errors_inside_multibyte_text() {
+        assert_eq!(
+            extract_documents("日本{語} {\"é\":\"ü\"} [1]ü"),
+            vec![json!({"é": "ü"}), json!([1])]
+        );
+    }
+
+    #[test]
+    fn balanced_length_of_escaped_json() {
+        assert_eq!(balanced_length(r#"{\"a\":[1]} rest"#), Some(11));
+        assert_eq!(balanced_length(r#"[\"]\",]] rest"#), Some(8));
+        assert_eq!(balanced_length(r#"{\"a\":1"#), None);
+        assert_eq!(balanced_length("{\\\"a\\\":\n1}"), None);
+        assert_eq!(balanced_length(r#"{\x}"#), None);
+    }
+
+    #[test]
+    fn ndjson() {
+        assert_eq!(
+            to_ndjson(&[json!({"a": 1}), json!([1, "b"])]),
+            "{\"a\":1}\n[1,\"b\"]"
+        );
+    }
+}
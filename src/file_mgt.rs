@@ -1,15 +1,22 @@
-use crate::json_editor;
+use crate::encoding::{Encoding, FALLBACK_ENCODINGS};
 use crate::json_editor::JsonEditorState;
+use crate::recent::{RecentEntry, RecentState};
+use crate::{encoding, har, json_editor, recent, units};
 use gtk::prelude::*;
 use gtk::{
-    ApplicationWindow, ButtonsType, FileChooserAction, FileChooserDialog, MessageDialog,
-    MessageType, ResponseType, WindowPosition,
+    ApplicationWindow, ButtonsType, ComboBoxText, FileChooserAction, FileChooserDialog, Label,
+    MessageDialog, MessageType, Orientation, ResponseType, ScrolledWindow, ShadowType, TextView,
+    WindowPosition,
 };
 use serde_json::Value;
-use std::fs::File;
-use std::io::{BufReader, Read};
+use std::fs;
+use std::path::Path;
 
-pub fn file_open_item_action(win: ApplicationWindow, json_editor: JsonEditorState) {
+pub fn file_open_item_action(
+    win: ApplicationWindow,
+    json_editor: JsonEditorState,
+    recent: RecentState,
+) {
     let file_chooser = FileChooserDialog::builder()
         .title("Open File")
         .parent(&win)
@@ -22,14 +29,9 @@ pub fn file_open_item_action(win: ApplicationWindow, json_editor: JsonEditorStat
         let json_editor = json_editor.clone();
         move |file_chooser, response| {
             if response == ResponseType::Ok {
-                let filename = file_chooser.filename().expect("Couldn't get filename");
-                let file = File::open(filename).expect("Couldn't open file");
-
-                let mut reader = BufReader::new(file);
-                let mut contents = String::new();
-                let _ = reader.read_to_string(&mut contents);
-
-                json_editor::retrieve_buffer(json_editor.clone()).set_text(&contents);
+                if let Some(filename) = file_chooser.filename() {
+                    open_file(win.clone(), json_editor.clone(), recent.clone(), &filename);
+                }
             }
             file_chooser.close();
         }
@@ -38,7 +40,281 @@ pub fn file_open_item_action(win: ApplicationWindow, json_editor: JsonEditorStat
     file_chooser.show_all();
 }
 
-pub fn file_open_url_item_action(win: ApplicationWindow, json_editor: JsonEditorState) {
+// Files larger than this can take a long time to load and parse, so are only opened once
+// confirmed
+const LARGE_FILE_WARNING_BYTES: u64 = 100 * 1024 * 1024;
+
+fn show_open_error(win: &ApplicationWindow, filename: &Path, error: &str) {
+    let error_dialog = MessageDialog::builder()
+        .transient_for(win)
+        .window_position(WindowPosition::CenterOnParent)
+        .message_type(MessageType::Warning)
+        .buttons(ButtonsType::Ok)
+        .title("Could not open file")
+        .text(format!(
+            "The file {} could not be opened.\n\n{}",
+            filename.display(),
+            error
+        ))
+        .build();
+    error_dialog.connect_response(move |error_dialog, _| {
+        error_dialog.close();
+    });
+    error_dialog.run();
+}
+
+fn confirm_large_file(win: &ApplicationWindow, filename: &Path, size: u64) -> bool {
+    let dialog = MessageDialog::builder()
+        .transient_for(win)
+        .window_position(WindowPosition::CenterOnParent)
+        .modal(true)
+        .message_type(MessageType::Question)
+        .buttons(ButtonsType::None)
+        .title("Large file")
+        .text(format!(
+            "The file {} is {}. Open it anyway?",
+            filename.display(),
+            units::format_bytes(size as usize)
+        ))
+        .secondary_text("Arson may be unresponsive while it is loaded and parsed.")
+        .build();
+    dialog.add_buttons(&[
+        ("Cancel", ResponseType::Cancel),
+        ("Open Anyway", ResponseType::Accept),
+    ]);
+    dialog.set_default_response(ResponseType::Cancel);
+    let response = dialog.run();
+    dialog.close();
+    response == ResponseType::Accept
+}
+
+// Ask how to decode a file that isn't valid UTF-8 and has no byte order mark
+fn choose_encoding(win: &ApplicationWindow, filename: &Path, error: &str) -> Option<Encoding> {
+    let dialog = gtk::Dialog::builder()
+        .transient_for(win)
+        .window_position(WindowPosition::CenterOnParent)
+        .title("Choose Encoding")
+        .modal(true)
+        .build();
+    dialog.add_buttons(&[("Cancel", ResponseType::Cancel), ("Open", ResponseType::Ok)]);
+    dialog.set_default_response(ResponseType::Ok);
+
+    let v_box = gtk::Box::builder()
+        .orientation(Orientation::Vertical)
+        .spacing(12)
+        .margin(12)
+        .build();
+    let label = Label::builder()
+        .label(format!(
+            "The file {} is not valid UTF-8 ({}).\n\nWhich encoding should it be read with?",
+            filename.display(),
+            error
+        ))
+        .wrap(true)
+        .max_width_chars(60)
+        .xalign(0.0)
+        .build();
+    let encoding_combo = ComboBoxText::new();
+    for (_, name) in FALLBACK_ENCODINGS {
+        encoding_combo.append_text(name);
+    }
+    encoding_combo.set_active(Some(0));
+    v_box.add(&label);
+    v_box.add(&encoding_combo);
+    dialog.content_area().add(&v_box);
+    dialog.show_all();
+
+    let response = dialog.run();
+    let chosen = encoding_combo
+        .active()
+        .and_then(|index| FALLBACK_ENCODINGS.get(index as usize))
+        .map(|(encoding, _)| *encoding);
+    dialog.close();
+
+    if response == ResponseType::Ok {
+        chosen
+    } else {
+        None
+    }
+}
+
+// The text of a file, or None if it couldn't be read or the user cancelled
+fn read_text(win: &ApplicationWindow, filename: &Path) -> Option<String> {
+    let size = match fs::metadata(filename) {
+        Ok(metadata) => metadata.len(),
+        Err(e) => {
+            show_open_error(win, filename, &e.to_string());
+            return None;
+        }
+    };
+    if size > LARGE_FILE_WARNING_BYTES && !confirm_large_file(win, filename, size) {
+        return None;
+    }
+
+    let bytes = match fs::read(filename) {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            show_open_error(win, filename, &e.to_string());
+            return None;
+        }
+    };
+
+    let decoded = match encoding::detect_bom(&bytes) {
+        Some((encoding, bom_length)) => encoding::decode(&bytes[bom_length..], encoding),
+        None => match String::from_utf8(bytes) {
+            Ok(text) => Ok(text),
+            Err(e) => {
+                let error = e.utf8_error().to_string();
+                let encoding = choose_encoding(win, filename, &error)?;
+                encoding::decode(&e.into_bytes(), encoding)
+            }
+        },
+    };
+    match decoded {
+        Ok(text) => Some(text),
+        Err(e) => {
+            show_open_error(win, filename, &e);
+            None
+        }
+    }
+}
+
+pub fn open_file(
+    win: ApplicationWindow,
+    json_editor: JsonEditorState,
+    recent: RecentState,
+    filename: &Path,
+) {
+    let Some(contents) = read_text(&win, filename) else {
+        return;
+    };
+
+    // Only files that opened are remembered
+    let entry = RecentEntry::File {
+        path: filename.to_path_buf(),
+    };
+
+    // HAR files list their requests, and the bodies are loaded from there
+    let is_har = filename
+        .extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("har"));
+    if is_har {
+        if let Ok(entries) = har::parse_har(&contents) {
+            recent::add(recent, entry);
+            let title = filename.file_name().unwrap_or_default().to_string_lossy();
+            har::har_viewer_action(win, json_editor, &title, entries);
+            return;
+        }
+    }
+
+    let buffer = json_editor::retrieve_buffer(json_editor.clone());
+    json_editor::replace_buffer_text(&buffer, &contents);
+    json_editor::set_file_path(json_editor, Some(filename.to_path_buf()));
+    buffer.set_modified(false);
+    recent::add(recent, entry);
+}
+
+// Save to the file the text came from, asking where to save it if there isn't one.
+// Returns whether it was saved.
+pub fn file_save_item_action(
+    win: ApplicationWindow,
+    json_editor: JsonEditorState,
+    recent: RecentState,
+) -> bool {
+    match json_editor::file_path(json_editor.clone()) {
+        Some(filename) => save_file(win, json_editor, recent, &filename),
+        None => file_save_as_item_action(win, json_editor, recent),
+    }
+}
+
+pub fn file_save_as_item_action(
+    win: ApplicationWindow,
+    json_editor: JsonEditorState,
+    recent: RecentState,
+) -> bool {
+    let file_chooser = FileChooserDialog::builder()
+        .title("Save File")
+        .parent(&win)
+        .action(FileChooserAction::Save)
+        .do_overwrite_confirmation(true)
+        .build();
+    file_chooser.add_buttons(&[("Save", ResponseType::Ok), ("Cancel", ResponseType::Cancel)]);
+    match json_editor::file_path(json_editor.clone()) {
+        Some(filename) => {
+            let _ = file_chooser.set_filename(filename);
+        }
+        None => file_chooser.set_current_name("untitled.json"),
+    }
+
+    let filename = match file_chooser.run() {
+        ResponseType::Ok => file_chooser.filename(),
+        _ => None,
+    };
+    file_chooser.close();
+
+    match filename {
+        Some(filename) => save_file(win, json_editor, recent, &filename),
+        None => false,
+    }
+}
+
+fn save_file(
+    win: ApplicationWindow,
+    json_editor: JsonEditorState,
+    recent: RecentState,
+    filename: &Path,
+) -> bool {
+    let buffer = json_editor::retrieve_buffer(json_editor.clone());
+    let (start, end) = buffer.bounds();
+    let contents = buffer.text(&start, &end, true).unwrap();
+
+    if let Err(e) = fs::write(filename, contents.as_str()) {
+        let error_dialog = MessageDialog::builder()
+            .transient_for(&win)
+            .window_position(WindowPosition::CenterOnParent)
+            .message_type(MessageType::Warning)
+            .buttons(ButtonsType::Ok)
+            .title("Could not save file")
+            .text(format!(
+                "The file {} could not be saved.\n\n{}",
+                filename.display(),
+                e
+            ))
+            .build();
+        error_dialog.connect_response(move |error_dialog, _| {
+            error_dialog.close();
+        });
+        error_dialog.run();
+        return false;
+    }
+
+    json_editor::set_file_path(json_editor, Some(filename.to_path_buf()));
+    buffer.set_modified(false);
+    recent::add(
+        recent,
+        RecentEntry::File {
+            path: filename.to_path_buf(),
+        },
+    );
+    true
+}
+
+pub fn file_open_url_item_action(
+    win: ApplicationWindow,
+    json_editor: JsonEditorState,
+    recent: RecentState,
+) {
+    open_url_dialog(win, json_editor, recent, "", "");
+}
+
+// Ask for a URL and its request headers, starting with those given
+pub fn open_url_dialog(
+    win: ApplicationWindow,
+    json_editor: JsonEditorState,
+    recent: RecentState,
+    url: &str,
+    headers: &str,
+) {
     let url_entry_dialog = gtk::Dialog::builder()
         .transient_for(&win)
         .window_position(WindowPosition::CenterOnParent)
@@ -47,9 +323,21 @@ pub fn file_open_url_item_action(win: ApplicationWindow, json_editor: JsonEditor
     let url_entry_label = gtk::Label::builder()
         .label("Enter the URL containing JSON to be opened")
         .build();
-    let url_entry_text = gtk::Entry::builder().build();
+    let url_entry_text = gtk::Entry::builder().text(url).build();
+    let headers_label = gtk::Label::builder()
+        .label("Request headers, one per line (e.g. Authorization: Bearer ...)")
+        .build();
+    let headers_text = TextView::builder().monospace(true).build();
+    headers_text.buffer().unwrap().set_text(headers);
+    let headers_scroller = ScrolledWindow::builder()
+        .shadow_type(ShadowType::In)
+        .height_request(80)
+        .build();
+    headers_scroller.add(&headers_text);
     url_entry_dialog.content_area().add(&url_entry_label);
     url_entry_dialog.content_area().add(&url_entry_text);
+    url_entry_dialog.content_area().add(&headers_label);
+    url_entry_dialog.content_area().add(&headers_scroller);
     url_entry_dialog.add_button("Open", ResponseType::Ok);
 
     url_entry_dialog.connect_response({
@@ -57,55 +345,17 @@ pub fn file_open_url_item_action(win: ApplicationWindow, json_editor: JsonEditor
         let json_editor = json_editor.clone();
         move |url_entry_dialog, response| {
             if response == ResponseType::Ok {
-                let url_text = url_entry_text.text();
-
-                let body = match reqwest::blocking::get(url_text.as_str()) {
-                    Ok(http_response) => http_response.text().unwrap(),
-                    Err(e) => {
-                        let error_dialog = MessageDialog::builder()
-                            .transient_for(&win)
-                            .window_position(WindowPosition::CenterOnParent)
-                            .message_type(MessageType::Warning)
-                            .buttons(ButtonsType::Ok)
-                            .title("JSON was invalid")
-                            .text(format!(
-                                "The URL {} could not be loaded.\n\n{}",
-                                url_text.as_str(),
-                                e
-                            ))
-                            .build();
-                        error_dialog.connect_response(move |error_dialog, _| {
-                            error_dialog.close();
-                        });
-                        error_dialog.run();
-                        return;
-                    }
-                };
-
-                let _: Value = match serde_json::from_str(body.as_str()) {
-                    Ok(v) => v,
-                    Err(e) => {
-                        let error_dialog = MessageDialog::builder()
-                            .transient_for(&win)
-                            .window_position(WindowPosition::CenterOnParent)
-                            .message_type(MessageType::Warning)
-                            .buttons(ButtonsType::Ok)
-                            .title("JSON was invalid")
-                            .text(format!(
-                                "The content from the URL {} was not valid JSON.\n\n{}",
-                                url_text.as_str(),
-                                e
-                            ))
-                            .build();
-                        error_dialog.connect_response(move |error_dialog, _| {
-                            error_dialog.close();
-                        });
-                        error_dialog.run();
-                        return;
-                    }
-                };
-
-                json_editor::retrieve_buffer(json_editor.clone()).set_text(body.as_str());
+                let headers_buffer = headers_text.buffer().unwrap();
+                let (start, end) = headers_buffer.bounds();
+                let headers = headers_buffer.text(&start, &end, true).unwrap();
+
+                open_url(
+                    win.clone(),
+                    json_editor.clone(),
+                    recent.clone(),
+                    url_entry_text.text().as_str(),
+                    headers.as_str(),
+                );
             }
             url_entry_dialog.close();
         }
@@ -113,3 +363,78 @@ pub fn file_open_url_item_action(win: ApplicationWindow, json_editor: JsonEditor
 
     url_entry_dialog.show_all();
 }
+
+// Load JSON from a URL, sending the "Name: value" headers given one per line
+pub fn open_url(
+    win: ApplicationWindow,
+    json_editor: JsonEditorState,
+    recent: RecentState,
+    url_text: &str,
+    headers: &str,
+) {
+    let mut request = reqwest::blocking::Client::new().get(url_text);
+    for (name, value) in headers.lines().filter_map(|line| line.split_once(':')) {
+        request = request.header(name.trim(), value.trim());
+    }
+
+    let body = match request
+        .send()
+        .and_then(|http_response| http_response.text())
+    {
+        Ok(body) => body,
+        Err(e) => {
+            let error_dialog = MessageDialog::builder()
+                .transient_for(&win)
+                .window_position(WindowPosition::CenterOnParent)
+                .message_type(MessageType::Warning)
+                .buttons(ButtonsType::Ok)
+                .title("JSON was invalid")
+                .text(format!(
+                    "The URL {} could not be loaded.\n\n{}",
+                    url_text, e
+                ))
+                .build();
+            error_dialog.connect_response(move |error_dialog, _| {
+                error_dialog.close();
+            });
+            error_dialog.run();
+            return;
+        }
+    };
+
+    let _: Value = match json_editor::parse_text(json_editor.clone(), body.as_str()) {
+        Ok(v) => v,
+        Err(e) => {
+            let error_dialog = MessageDialog::builder()
+                .transient_for(&win)
+                .window_position(WindowPosition::CenterOnParent)
+                .message_type(MessageType::Warning)
+                .buttons(ButtonsType::Ok)
+                .title("JSON was invalid")
+                .text(format!(
+                    "The content from the URL {} was not valid JSON.\n\n{}",
+                    url_text, e
+                ))
+                .build();
+            error_dialog.connect_response(move |error_dialog, _| {
+                error_dialog.close();
+            });
+            error_dialog.run();
+            return;
+        }
+    };
+
+    let buffer = json_editor::retrieve_buffer(json_editor.clone());
+    json_editor::replace_buffer_text(&buffer, body.as_str());
+    json_editor::set_file_path(json_editor, None);
+    buffer.set_modified(false);
+
+    // Only URLs that loaded are remembered
+    recent::add(
+        recent,
+        RecentEntry::Url {
+            url: url_text.to_string(),
+            header_names: recent::header_names(headers),
+        },
+    );
+}
@@ -0,0 +1,223 @@
+// Viewer for HTTP Archive (HAR) files, as saved from the network tab of browser dev tools
+use crate::json_editor::JsonEditorState;
+use crate::{json_editor, units};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use gtk::pango::EllipsizeMode;
+use gtk::prelude::*;
+use gtk::{
+    Align, ApplicationWindow, CellRendererText, Label, ListStore, Orientation, PolicyType,
+    RadioButton, ScrolledWindow, ShadowType, TreeView, TreeViewColumn, WindowPosition,
+};
+use serde_json::Value;
+use std::rc::Rc;
+
+pub struct HarEntry {
+    method: String,
+    url: String,
+    status: i64,
+    // Bytes of response content, if known
+    size: Option<usize>,
+    time_ms: f64,
+    request_body: Option<HarBody>,
+    response_body: Option<HarBody>,
+}
+
+struct HarBody {
+    text: String,
+    mime_type: String,
+    // Binary content is base64 encoded in HAR files
+    base64: bool,
+}
+
+pub fn parse_har(text: &str) -> Result<Vec<HarEntry>, String> {
+    let har: Value = serde_json::from_str(text).map_err(|e| e.to_string())?;
+    let entries = har["log"]["entries"]
+        .as_array()
+        .ok_or("There is no log.entries array, so this is not a HAR file")?;
+
+    Ok(entries
+        .iter()
+        .map(|entry| {
+            let request = &entry["request"];
+            let response = &entry["response"];
+            HarEntry {
+                method: request["method"].as_str().unwrap_or_default().to_string(),
+                url: request["url"].as_str().unwrap_or_default().to_string(),
+                status: response["status"].as_i64().unwrap_or_default(),
+                size: response["content"]["size"]
+                    .as_u64()
+                    .or_else(|| response["bodySize"].as_u64())
+                    .map(|size| size as usize),
+                time_ms: entry["time"].as_f64().unwrap_or_default(),
+                request_body: request["postData"]["text"].as_str().map(|text| HarBody {
+                    text: text.to_string(),
+                    mime_type: request["postData"]["mimeType"]
+                        .as_str()
+                        .unwrap_or_default()
+                        .to_string(),
+                    base64: false,
+                }),
+                response_body: response["content"]["text"].as_str().map(|text| HarBody {
+                    text: text.to_string(),
+                    mime_type: response["content"]["mimeType"]
+                        .as_str()
+                        .unwrap_or_default()
+                        .to_string(),
+                    base64: response["content"]["encoding"].as_str() == Some("base64"),
+                }),
+            }
+        })
+        .collect())
+}
+
+// The text of a body, or why it can't be shown
+fn decode_body(body: Option<&HarBody>, which: &str) -> Result<String, String> {
+    let body = body
+        .filter(|body| !body.text.is_empty())
+        .ok_or(format!("There is no {} body", which))?;
+
+    let text = if body.base64 {
+        let bytes = STANDARD
+            .decode(body.text.trim())
+            .map_err(|e| format!("The {} body is not valid base64: {}", which, e))?;
+        String::from_utf8(bytes).map_err(|e| {
+            format!(
+                "The {} body is binary ({}, {} bytes), so can't be shown",
+                which,
+                body.mime_type,
+                e.as_bytes().len()
+            )
+        })?
+    } else {
+        body.text.clone()
+    };
+
+    Ok(text)
+}
+
+pub fn har_viewer_action(
+    win: ApplicationWindow,
+    json_editor: JsonEditorState,
+    title: &str,
+    entries: Vec<HarEntry>,
+) {
+    let window = gtk::Window::builder()
+        .transient_for(&win)
+        .window_position(WindowPosition::CenterOnParent)
+        .title(format!("HAR: {}", title))
+        .default_width(900)
+        .default_height(400)
+        .build();
+
+    let v_box = gtk::Box::builder()
+        .orientation(Orientation::Vertical)
+        .spacing(6)
+        .margin(8)
+        .build();
+    window.add(&v_box);
+
+    let body_box = gtk::Box::new(Orientation::Horizontal, 12);
+    let response_radio = RadioButton::with_label("Response body");
+    let request_radio = RadioButton::with_label_from_widget(&response_radio, "Request body");
+    body_box.add(&Label::new(Some("Load into the editor:")));
+    body_box.add(&response_radio);
+    body_box.add(&request_radio);
+    v_box.add(&body_box);
+
+    // Method, URL, status, size, time and the index into the entries
+    let model = ListStore::new(&[
+        String::static_type(),
+        String::static_type(),
+        String::static_type(),
+        String::static_type(),
+        String::static_type(),
+        u32::static_type(),
+    ]);
+    for (i, entry) in entries.iter().enumerate() {
+        model.set(
+            &model.append(),
+            &[
+                (0, &entry.method),
+                (1, &entry.url),
+                (2, &entry.status.to_string()),
+                (3, &entry.size.map(units::format_bytes).unwrap_or_default()),
+                (4, &format!("{:.0} ms", entry.time_ms)),
+                (5, &(i as u32)),
+            ],
+        );
+    }
+
+    let list = TreeView::builder().model(&model).build();
+    for (title, column_id) in [
+        ("Method", 0),
+        ("URL", 1),
+        ("Status", 2),
+        ("Size", 3),
+        ("Time", 4),
+    ] {
+        let column = TreeViewColumn::new();
+        column.set_title(title);
+        column.set_resizable(true);
+        let cell = CellRendererText::new();
+        if column_id == 1 {
+            cell.set_ellipsize(EllipsizeMode::Middle);
+            column.set_expand(true);
+        }
+        gtk::prelude::CellLayoutExt::pack_start(&column, &cell, true);
+        gtk::prelude::TreeViewColumnExt::add_attribute(&column, &cell, "text", column_id);
+        list.append_column(&column);
+    }
+
+    let scroller = ScrolledWindow::builder()
+        .shadow_type(ShadowType::In)
+        .hscrollbar_policy(PolicyType::Never)
+        .expand(true)
+        .build();
+    scroller.add(&list);
+    v_box.add(&scroller);
+
+    let status_label = Label::builder().halign(Align::Start).build();
+    v_box.add(&status_label);
+
+    let entries = Rc::new(entries);
+    let load_selected = {
+        let list = list.clone();
+        let request_radio = request_radio.clone();
+        move || {
+            let Some((model, iter)) = list.selection().selected() else {
+                return;
+            };
+            let index = model.value(&iter, 5).get::<u32>().unwrap() as usize;
+            let entry = &entries[index];
+
+            let body = if request_radio.is_active() {
+                decode_body(entry.request_body.as_ref(), "request")
+            } else {
+                decode_body(entry.response_body.as_ref(), "response")
+            };
+
+            match body {
+                Ok(text) => {
+                    // Non-JSON bodies are still loaded, the tree will say they are invalid
+                    let is_json =
+                        json_editor::parse_text(json_editor.clone(), text.as_str()).is_ok();
+                    status_label.set_label(if is_json { "" } else { "This body is not JSON" });
+                    json_editor::replace_buffer_text(
+                        &json_editor::retrieve_buffer(json_editor.clone()),
+                        &text,
+                    );
+                }
+                Err(e) => status_label.set_label(&e),
+            }
+        }
+    };
+
+    list.selection().connect_changed({
+        let load_selected = load_selected.clone();
+        move |_| load_selected()
+    });
+    request_radio.connect_toggled(move |_| load_selected());
+
+    window.show_all();
+}
@@ -1,19 +1,43 @@
-use crate::tree_view;
+use crate::json_parser::ParseMode;
+use crate::large_view::LargeViewState;
+use crate::preferences::PreferencesState;
+use crate::theme::Palette;
 use crate::tree_view::TreeViewState;
-use gtk::gdk::{EventKey, EventScroll, ModifierType, ScrollDirection};
-use gtk::glib::Propagation;
+use crate::undo::HistoryState;
+use crate::{canonical, cst, json_parser, json_pointer, large_view, syntax, tree_view, undo};
+use gtk::glib;
+use gtk::glib::SourceId;
+use gtk::pango::FontDescription;
 use gtk::prelude::*;
 use gtk::{
     ApplicationWindow, ButtonsType, CssProvider, MessageDialog, MessageType, ScrolledWindow,
-    ShadowType, StateFlags, TextBuffer, TextView, WindowPosition,
-    STYLE_PROVIDER_PRIORITY_APPLICATION,
+    ShadowType, Stack, TextBuffer, TextView, WindowPosition, STYLE_PROVIDER_PRIORITY_APPLICATION,
 };
 use serde_json::Value;
+use std::cell::{Cell, RefCell};
+use std::ops::Range;
+use std::path::PathBuf;
+use std::rc::Rc;
+use std::time::Duration;
+
+// Above this size the text is shown read-only in a LargeView instead of the TextView
+const LARGE_DOCUMENT_CHARS: i32 = 5_000_000;
+
+const HIGHLIGHT_DELAY: Duration = Duration::from_millis(150);
 
 #[derive(Clone)]
 pub struct JsonEditorState {
-    pub scrolled_window: ScrolledWindow,
+    pub stack: Stack,
     text_view: TextView,
+    // Kept separately as the TextView is given an empty buffer in large document mode
+    buffer: TextBuffer,
+    large_view: LargeViewState,
+    large_document: Rc<Cell<bool>>,
+    parse_mode: Rc<Cell<ParseMode>>,
+    history: HistoryState,
+    // The file the text was opened from or last saved to
+    file_path: Rc<RefCell<Option<PathBuf>>>,
+    pending_highlight: Rc<RefCell<Option<SourceId>>>,
 }
 
 pub fn factory_json_editor() -> JsonEditorState {
@@ -31,112 +55,200 @@ pub fn factory_json_editor() -> JsonEditorState {
         .build();
     scrolled_window.add(&text_view);
 
+    let buffer = text_view.buffer().unwrap();
+    syntax::create_tags(&buffer);
+    let history = HistoryState::default();
+    undo::attach_to_buffer(&buffer, history.clone());
+
+    let large_view = large_view::factory_large_view();
+
+    let stack = Stack::builder().visible(true).build();
+    stack.add_named(&scrolled_window, "editor");
+    stack.add_named(&large_view.container, "large");
+
     JsonEditorState {
-        scrolled_window,
+        stack,
         text_view,
+        buffer,
+        large_view,
+        large_document: Rc::new(Cell::new(false)),
+        parse_mode: Rc::new(Cell::new(ParseMode::Strict)),
+        history,
+        file_path: Rc::new(RefCell::new(None)),
+        pending_highlight: Rc::new(RefCell::new(None)),
     }
 }
 
 pub fn attach_listeners(json_editor: JsonEditorState, tree_view: TreeViewState) {
-    if let Some(buffer) = json_editor.text_view.buffer() {
+    let buffer = retrieve_buffer(json_editor.clone());
+    buffer.connect_changed({
+        move |_| {
+            update_large_document_mode(json_editor.clone());
+            schedule_highlight(json_editor.clone());
+            tree_view::build_tree_from_text(json_editor.clone(), tree_view.clone());
+        }
+    });
+}
+
+// Re-highlight once typing pauses
+fn schedule_highlight(json_editor: JsonEditorState) {
+    if let Some(pending_highlight) = json_editor.pending_highlight.take() {
+        pending_highlight.remove();
+    }
+
+    let source_id = glib::timeout_add_local_once(HIGHLIGHT_DELAY, {
         let json_editor = json_editor.clone();
-        let tree_view = tree_view.clone();
-        buffer.connect_changed({
-            move |_| {
-                tree_view::build_tree_from_text(json_editor.clone(), tree_view.clone());
-            }
-        });
+        move || {
+            json_editor.pending_highlight.take();
+            syntax::highlight(&json_editor.buffer);
+        }
+    });
+    json_editor.pending_highlight.replace(Some(source_id));
+}
+
+pub fn set_palette(json_editor: JsonEditorState, palette: &Palette) {
+    syntax::set_palette(&json_editor.buffer, palette);
+}
+
+// Switch between the editable TextView and the read-only LargeView depending on the size
+// of the text. The LargeView's text is updated by the background parse (see
+// show_large_text), as copying and indexing it on every change would hang.
+fn update_large_document_mode(json_editor: JsonEditorState) {
+    let buffer = retrieve_buffer(json_editor.clone());
+    let is_large = buffer.char_count() > LARGE_DOCUMENT_CHARS;
+
+    if is_large && !json_editor.large_document.get() {
+        json_editor.large_document.set(true);
+        // Detaching the buffer stops the TextView laying out the whole document
+        json_editor
+            .text_view
+            .set_buffer(Some(&TextBuffer::new(None::<&gtk::TextTagTable>)));
+        json_editor.stack.set_visible_child_name("large");
+        // Each step would hold another copy of the document, so nothing is recorded until
+        // the text is small enough to edit again
+        let mut history = json_editor.history.borrow_mut();
+        history.clear();
+        history.set_recording(false);
+    } else if !is_large && json_editor.large_document.get() {
+        json_editor.large_document.set(false);
+        json_editor.text_view.set_buffer(Some(&buffer));
+        json_editor.stack.set_visible_child_name("editor");
+        large_view::clear(json_editor.large_view.clone());
+        json_editor.history.borrow_mut().set_recording(true);
     }
 }
 
-pub fn init_on_show(json_editor: JsonEditorState) {
-    json_editor.text_view.grab_focus();
-    retrieve_buffer(json_editor.clone()).set_text("{}");
+// Show the text of a large document, with its rows as indexed by large_view::index_rows
+pub fn show_large_text(json_editor: JsonEditorState, text: String, rows: Vec<usize>) {
+    if json_editor.large_document.get() {
+        large_view::set_text(json_editor.large_view, text, rows);
+    }
 }
 
-pub fn retrieve_buffer(json_editor: JsonEditorState) -> TextBuffer {
-    json_editor.text_view.buffer().unwrap()
+pub fn is_large_document(json_editor: JsonEditorState) -> bool {
+    json_editor.large_document.get()
 }
 
-pub fn remove_double_newline_action(json_editor: JsonEditorState) {
-    let buffer = json_editor.text_view.buffer().unwrap();
-    let (start, end) = buffer.bounds();
-    let text_content = buffer.text(&start, &end, true).unwrap();
+pub fn init_on_show(json_editor: JsonEditorState, initial_text: &str) {
+    json_editor.text_view.grab_focus();
+    let buffer = retrieve_buffer(json_editor.clone());
+    buffer.set_text(initial_text);
+    // Nothing has been typed yet, so closing straight away shouldn't ask to save
+    buffer.set_modified(false);
+    json_editor.history.borrow_mut().clear();
+}
 
-    buffer.set_text(text_content.as_str().replace("\n\n", "").as_str());
+pub fn file_path(json_editor: JsonEditorState) -> Option<PathBuf> {
+    json_editor.file_path.borrow().clone()
 }
 
-pub fn ctrl_scroll_resize_text_view_action(
-    event_key: EventScroll,
-    json_editor: JsonEditorState,
-) -> Propagation {
-    if event_key.state().contains(ModifierType::CONTROL_MASK)
-        && (event_key.direction() == ScrollDirection::Down
-            || event_key.direction() == ScrollDirection::Up)
-    {
-        let mut dir = 1;
+pub fn set_file_path(json_editor: JsonEditorState, file_path: Option<PathBuf>) {
+    json_editor.file_path.replace(file_path);
+}
 
-        if event_key.direction() == ScrollDirection::Down {
-            dir = -1;
-        }
+pub fn retrieve_buffer(json_editor: JsonEditorState) -> TextBuffer {
+    json_editor.buffer
+}
 
-        let cur_size = json_editor
-            .text_view
-            .style_context()
-            .font(StateFlags::NORMAL)
-            .size()
-            / gtk::pango::SCALE;
-        let css_override = CssProvider::new();
-        let _ = css_override
-            .load_from_data(format!("* {{ font-size: {}pt; }}", cur_size + dir).as_bytes());
+pub fn text_view(json_editor: JsonEditorState) -> TextView {
+    json_editor.text_view
+}
 
+// Replace the whole buffer as a single undoable step
+pub fn replace_buffer_text(buffer: &TextBuffer, text: &str) {
+    buffer.begin_user_action();
+    buffer.set_text(text);
+    buffer.end_user_action();
+}
+
+// Select the text between two character offsets and scroll it into view
+pub fn select_range(json_editor: JsonEditorState, start: i32, end: i32) {
+    let buffer = retrieve_buffer(json_editor.clone());
+    buffer.select_range(&buffer.iter_at_offset(start), &buffer.iter_at_offset(end));
+    if json_editor.large_document.get() {
+        large_view::scroll_to_char_offset(json_editor.large_view, start);
+    } else if let Some(insert) = buffer.get_insert() {
         json_editor
             .text_view
-            .style_context()
-            .add_provider(&css_override, STYLE_PROVIDER_PRIORITY_APPLICATION);
+            .scroll_to_mark(&insert, 0.1, true, 0.0, 0.5);
     }
+}
 
-    Propagation::Proceed
+pub fn undo_action(json_editor: JsonEditorState) {
+    undo::undo(&retrieve_buffer(json_editor.clone()), json_editor.history);
 }
 
-pub fn ctrl_plus_minus_text_view_action(
-    event_key: EventKey,
-    json_editor: JsonEditorState,
-) -> Propagation {
-    if event_key.state().contains(ModifierType::CONTROL_MASK)
-        && (event_key.hardware_keycode() == 86 || event_key.hardware_keycode() == 82)
-    {
-        let mut dir = -1;
-        if event_key.hardware_keycode() == 86 {
-            dir = 1;
-        }
+pub fn redo_action(json_editor: JsonEditorState) {
+    undo::redo(&retrieve_buffer(json_editor.clone()), json_editor.history);
+}
 
-        let cur_size = json_editor
-            .text_view
-            .style_context()
-            .font(StateFlags::NORMAL)
-            .size()
-            / gtk::pango::SCALE;
-        let css_override = CssProvider::new();
-        let _ = css_override
-            .load_from_data(format!("* {{ font-size: {}pt; }}", cur_size + dir).as_bytes());
+pub fn set_parse_mode(json_editor: JsonEditorState, parse_mode: ParseMode) {
+    json_editor.parse_mode.set(parse_mode);
+}
 
-        json_editor
-            .text_view
-            .style_context()
-            .add_provider(&css_override, STYLE_PROVIDER_PRIORITY_APPLICATION);
-    }
+pub fn parse_mode(json_editor: JsonEditorState) -> ParseMode {
+    json_editor.parse_mode.get()
+}
+
+pub fn parse_text(json_editor: JsonEditorState, text: &str) -> Result<Value, String> {
+    json_parser::parse(text, json_editor.parse_mode.get())
+}
+
+pub fn remove_double_newline_action(json_editor: JsonEditorState) {
+    let buffer = retrieve_buffer(json_editor.clone());
+    let (start, end) = buffer.bounds();
+    let text_content = buffer.text(&start, &end, true).unwrap();
+
+    replace_buffer_text(&buffer, text_content.as_str().replace("\n\n", "").as_str());
+}
+
+pub fn add_style_provider(json_editor: JsonEditorState, provider: &CssProvider) {
+    json_editor
+        .text_view
+        .style_context()
+        .add_provider(provider, STYLE_PROVIDER_PRIORITY_APPLICATION);
+}
 
-    Propagation::Proceed
+// The read-only view of large documents draws its own text, so can't be styled with CSS
+pub fn set_large_view_font(json_editor: JsonEditorState, font: FontDescription) {
+    large_view::set_font(json_editor.large_view, font);
 }
 
-pub fn prettify_json_action(win: ApplicationWindow, json_editor: JsonEditorState) {
-    let buffer = json_editor.text_view.buffer().unwrap();
+pub fn prettify_json_action(
+    win: ApplicationWindow,
+    json_editor: JsonEditorState,
+    preferences: PreferencesState,
+) {
+    let buffer = retrieve_buffer(json_editor.clone());
     let (start, end) = buffer.bounds();
     let pretty_json = buffer.text(&start, &end, true).unwrap();
 
-    let v: Value = match serde_json::from_str(pretty_json.as_str()) {
-        Ok(v) => v,
+    // Validate according to the parse mode, then format the lossless syntax tree so that
+    // comments and number literals survive
+    let document = match parse_text(json_editor.clone(), pretty_json.as_str())
+        .and_then(|_| cst::parse(pretty_json.as_str()))
+    {
+        Ok(document) => document,
         Err(e) => {
             let error_dialog = MessageDialog::builder()
                 .transient_for(&win)
@@ -154,16 +266,21 @@ pub fn prettify_json_action(win: ApplicationWindow, json_editor: JsonEditorState
         }
     };
 
-    buffer.set_text(&serde_json::to_string_pretty(&v).unwrap());
+    replace_buffer_text(
+        &buffer,
+        &cst::prettify(&document, &preferences.borrow().format),
+    );
 }
 
 pub fn minify_json_action(win: ApplicationWindow, json_editor: JsonEditorState) {
-    let buffer = json_editor.text_view.buffer().unwrap();
+    let buffer = retrieve_buffer(json_editor.clone());
     let (start, end) = buffer.bounds();
     let ugly_json = buffer.text(&start, &end, true).unwrap();
 
-    let v: Value = match serde_json::from_str(ugly_json.as_str()) {
-        Ok(v) => v,
+    let document = match parse_text(json_editor.clone(), ugly_json.as_str())
+        .and_then(|_| cst::parse(ugly_json.as_str()))
+    {
+        Ok(document) => document,
         Err(e) => {
             let error_dialog = MessageDialog::builder()
                 .transient_for(&win)
@@ -181,29 +298,32 @@ pub fn minify_json_action(win: ApplicationWindow, json_editor: JsonEditorState)
         }
     };
 
-    buffer.set_text(&serde_json::to_string(&v).unwrap());
+    replace_buffer_text(&buffer, &cst::minify(&document));
+}
+
+// Interpret text as a JSON string, with or without its surrounding quotes
+// Example input: {\"a\":1} or "{\"a\":1}"
+// Output: {"a":1}
+pub fn unescape_json_text(text: &str) -> Result<String, serde_json::Error> {
+    // 1) First, try to parse the whole text as a JSON string
+    if let Ok(unescaped) = serde_json::from_str::<String>(text) {
+        return Ok(unescaped);
+    }
+
+    // 2) If that failed, try treating the text as the content of a JSON string
+    //    by wrapping it in quotes (useful when the text is missing surrounding quotes)
+    serde_json::from_str::<String>(&format!("\"{}\"", text))
 }
 
 pub fn unescape_json_action(win: ApplicationWindow, json_editor: JsonEditorState) {
     // Unescape a buffer that contains JSON encoded as a JSON string
-    // Example input: {\"a\":1} or "{\"a\":1}"
-    // Output: {"a":1}
-    let buffer = json_editor.text_view.buffer().unwrap();
+    let buffer = retrieve_buffer(json_editor.clone());
     let (start, end) = buffer.bounds();
     let current_text = buffer.text(&start, &end, true).unwrap();
 
-    // 1) First, try to parse the whole buffer as a JSON string
-    if let Ok(unescaped) = serde_json::from_str::<String>(current_text.as_str()) {
-        buffer.set_text(&unescaped);
-        return;
-    }
-
-    // 2) If that failed, try treating the buffer as the content of a JSON string
-    //    by wrapping it in quotes (useful when the buffer is missing surrounding quotes)
-    let wrapped = format!("\"{}\"", current_text);
-    match serde_json::from_str::<String>(&wrapped) {
+    match unescape_json_text(current_text.as_str()) {
         Ok(unescaped) => {
-            buffer.set_text(&unescaped);
+            replace_buffer_text(&buffer, &unescaped);
         }
         Err(e) => {
             let error_dialog = MessageDialog::builder()
@@ -229,10 +349,148 @@ pub fn escape_json_action(json_editor: JsonEditorState) {
     // Escape the current buffer into a JSON string
     // Example input: {"a":1}
     // Output: "{\"a\":1}"
-    let buffer = json_editor.text_view.buffer().unwrap();
+    let buffer = retrieve_buffer(json_editor.clone());
     let (start, end) = buffer.bounds();
     let current_text = buffer.text(&start, &end, true).unwrap();
 
     let escaped = serde_json::to_string(current_text.as_str()).unwrap();
-    buffer.set_text(&escaped);
+    replace_buffer_text(&buffer, &escaped);
+}
+
+pub fn normalize_json_action(win: ApplicationWindow, json_editor: JsonEditorState) {
+    // Rewrite JSON5/JSONC (comments, trailing commas, single quotes, unquoted keys...)
+    // into strict JSON, regardless of the current parse mode
+    let buffer = retrieve_buffer(json_editor.clone());
+    let (start, end) = buffer.bounds();
+    let lenient_json = buffer.text(&start, &end, true).unwrap();
+
+    match json_parser::normalize_to_strict(lenient_json.as_str()) {
+        Ok(strict_json) => replace_buffer_text(&buffer, &strict_json),
+        Err(e) => {
+            let error_dialog = MessageDialog::builder()
+                .transient_for(&win)
+                .window_position(WindowPosition::CenterOnParent)
+                .message_type(MessageType::Warning)
+                .buttons(ButtonsType::Ok)
+                .title("JSON was invalid")
+                .text(format!(
+                    "The current text could not be parsed, even as JSON5/JSONC.\n\n{}",
+                    e
+                ))
+                .build();
+            error_dialog.connect_response(move |error_dialog, _| {
+                error_dialog.close();
+            });
+            error_dialog.run();
+        }
+    }
+}
+
+pub fn canonicalize_json_action(win: ApplicationWindow, json_editor: JsonEditorState) {
+    // RFC 8785: sorted keys, no whitespace and ECMAScript number formatting
+    let buffer = retrieve_buffer(json_editor.clone());
+    let (start, end) = buffer.bounds();
+    let current_text = buffer.text(&start, &end, true).unwrap();
+
+    let v: Value = match parse_text(json_editor.clone(), current_text.as_str()) {
+        Ok(v) => v,
+        Err(e) => {
+            let error_dialog = MessageDialog::builder()
+                .transient_for(&win)
+                .window_position(WindowPosition::CenterOnParent)
+                .message_type(MessageType::Warning)
+                .buttons(ButtonsType::Ok)
+                .title("JSON was invalid")
+                .text(format!("The current text was not valid JSON.\n\n{}", e))
+                .build();
+            error_dialog.connect_response(move |error_dialog, _| {
+                error_dialog.close();
+            });
+            error_dialog.run();
+            return;
+        }
+    };
+
+    replace_buffer_text(&buffer, &canonical::canonicalize(&v));
+}
+
+pub fn edit_value_action(
+    win: ApplicationWindow,
+    json_editor: JsonEditorState,
+    pointer: &str,
+    new_text: &str,
+) {
+    // Replace a single scalar value in place, leaving every other byte of the buffer untouched
+    let buffer = retrieve_buffer(json_editor.clone());
+    let (start, end) = buffer.bounds();
+    let current_text = buffer.text(&start, &end, true).unwrap();
+    let Some(span) = value_span(&win, current_text.as_str(), pointer) else {
+        return;
+    };
+
+    // Strings stay strings; anything else may become any scalar literal typed by the user,
+    // falling back to a string if it isn't one
+    let old_literal = &current_text[span.clone()];
+    let new_literal = match serde_json::from_str::<Value>(new_text.trim()) {
+        Ok(v) if !old_literal.starts_with(['"', '\'']) && !v.is_object() && !v.is_array() => {
+            new_text.trim().to_string()
+        }
+        _ => serde_json::to_string(new_text).unwrap(),
+    };
+    if new_literal == old_literal {
+        return;
+    }
+
+    replace_span(&buffer, current_text.as_str(), span, &new_literal);
+}
+
+// Replace a string value with the JSON it holds, laid out like the Pretty button
+pub fn replace_with_nested_action(
+    win: ApplicationWindow,
+    json_editor: JsonEditorState,
+    pointer: &str,
+    nested: &Value,
+    options: &cst::FormatOptions,
+) {
+    let buffer = retrieve_buffer(json_editor.clone());
+    let (start, end) = buffer.bounds();
+    let current_text = buffer.text(&start, &end, true).unwrap();
+    let Some(span) = value_span(&win, current_text.as_str(), pointer) else {
+        return;
+    };
+
+    let replacement = cst::prettify_in_place(current_text.as_str(), span.start, nested, options);
+    replace_span(&buffer, current_text.as_str(), span, &replacement);
+}
+
+// Byte range of the value at a JSON Pointer, if the text is valid
+fn value_span(win: &ApplicationWindow, text: &str, pointer: &str) -> Option<Range<usize>> {
+    match cst::parse(text) {
+        Ok(document) => document.value_span(&json_pointer::segments(pointer)),
+        Err(e) => {
+            let error_dialog = MessageDialog::builder()
+                .transient_for(win)
+                .window_position(WindowPosition::CenterOnParent)
+                .message_type(MessageType::Warning)
+                .buttons(ButtonsType::Ok)
+                .title("JSON was invalid")
+                .text(format!("The current text was not valid JSON.\n\n{}", e))
+                .build();
+            error_dialog.connect_response(move |error_dialog, _| {
+                error_dialog.close();
+            });
+            error_dialog.run();
+            None
+        }
+    }
+}
+
+// Leaves every other byte of the buffer untouched, as one step to undo
+fn replace_span(buffer: &TextBuffer, text: &str, span: Range<usize>, replacement: &str) {
+    let mut start = buffer.iter_at_offset(text[..span.start].chars().count() as i32);
+    let mut end = buffer.iter_at_offset(text[..span.end].chars().count() as i32);
+    buffer.begin_user_action();
+    buffer.delete(&mut start, &mut end);
+    buffer.insert(&mut start, replacement);
+    buffer.end_user_action();
 }
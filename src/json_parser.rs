@@ -0,0 +1,533 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::ops::Range;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
+pub enum ParseMode {
+    // Only standard JSON (RFC 8259) is accepted
+    #[default]
+    Strict,
+    // JSON5/JSONC: comments, trailing commas, single-quoted strings, unquoted keys etc.
+    Lenient,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum TokenKind {
+    LBrace,
+    RBrace,
+    LBracket,
+    RBracket,
+    Colon,
+    Comma,
+    String,
+    Number,
+    Ident,
+    Whitespace,
+    LineComment,
+    BlockComment,
+}
+
+impl TokenKind {
+    pub fn is_trivia(self) -> bool {
+        matches!(
+            self,
+            TokenKind::Whitespace | TokenKind::LineComment | TokenKind::BlockComment
+        )
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Token {
+    pub kind: TokenKind,
+    pub span: Range<usize>,
+}
+
+pub fn parse(text: &str, mode: ParseMode) -> Result<Value, String> {
+    match mode {
+        ParseMode::Strict => serde_json::from_str(text).map_err(|e| e.to_string()),
+        ParseMode::Lenient => {
+            // Strict JSON is a subset of JSON5, so only rewrite when it's actually needed
+            serde_json::from_str(text).or_else(|_| {
+                let strict = to_strict(text)?;
+                serde_json::from_str(&strict).map_err(|e| e.to_string())
+            })
+        }
+    }
+}
+
+pub fn normalize_to_strict(text: &str) -> Result<String, String> {
+    let v = parse(text, ParseMode::Lenient)?;
+    Ok(serde_json::to_string_pretty(&v).unwrap())
+}
+
+// Rewrite JSON5/JSONC into strict JSON token by token. Comments are replaced by the
+// newlines they contained so that line numbers in any later error messages still match.
+fn to_strict(text: &str) -> Result<String, String> {
+    let tokens = tokenize(text)?;
+    let significant: Vec<usize> = (0..tokens.len())
+        .filter(|&i| !tokens[i].kind.is_trivia())
+        .collect();
+    let mut out = String::with_capacity(text.len());
+
+    for (pos, &i) in significant.iter().enumerate() {
+        let token = &tokens[i];
+        let raw = &text[token.span.clone()];
+        let next = significant.get(pos + 1).map(|&n| tokens[n].kind);
+        let previous = pos.checked_sub(1).map(|p| tokens[significant[p]].kind);
+
+        // Copy the trivia between the previous significant token and this one
+        let trivia_start = if pos == 0 {
+            0
+        } else {
+            significant[pos - 1] + 1
+        };
+        push_trivia(&mut out, text, &tokens[trivia_start..i]);
+
+        // Values with nothing between them, like `0x1e+1`, must not run together as one
+        let is_value = |kind| {
+            matches!(
+                kind,
+                TokenKind::String | TokenKind::Number | TokenKind::Ident
+            )
+        };
+        if trivia_start == i && is_value(token.kind) && previous.is_some_and(is_value) {
+            out.push(' ');
+        }
+
+        match token.kind {
+            // Only a comma after a value is trailing, so `[,]` is still rejected
+            TokenKind::Comma
+                if matches!(next, Some(TokenKind::RBrace) | Some(TokenKind::RBracket))
+                    && !matches!(
+                        previous,
+                        None | Some(TokenKind::LBrace | TokenKind::LBracket | TokenKind::Comma)
+                    ) => {}
+            TokenKind::String => {
+                out.push_str(&serde_json::to_string(&decode_string(raw)?).unwrap());
+            }
+            TokenKind::Number => out.push_str(&normalize_number(raw)?),
+            TokenKind::Ident => match raw {
+                "true" | "false" | "null" if next != Some(TokenKind::Colon) => out.push_str(raw),
+                _ if next == Some(TokenKind::Colon) => {
+                    out.push_str(&serde_json::to_string(&decode_identifier(raw)?).unwrap());
+                }
+                _ => {
+                    return Err(format!(
+                        "unexpected identifier `{}` at {}",
+                        raw,
+                        describe_offset(text, token.span.start)
+                    ))
+                }
+            },
+            _ => out.push_str(raw),
+        }
+    }
+
+    let trailing_start = significant.last().map_or(0, |&i| i + 1);
+    push_trivia(&mut out, text, &tokens[trailing_start..]);
+
+    Ok(out)
+}
+
+fn push_trivia(out: &mut String, text: &str, tokens: &[Token]) {
+    for token in tokens {
+        let raw = &text[token.span.clone()];
+        match token.kind {
+            TokenKind::Whitespace => {
+                // JSON5 allows extra whitespace characters (e.g. NBSP) which JSON does not
+                out.extend(raw.chars().map(|c| match c {
+                    ' ' | '\t' | '\n' | '\r' => c,
+                    _ => ' ',
+                }));
+            }
+            _ => out.extend(raw.chars().filter(|&c| c == '\n')),
+        }
+    }
+}
+
+pub fn tokenize(text: &str) -> Result<Vec<Token>, String> {
+    let bytes = text.as_bytes();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    while i < bytes.len() {
+        let start = i;
+        let c = text[i..].chars().next().unwrap();
+        let kind = match c {
+            '{' => single(&mut i, TokenKind::LBrace),
+            '}' => single(&mut i, TokenKind::RBrace),
+            '[' => single(&mut i, TokenKind::LBracket),
+            ']' => single(&mut i, TokenKind::RBracket),
+            ':' => single(&mut i, TokenKind::Colon),
+            ',' => single(&mut i, TokenKind::Comma),
+            '"' | '\'' => {
+                i = scan_string(text, i)?;
+                TokenKind::String
+            }
+            '/' if bytes.get(i + 1) == Some(&b'/') => {
+                i = text[i..].find('\n').map_or(bytes.len(), |n| i + n);
+                TokenKind::LineComment
+            }
+            '/' if bytes.get(i + 1) == Some(&b'*') => {
+                i = match text[i + 2..].find("*/") {
+                    Some(n) => i + 2 + n + 2,
+                    None => {
+                        return Err(format!(
+                            "unterminated block comment at {}",
+                            describe_offset(text, start)
+                        ))
+                    }
+                };
+                TokenKind::BlockComment
+            }
+            '+' | '-' | '.' | '0'..='9' => {
+                i = scan_number(text, i);
+                TokenKind::Number
+            }
+            _ if is_json5_whitespace(c) => {
+                while let Some(c) = text[i..].chars().next().filter(|&c| is_json5_whitespace(c)) {
+                    i += c.len_utf8();
+                }
+                TokenKind::Whitespace
+            }
+            _ if is_identifier_char(c) => {
+                while let Some(c) = text[i..].chars().next().filter(|&c| is_identifier_char(c)) {
+                    i += c.len_utf8();
+                }
+                // Infinity and NaN are numbers in JSON5
+                match &text[start..i] {
+                    "Infinity" | "NaN" => TokenKind::Number,
+                    _ => TokenKind::Ident,
+                }
+            }
+            _ => {
+                return Err(format!(
+                    "unexpected character `{}` at {}",
+                    c,
+                    describe_offset(text, start)
+                ))
+            }
+        };
+        tokens.push(Token {
+            kind,
+            span: start..i,
+        });
+    }
+
+    Ok(tokens)
+}
+
+fn single(i: &mut usize, kind: TokenKind) -> TokenKind {
+    *i += 1;
+    kind
+}
+
+fn scan_string(text: &str, start: usize) -> Result<usize, String> {
+    let bytes = text.as_bytes();
+    let quote = bytes[start];
+    let mut i = start + 1;
+    while i < bytes.len() {
+        match bytes[i] {
+            // Skip escaped characters, including \r\n line continuations
+            b'\\' if bytes.get(i + 1) == Some(&b'\r') && bytes.get(i + 2) == Some(&b'\n') => i += 3,
+            b'\\' => i += 2,
+            b if b == quote => return Ok(i + 1),
+            b'\n' => break,
+            _ => i += 1,
+        }
+    }
+    Err(format!(
+        "unterminated string at {}",
+        describe_offset(text, start)
+    ))
+}
+
+fn scan_number(text: &str, start: usize) -> usize {
+    let mut i = start;
+    let bytes = text.as_bytes();
+    if matches!(bytes[i], b'+' | b'-') {
+        i += 1;
+    }
+    // Covers digits, hex digits, exponents, Infinity and NaN; validated in normalize_number
+    while i < bytes.len() {
+        match bytes[i] {
+            b'0'..=b'9' | b'a'..=b'z' | b'A'..=b'Z' | b'.' => i += 1,
+            b'+' | b'-'
+                if matches!(bytes[i - 1], b'e' | b'E') && !is_hex_number(&text[start..i]) =>
+            {
+                i += 1
+            }
+            _ => break,
+        }
+    }
+    i
+}
+
+fn is_hex_number(raw: &str) -> bool {
+    let unsigned = raw.trim_start_matches(['+', '-']);
+    unsigned.starts_with("0x") || unsigned.starts_with("0X")
+}
+
+pub fn normalize_number(raw: &str) -> Result<String, String> {
+    let (negative, unsigned) = match raw.as_bytes().first() {
+        Some(b'-') => (true, &raw[1..]),
+        Some(b'+') => (false, &raw[1..]),
+        _ => (false, raw),
+    };
+    let sign = if negative { "-" } else { "" };
+
+    // There is no way to represent these in JSON, so treat them like JSON.stringify does
+    if unsigned == "Infinity" || unsigned == "NaN" {
+        return Ok("null".to_string());
+    }
+
+    if let Some(hex) = unsigned
+        .strip_prefix("0x")
+        .or_else(|| unsigned.strip_prefix("0X"))
+    {
+        return u128::from_str_radix(hex, 16)
+            .map(|n| format!("{}{}", sign, n))
+            .map_err(|_| format!("invalid hexadecimal number `{}`", raw));
+    }
+
+    let (mantissa, exponent) = match unsigned.find(['e', 'E']) {
+        Some(n) => (&unsigned[..n], &unsigned[n..]),
+        None => (unsigned, ""),
+    };
+    if !mantissa.bytes().any(|b| b.is_ascii_digit()) {
+        return Err(format!("invalid number `{}`", raw));
+    }
+    let (int_part, frac_part) = match mantissa.split_once('.') {
+        Some((int_part, frac_part)) => (int_part, frac_part),
+        None => (mantissa, ""),
+    };
+    let int_part = if int_part.is_empty() { "0" } else { int_part };
+    let frac_part = if frac_part.is_empty() {
+        String::new()
+    } else {
+        format!(".{}", frac_part)
+    };
+
+    let strict = format!("{}{}{}{}", sign, int_part, frac_part, exponent);
+    match serde_json::from_str::<Value>(&strict) {
+        Ok(Value::Number(_)) => Ok(strict),
+        _ => Err(format!("invalid number `{}`", raw)),
+    }
+}
+
+pub fn decode_string(raw: &str) -> Result<String, String> {
+    let inner = &raw[1..raw.len() - 1];
+    let mut out = String::with_capacity(inner.len());
+    let mut chars = inner.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('b') => out.push('\u{8}'),
+            Some('f') => out.push('\u{c}'),
+            Some('n') => out.push('\n'),
+            Some('r') => out.push('\r'),
+            Some('t') => out.push('\t'),
+            Some('v') => out.push('\u{b}'),
+            Some('0') => out.push('\0'),
+            Some('x') => {
+                let hex: String = chars.by_ref().take(2).collect();
+                let code = parse_hex_escape(&hex, 2)
+                    .ok_or_else(|| format!("invalid escape `\\x{}` in string", hex))?;
+                out.push(char::from_u32(code).unwrap());
+            }
+            Some('u') => {
+                let hex: String = chars.by_ref().take(4).collect();
+                let code = parse_hex_escape(&hex, 4)
+                    .ok_or_else(|| format!("invalid escape `\\u{}` in string", hex))?;
+                out.push(decode_utf16_escape(code, &mut chars));
+            }
+            // Line continuations
+            Some('\r') => {
+                chars.next_if_eq(&'\n');
+            }
+            Some('\n') | Some('\u{2028}') | Some('\u{2029}') => {}
+            // Any other escaped character (including quotes and backslash) is itself
+            Some(other) => out.push(other),
+            None => return Err("string ends with a lone backslash".to_string()),
+        }
+    }
+
+    Ok(out)
+}
+
+// Exactly that many hex digits, as from_str_radix alone also takes a sign
+fn parse_hex_escape(hex: &str, digits: usize) -> Option<u32> {
+    if hex.len() == digits && hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+        u32::from_str_radix(hex, 16).ok()
+    } else {
+        None
+    }
+}
+
+fn decode_utf16_escape(high: u32, chars: &mut std::iter::Peekable<std::str::Chars>) -> char {
+    if (0xD800..0xDC00).contains(&high) {
+        let mut lookahead = chars.clone();
+        if lookahead.next() == Some('\\') && lookahead.next() == Some('u') {
+            let hex: String = lookahead.by_ref().take(4).collect();
+            if let Some(low @ 0xDC00..=0xDFFF) = parse_hex_escape(&hex, 4) {
+                *chars = lookahead;
+                return char::from_u32(0x10000 + ((high - 0xD800) << 10) + (low - 0xDC00)).unwrap();
+            }
+        }
+    }
+    char::from_u32(high).unwrap_or(char::REPLACEMENT_CHARACTER)
+}
+
+pub fn decode_identifier(raw: &str) -> Result<String, String> {
+    if raw.contains('\\') {
+        // Identifiers may contain unicode escapes, which decode exactly like string escapes
+        decode_string(&format!("\"{}\"", raw))
+    } else {
+        Ok(raw.to_string())
+    }
+}
+
+fn is_json5_whitespace(c: char) -> bool {
+    matches!(
+        c,
+        ' ' | '\t'
+            | '\n'
+            | '\r'
+            | '\u{b}'
+            | '\u{c}'
+            | '\u{a0}'
+            | '\u{feff}'
+            | '\u{2028}'
+            | '\u{2029}'
+    ) || (c.is_whitespace() && !c.is_ascii())
+}
+
+fn is_identifier_char(c: char) -> bool {
+    c == '_' || c == '$' || c == '\\' || c.is_alphanumeric()
+}
+
+pub fn describe_offset(text: &str, offset: usize) -> String {
+    let before = &text[..offset];
+    let line = before.matches('\n').count() + 1;
+    let column = before.rsplit('\n').next().unwrap().chars().count() + 1;
+    format!("line {} column {}", line, column)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn lenient(text: &str) -> Result<Value, String> {
+        parse(text, ParseMode::Lenient)
+    }
+
+    #[test]
+    fn strict_rejects_json5() {
+        assert_eq!(parse("[1, 2]", ParseMode::Strict), Ok(json!([1, 2])));
+        assert!(parse("[1, 2,]", ParseMode::Strict).is_err());
+        assert!(parse("// comment\n[]", ParseMode::Strict).is_err());
+        assert!(parse("{a: 1}", ParseMode::Strict).is_err());
+    }
+
+    #[test]
+    fn comments_and_trailing_commas() {
+        let text =
+            "// leading\n{\n  /* block */ \"a\": [1, 2,], // after\n  \"b\": {\"c\": true,},\n}";
+        assert_eq!(lenient(text), Ok(json!({"a": [1, 2], "b": {"c": true}})));
+        assert_eq!(lenient("[1] // no newline at the end"), Ok(json!([1])));
+    }
+
+    #[test]
+    fn commas_without_a_value_before_them() {
+        for text in ["[,]", "{,}", "[1,,]", "[,1]", "{\"a\": 1,,}"] {
+            assert!(lenient(text).is_err(), "{} was accepted", text);
+        }
+    }
+
+    #[test]
+    fn quotes_and_keys() {
+        assert_eq!(
+            lenient("{unquoted: 'single \"quoted\"', $_id2: 'it\\'s'}"),
+            Ok(json!({"unquoted": "single \"quoted\"", "$_id2": "it's"}))
+        );
+        // Identifiers may contain unicode escapes
+        assert_eq!(lenient(r#"{\u0061b: 1}"#), Ok(json!({"ab": 1})));
+        // Keywords are still values, but can be keys
+        assert_eq!(lenient("{null: null}"), Ok(json!({"null": null})));
+        assert!(lenient("[undefined]").is_err());
+    }
+
+    #[test]
+    fn numbers() {
+        assert_eq!(
+            lenient("[0x1F, -0XfF, +1, .5, 5., -.5e1, 1e+2]"),
+            Ok(json!([31, -255, 1, 0.5, 5, -5.0, 100.0]))
+        );
+        assert_eq!(
+            lenient("[Infinity, -Infinity, NaN]"),
+            Ok(json!([null, null, null]))
+        );
+        for text in ["[0x]", "[0xG]", "[.]", "[+]", "[1e]", "[0x1e+1]"] {
+            assert!(lenient(text).is_err(), "{} was accepted", text);
+        }
+    }
+
+    #[test]
+    fn escapes() {
+        assert_eq!(
+            decode_string(r#"'\x41é😀\v\0\q'"#),
+            Ok("Aé😀\u{b}\0q".to_string())
+        );
+        // A lone surrogate can't be represented, so it is replaced
+        assert_eq!(decode_string(r#""\ud800x""#), Ok("\u{fffd}x".to_string()));
+        assert_eq!(
+            decode_string("'line \\\ncontinued \\\r\nagain'"),
+            Ok("line continued again".to_string())
+        );
+    }
+
+    #[test]
+    fn escapes_need_every_hex_digit() {
+        for raw in [
+            r#""\x+1""#,
+            r#""\x4""#,
+            r#""\u+041""#,
+            r#""\u-041""#,
+            r#""\u12""#,
+            r#""\""#,
+        ] {
+            assert!(decode_string(raw).is_err(), "{} was accepted", raw);
+        }
+        // Nor is a sign taken for the second half of a surrogate pair
+        assert_eq!(
+            decode_string(r#""\ud83d\u+e00""#),
+            Err("invalid escape `\\u+e00` in string".to_string())
+        );
+    }
+
+    #[test]
+    fn whitespace() {
+        assert_eq!(lenient("\u{feff}\u{a0}[\u{2028}1\u{b}]"), Ok(json!([1])));
+    }
+
+    #[test]
+    fn errors_keep_their_line() {
+        let error = lenient("/* one\ntwo */\n[1 2]").unwrap_err();
+        assert!(error.contains("line 3"), "{}", error);
+        let error = lenient("[\n'unterminated]").unwrap_err();
+        assert_eq!(error, "unterminated string at line 2 column 1");
+    }
+
+    #[test]
+    fn normalize_keeps_strict_json() {
+        assert_eq!(
+            normalize_to_strict("{a: [1,],}"),
+            Ok("{\n  \"a\": [\n    1\n  ]\n}".to_string())
+        );
+    }
+}
@@ -0,0 +1,19 @@
+// Helpers for RFC 6901 JSON Pointers, e.g. `/users/0/name`. The root is the empty pointer.
+
+pub fn append(pointer: &str, segment: &str) -> String {
+    format!(
+        "{}/{}",
+        pointer,
+        segment.replace('~', "~0").replace('/', "~1")
+    )
+}
+
+pub fn segments(pointer: &str) -> Vec<String> {
+    if pointer.is_empty() {
+        return Vec::new();
+    }
+    pointer[1..]
+        .split('/')
+        .map(|s| s.replace("~1", "/").replace("~0", "~"))
+        .collect()
+}
@@ -0,0 +1,200 @@
+// Read-only view for documents too large for a TextView, which measures every line up
+// front. Only the rows currently on screen are laid out and drawn.
+use gtk::gdk::{EventMask, ScrollDirection};
+use gtk::glib::Propagation;
+use gtk::pango::FontDescription;
+use gtk::prelude::*;
+use gtk::{Adjustment, DrawingArea, Orientation, Scrollbar};
+use std::cell::RefCell;
+use std::rc::Rc;
+
+// Long lines are wrapped at this many characters, so minified documents stay readable
+const WRAP_COLUMNS: usize = 120;
+
+const SCROLL_ROWS: f64 = 3.0;
+
+#[derive(Clone)]
+pub struct LargeViewState {
+    pub container: gtk::Box,
+    drawing_area: DrawingArea,
+    adjustment: Adjustment,
+    text: Rc<RefCell<String>>,
+    // Byte offset at which each displayed row starts
+    rows: Rc<RefCell<Vec<usize>>>,
+    font: Rc<RefCell<FontDescription>>,
+}
+
+pub fn factory_large_view() -> LargeViewState {
+    let drawing_area = DrawingArea::builder()
+        .visible(true)
+        .expand(true)
+        .can_focus(true)
+        .build();
+    drawing_area.add_events(EventMask::SCROLL_MASK | EventMask::SMOOTH_SCROLL_MASK);
+    // Use the same background and text colours as the editor
+    drawing_area.style_context().add_class("view");
+
+    let adjustment = Adjustment::new(0.0, 0.0, 0.0, 1.0, 1.0, 0.0);
+    let scrollbar = Scrollbar::builder()
+        .visible(true)
+        .orientation(Orientation::Vertical)
+        .adjustment(&adjustment)
+        .build();
+
+    let container = gtk::Box::builder()
+        .visible(true)
+        .orientation(Orientation::Horizontal)
+        .build();
+    container.pack_start(&drawing_area, true, true, 0);
+    container.pack_end(&scrollbar, false, false, 0);
+
+    let large_view = LargeViewState {
+        container,
+        drawing_area,
+        adjustment,
+        text: Rc::new(RefCell::new(String::new())),
+        rows: Rc::new(RefCell::new(vec![0])),
+        font: Rc::new(RefCell::new(FontDescription::from_string("Monospace"))),
+    };
+    attach_listeners(large_view.clone());
+
+    large_view
+}
+
+fn attach_listeners(large_view: LargeViewState) {
+    large_view.adjustment.connect_value_changed({
+        let drawing_area = large_view.drawing_area.clone();
+        move |_| drawing_area.queue_draw()
+    });
+
+    large_view.drawing_area.connect_size_allocate({
+        let large_view = large_view.clone();
+        move |_, _| update_adjustment(&large_view)
+    });
+
+    large_view.drawing_area.connect_scroll_event({
+        let adjustment = large_view.adjustment.clone();
+        move |_, event| {
+            let delta = match event.direction() {
+                ScrollDirection::Up => -SCROLL_ROWS,
+                ScrollDirection::Down => SCROLL_ROWS,
+                ScrollDirection::Smooth => event.delta().1 * SCROLL_ROWS,
+                _ => return Propagation::Proceed,
+            };
+            adjustment.set_value(adjustment.value() + delta);
+            Propagation::Stop
+        }
+    });
+
+    large_view.drawing_area.connect_draw({
+        let large_view = large_view.clone();
+        move |drawing_area, cr| {
+            let style_context = drawing_area.style_context();
+            let width = drawing_area.allocated_width() as f64;
+            let height = drawing_area.allocated_height() as f64;
+            gtk::render_background(&style_context, cr, 0.0, 0.0, width, height);
+
+            let text = large_view.text.borrow();
+            let rows = large_view.rows.borrow();
+            let first = (large_view.adjustment.value() as usize).min(rows.len());
+            let last = (first + visible_rows(&large_view) + 1).min(rows.len());
+
+            let mut visible_text = String::new();
+            for row in first..last {
+                visible_text.push_str(row_text(&text, &rows, row));
+                visible_text.push('\n');
+            }
+
+            let layout = drawing_area.create_pango_layout(Some(&visible_text));
+            layout.set_font_description(Some(&large_view.font.borrow()));
+            gtk::render_layout(&style_context, cr, 4.0, 0.0, &layout);
+            Propagation::Stop
+        }
+    });
+}
+
+fn row_text<'a>(text: &'a str, rows: &[usize], row: usize) -> &'a str {
+    let end = rows.get(row + 1).copied().unwrap_or(text.len());
+    text[rows[row]..end].trim_end_matches(['\n', '\r'])
+}
+
+fn row_height(large_view: &LargeViewState) -> i32 {
+    let layout = large_view.drawing_area.create_pango_layout(Some("X"));
+    layout.set_font_description(Some(&large_view.font.borrow()));
+    layout.pixel_size().1.max(1)
+}
+
+fn visible_rows(large_view: &LargeViewState) -> usize {
+    (large_view.drawing_area.allocated_height() / row_height(large_view)).max(1) as usize
+}
+
+fn update_adjustment(large_view: &LargeViewState) {
+    let page = visible_rows(large_view) as f64;
+    let upper = large_view.rows.borrow().len() as f64;
+    large_view.adjustment.configure(
+        large_view.adjustment.value().min((upper - page).max(0.0)),
+        0.0,
+        upper,
+        1.0,
+        page,
+        page,
+    );
+}
+
+// Where each displayed row starts, which takes a while for large documents so is done by
+// the background parse
+pub fn index_rows(text: &str) -> Vec<usize> {
+    let mut rows = vec![0];
+    let mut column = 0;
+    for (i, c) in text.char_indices() {
+        if c == '\n' {
+            rows.push(i + 1);
+            column = 0;
+            continue;
+        }
+        if column == WRAP_COLUMNS {
+            rows.push(i);
+            column = 0;
+        }
+        column += 1;
+    }
+    rows
+}
+
+// Show text along with its rows from index_rows
+pub fn set_text(large_view: LargeViewState, text: String, rows: Vec<usize>) {
+    large_view.rows.replace(rows);
+    large_view.text.replace(text);
+    update_adjustment(&large_view);
+    large_view.drawing_area.queue_draw();
+}
+
+pub fn set_font(large_view: LargeViewState, font: FontDescription) {
+    large_view.font.replace(font);
+    update_adjustment(&large_view);
+    large_view.drawing_area.queue_draw();
+}
+
+pub fn clear(large_view: LargeViewState) {
+    set_text(large_view, String::new(), vec![0]);
+}
+
+// Scroll so that the row containing the given character offset is in the middle
+pub fn scroll_to_char_offset(large_view: LargeViewState, char_offset: i32) {
+    let byte_offset = {
+        let text = large_view.text.borrow();
+        text.char_indices()
+            .nth(char_offset.max(0) as usize)
+            .map_or(text.len(), |(i, _)| i)
+    };
+    let row = large_view
+        .rows
+        .borrow()
+        .partition_point(|start| *start <= byte_offset)
+        .saturating_sub(1);
+
+    let page = large_view.adjustment.page_size();
+    large_view
+        .adjustment
+        .set_value((row as f64 - page / 2.0).max(0.0));
+}
@@ -7,11 +7,38 @@ use gtk::{Application, ApplicationWindow, Box, Orientation, Paned, WindowPositio
 use std::cell::Cell;
 use std::rc::Rc;
 
+mod actions;
+mod canonical;
+mod command_palette;
+mod cst;
+mod detail_pane;
+mod drag_drop;
+mod encoding;
+mod extract_json;
 mod file_mgt;
+mod har;
 mod json_editor;
+mod json_parser;
+mod json_pointer;
+mod jwt;
+mod large_view;
 mod menu_bar;
+mod nested_json;
+mod parse_summary;
+mod preferences;
+mod recent;
+mod search_bar;
+mod session;
+mod sort_keys;
+mod statistics;
+mod status_bar;
+mod syntax;
+mod theme;
 mod tool_bar;
 mod tree_view;
+mod undo;
+mod units;
+mod zoom;
 
 fn main() {
     let app = Application::builder()
@@ -23,17 +50,32 @@ fn main() {
             MemoryInputStream::from_bytes(&Bytes::from(include_bytes!("../fire-emoji.ico")));
         let fire_emoji_icon_pb = Pixbuf::from_stream(&stream, Cancellable::NONE).unwrap();
 
+        let preferences = preferences::load();
+        let recent = recent::load();
+
         let screen_height = unsafe { gdk_screen_height() } as f64;
         let win_height = (screen_height * 0.7).round();
+        // Use the size it was last closed at, if known
+        let (win_width, win_height) = {
+            let preferences = preferences.borrow();
+            if preferences.window_width > 0 && preferences.window_height > 0 {
+                (preferences.window_width, preferences.window_height)
+            } else {
+                ((win_height * 1.33).round() as i32, win_height as i32)
+            }
+        };
 
         let win = ApplicationWindow::builder()
             .application(app)
-            .default_width((win_height * 1.33).round() as i32)
-            .default_height(win_height as i32)
+            .default_width(win_width)
+            .default_height(win_height)
             .window_position(WindowPosition::Center)
             .title("Arson JSON")
             .icon(&fire_emoji_icon_pb.clone())
             .build();
+        if preferences.borrow().window_maximized {
+            win.maximize();
+        }
 
         let v_box = Box::builder()
             .visible(true)
@@ -49,70 +91,134 @@ fn main() {
         let tool_bar = tool_bar::factory_tool_bar();
         v_box.add(&tool_bar.toolbar);
 
+        let search_bar = search_bar::factory_search_bar();
+        v_box.add(&search_bar.search_bar);
+
         paned.set_visible(true);
         v_box.add(&paned);
 
         let json_editor = json_editor::factory_json_editor();
-        paned.pack1(&json_editor.scrolled_window, true, true);
+        paned.pack1(&json_editor.stack, true, true);
 
         let tree_view = tree_view::factory_tree_view();
+        let detail_pane = detail_pane::factory_detail_pane();
+
+        let theme = theme::factory_theme();
+        let zoom = zoom::factory_zoom(json_editor.clone(), tree_view.clone());
+
+        let status_bar = status_bar::factory_status_bar();
+        v_box.add(&status_bar.status_bar);
+        status_bar
+            .status_bar
+            .pack_end(&zoom.indicator, false, false, 0);
 
         tool_bar::attach_listeners(
-            &tool_bar,
             &win.clone(),
             json_editor.clone(),
             tree_view.clone(),
+            preferences.clone(),
         );
         menu_bar::attach_listeners(
             &menu_bar,
             &win.clone(),
             json_editor.clone(),
+            tree_view.clone(),
+            status_bar.clone(),
+            search_bar.clone(),
             &fire_emoji_icon_pb.clone(),
+            preferences.clone(),
+            recent.clone(),
+            theme.clone(),
+            zoom.clone(),
         );
+        actions::apply_shortcuts(&win, &preferences.borrow());
         json_editor::attach_listeners(json_editor.clone(), tree_view.clone());
+        tree_view::attach_listeners(tree_view.clone(), &win.clone(), json_editor.clone());
+        status_bar::attach_listeners(
+            status_bar.clone(),
+            json_editor.clone(),
+            tree_view.clone(),
+            preferences.clone(),
+        );
+        search_bar::attach_listeners(search_bar.clone(), json_editor.clone(), tree_view.clone());
+        detail_pane::attach_listeners(
+            detail_pane.clone(),
+            &win,
+            json_editor.clone(),
+            tree_view.clone(),
+            preferences.clone(),
+        );
+        drag_drop::attach_listeners(&win, json_editor.clone(), tree_view.clone(), recent.clone());
 
+        zoom::attach_listeners(zoom.clone(), json_editor.clone(), preferences.clone());
         win.connect_scroll_event({
+            let zoom = zoom.clone();
             let json_editor = json_editor.clone();
-            move |_, event_key| {
-                json_editor::ctrl_scroll_resize_text_view_action(
-                    event_key.clone(),
+            let preferences = preferences.clone();
+            move |_, event| {
+                zoom::ctrl_scroll_zoom_action(
+                    event,
+                    zoom.clone(),
                     json_editor.clone(),
+                    preferences.clone(),
                 )
             }
         });
 
-        win.connect_key_press_event({
-            let json_editor = json_editor.clone();
-            move |_, event_key| {
-                json_editor::ctrl_plus_minus_text_view_action(
-                    event_key.clone(),
-                    json_editor.clone(),
-                )
-            }
-        });
+        session::attach_listeners(&win, json_editor.clone(), tree_view.clone(), recent.clone());
+        // After the prompt to save, which stops the delete event if the close is cancelled
+        preferences::remember_layout(&win, &paned, preferences.clone());
 
         win.connect_show({
             let json_editor = json_editor.clone();
+            let tree_view = tree_view.clone();
             move |_| {
-                json_editor::init_on_show(json_editor.clone());
+                session::restore_on_show(json_editor.clone(), tree_view.clone());
             }
         });
 
-        paned.pack2(&tree_view.overlay, true, true);
+        // The detail pane sits below the tree
+        let tree_paned = Paned::builder()
+            .visible(true)
+            .orientation(Orientation::Vertical)
+            .build();
+        tree_paned.pack1(&tree_view.overlay, true, true);
+        tree_paned.pack2(&detail_pane.container, false, true);
+        paned.pack2(&tree_paned, true, true);
 
         let init_done = Rc::new(Cell::new(false));
         paned.connect_size_allocate({
             let paned = paned.clone();
             let init_done = init_done.clone();
+            let pane_position = preferences.borrow().pane_position;
             move |_, alloc| {
                 if !init_done.get() {
-                    paned.set_position(((alloc.width() as f64) * 0.7).round() as i32);
+                    paned.set_position(((alloc.width() as f64) * pane_position).round() as i32);
                     init_done.set(true);
                 }
             }
         });
 
+        let _ = theme::apply_theme(
+            theme.clone(),
+            json_editor.clone(),
+            tree_view.clone(),
+            &preferences.borrow(),
+        );
+        theme::attach_listeners(
+            theme.clone(),
+            json_editor.clone(),
+            tree_view.clone(),
+            preferences.clone(),
+        );
+        zoom::apply(zoom.clone(), json_editor.clone(), &preferences.borrow());
+
         win.show_all();
+
+        // Hidden after show_all, which would show it again
+        if !preferences.borrow().show_tree {
+            tree_view::toggle_tree_view_visibility(json_editor.clone(), tree_view.clone());
+        }
     });
 
     app.run();
@@ -1,82 +1,367 @@
-use crate::file_mgt;
+use crate::actions::add_action;
+use crate::json_editor::JsonEditorState;
+use crate::preferences::PreferencesState;
+use crate::recent::RecentState;
+use crate::search_bar::SearchBarState;
+use crate::status_bar::StatusBarState;
+use crate::theme::ThemeState;
+use crate::tree_view::TreeViewState;
+use crate::zoom::ZoomState;
+use crate::{
+    actions, command_palette, extract_json, file_mgt, json_editor, jwt, nested_json, preferences,
+    recent, search_bar, sort_keys, statistics, status_bar, tree_view, zoom,
+};
 use gtk::prelude::*;
-use gtk::{AboutDialog, Menu, MenuBar, MenuItem};
+use gtk::{AboutDialog, CheckMenuItem, Menu, MenuBar, MenuItem, SeparatorMenuItem};
 
 pub struct MenuBarState {
     pub menu_bar: MenuBar,
-    file_open_item: MenuItem,
-    file_open_url_item: MenuItem,
-    file_quit_item: MenuItem,
-    help_github_item: MenuItem,
-    help_about_item: MenuItem,
+    file_recent_item: MenuItem,
+    file_recent_menu: Menu,
+}
+
+// Items trigger the application action of the same name, so share its shortcut
+fn menu_item(label: &str, action: &str) -> MenuItem {
+    MenuItem::builder()
+        .label(label)
+        .action_name(format!("app.{}", action))
+        .build()
 }
 
 pub fn factory_menu_bar() -> MenuBarState {
     let file_menu = Menu::new();
-    let file_open_item = MenuItem::builder().label("Open...").build();
-    let file_open_url_item = MenuItem::builder().label("Open URL...").build();
-    let file_quit_item = MenuItem::builder().label("Quit").build();
-    file_menu.append(&file_open_item);
-    file_menu.append(&file_open_url_item);
-    file_menu.append(&file_quit_item);
+    let file_recent_menu = Menu::new();
+    let file_recent_item = MenuItem::builder()
+        .label("Recent")
+        .submenu(&file_recent_menu)
+        .build();
+    file_menu.append(&menu_item("Open...", "open"));
+    file_menu.append(&menu_item("Open URL...", "open-url"));
+    file_menu.append(&file_recent_item);
+    file_menu.append(&SeparatorMenuItem::new());
+    file_menu.append(&menu_item("Save", "save"));
+    file_menu.append(&menu_item("Save As...", "save-as"));
+    file_menu.append(&SeparatorMenuItem::new());
+    file_menu.append(&menu_item("Quit", "quit"));
+
+    let edit_menu = Menu::new();
+    edit_menu.append(&menu_item("Undo", "undo"));
+    edit_menu.append(&menu_item("Redo", "redo"));
+    edit_menu.append(&SeparatorMenuItem::new());
+    edit_menu.append(&menu_item("Find...", "find"));
+    edit_menu.append(&menu_item("Replace...", "replace"));
+    edit_menu.append(&SeparatorMenuItem::new());
+    edit_menu.append(&menu_item("Command Palette...", "command-palette"));
+    edit_menu.append(&menu_item("Preferences...", "preferences"));
+
+    let view_menu = Menu::new();
+    let view_canonical_hash_item = CheckMenuItem::builder()
+        .label("Show SHA-256 of Canonical Form")
+        .action_name("app.canonical-hash")
+        .build();
+    view_menu.append(&view_canonical_hash_item);
+    view_menu.append(&SeparatorMenuItem::new());
+    view_menu.append(&menu_item("Expand All", "expand-all"));
+    view_menu.append(&menu_item("Collapse All", "collapse-all"));
+    view_menu.append(&menu_item("Expand to Depth...", "expand-to-depth"));
+    view_menu.append(&SeparatorMenuItem::new());
+    view_menu.append(&menu_item("Zoom In", "zoom-in"));
+    view_menu.append(&menu_item("Zoom Out", "zoom-out"));
+    view_menu.append(&menu_item("Reset Zoom", "reset-zoom"));
+
+    let transform_menu = Menu::new();
+    transform_menu.append(&menu_item("Sort Keys...", "sort-keys"));
+    transform_menu.append(&menu_item("Canonicalize (RFC 8785)", "canonicalize"));
+    transform_menu.append(&SeparatorMenuItem::new());
+    transform_menu.append(&menu_item("Extract JSON...", "extract-json"));
+    transform_menu.append(&menu_item("Deep Unescape", "deep-unescape"));
+    transform_menu.append(&menu_item("Re-encode Selected as String", "re-encode"));
+
+    let tools_menu = Menu::new();
+    tools_menu.append(&menu_item("JWT Inspector...", "jwt-inspector"));
+    tools_menu.append(&menu_item("Statistics...", "statistics"));
 
     let help_menu = Menu::new();
-    let help_github_item = MenuItem::builder().label("GitHub Issues").build();
-    let help_about_item = MenuItem::builder().label("About").build();
-    help_menu.append(&help_github_item);
-    help_menu.append(&help_about_item);
+    help_menu.append(&menu_item("GitHub Issues", "github-issues"));
+    help_menu.append(&menu_item("About", "about"));
 
     let menu_bar = MenuBar::new();
     let file_item = MenuItem::builder()
         .label("File")
         .submenu(&file_menu)
         .build();
+    let edit_item = MenuItem::builder()
+        .label("Edit")
+        .submenu(&edit_menu)
+        .build();
+    let view_item = MenuItem::builder()
+        .label("View")
+        .submenu(&view_menu)
+        .build();
+    let transform_item = MenuItem::builder()
+        .label("Transform")
+        .submenu(&transform_menu)
+        .build();
+    let tools_item = MenuItem::builder()
+        .label("Tools")
+        .submenu(&tools_menu)
+        .build();
     let help_item = MenuItem::builder()
         .label("Help")
         .submenu(&help_menu)
         .build();
     menu_bar.append(&file_item);
+    menu_bar.append(&edit_item);
+    menu_bar.append(&view_item);
+    menu_bar.append(&transform_item);
+    menu_bar.append(&tools_item);
     menu_bar.append(&help_item);
 
     MenuBarState {
         menu_bar,
-        file_open_item,
-        file_open_url_item,
-        file_quit_item,
-        help_github_item,
-        help_about_item,
+        file_recent_item,
+        file_recent_menu,
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn attach_listeners(
     menu_bar: &MenuBarState,
     win: &gtk::ApplicationWindow,
-    json_editor: crate::json_editor::JsonEditorState,
+    json_editor: JsonEditorState,
+    tree_view: TreeViewState,
+    status_bar: StatusBarState,
+    search_bar: SearchBarState,
     fire_emoji_icon_pb: &gtk::gdk_pixbuf::Pixbuf,
+    preferences: PreferencesState,
+    recent: RecentState,
+    theme: ThemeState,
+    zoom: ZoomState,
 ) {
-    menu_bar.file_quit_item.connect_activate({
+    let app = win.application().unwrap();
+
+    add_action(&app, "quit", {
         let win = win.clone();
-        move |_| {
+        move || {
             win.close();
         }
     });
 
-    menu_bar.file_open_item.connect_activate({
+    add_action(&app, "open", {
         let win = win.clone();
         let json_editor = json_editor.clone();
-        move |_| file_mgt::file_open_item_action(win.clone(), json_editor.clone())
+        let recent = recent.clone();
+        move || file_mgt::file_open_item_action(win.clone(), json_editor.clone(), recent.clone())
     });
 
-    menu_bar.file_open_url_item.connect_activate({
+    add_action(&app, "open-url", {
         let win = win.clone();
         let json_editor = json_editor.clone();
-        move |_| file_mgt::file_open_url_item_action(win.clone(), json_editor.clone())
+        let recent = recent.clone();
+        move || {
+            file_mgt::file_open_url_item_action(win.clone(), json_editor.clone(), recent.clone())
+        }
     });
 
-    menu_bar.help_about_item.connect_activate({
+    add_action(&app, "save", {
         let win = win.clone();
-        let fire_emoji_icon_pb = fire_emoji_icon_pb.clone();
+        let json_editor = json_editor.clone();
+        let recent = recent.clone();
+        move || {
+            file_mgt::file_save_item_action(win.clone(), json_editor.clone(), recent.clone());
+        }
+    });
+
+    add_action(&app, "save-as", {
+        let win = win.clone();
+        let json_editor = json_editor.clone();
+        let recent = recent.clone();
+        move || {
+            file_mgt::file_save_as_item_action(win.clone(), json_editor.clone(), recent.clone());
+        }
+    });
+
+    // Rebuilt whenever it is opened, so it includes files opened since and drops deleted ones
+    recent::rebuild_menu(
+        &menu_bar.file_recent_menu,
+        win.clone(),
+        json_editor.clone(),
+        recent.clone(),
+    );
+    menu_bar.file_recent_item.connect_select({
+        let file_recent_menu = menu_bar.file_recent_menu.clone();
+        let win = win.clone();
+        let json_editor = json_editor.clone();
         move |_| {
+            recent::rebuild_menu(
+                &file_recent_menu,
+                win.clone(),
+                json_editor.clone(),
+                recent.clone(),
+            )
+        }
+    });
+
+    add_action(&app, "undo", {
+        let json_editor = json_editor.clone();
+        move || json_editor::undo_action(json_editor.clone())
+    });
+
+    add_action(&app, "redo", {
+        let json_editor = json_editor.clone();
+        move || json_editor::redo_action(json_editor.clone())
+    });
+
+    add_action(&app, "find", {
+        let search_bar = search_bar.clone();
+        move || search_bar::show_search_action(search_bar.clone(), false)
+    });
+
+    add_action(&app, "replace", move || {
+        search_bar::show_search_action(search_bar.clone(), true)
+    });
+
+    add_action(&app, "command-palette", {
+        let win = win.clone();
+        move || command_palette::command_palette_action(win.clone())
+    });
+
+    add_action(&app, "preferences", {
+        let win = win.clone();
+        let preferences = preferences.clone();
+        let json_editor = json_editor.clone();
+        let tree_view = tree_view.clone();
+        let zoom = zoom.clone();
+        move || {
+            preferences::preferences_dialog_action(
+                win.clone(),
+                json_editor.clone(),
+                tree_view.clone(),
+                theme.clone(),
+                zoom.clone(),
+                preferences.clone(),
+            )
+        }
+    });
+
+    actions::add_toggle_action(
+        &app,
+        "canonical-hash",
+        preferences.borrow().show_canonical_hash,
+        {
+            let json_editor = json_editor.clone();
+            let tree_view = tree_view.clone();
+            let preferences = preferences.clone();
+            move |active| {
+                preferences.borrow_mut().show_canonical_hash = active;
+                let _ = preferences::save(&preferences.borrow());
+                status_bar::update_canonical_hash(
+                    status_bar.clone(),
+                    json_editor.clone(),
+                    tree_view.clone(),
+                    preferences.clone(),
+                );
+            }
+        },
+    );
+
+    add_action(&app, "expand-all", {
+        let tree_view = tree_view.clone();
+        move || tree_view::expand_all_action(tree_view.clone())
+    });
+
+    add_action(&app, "collapse-all", {
+        let tree_view = tree_view.clone();
+        move || tree_view::collapse_all_action(tree_view.clone())
+    });
+
+    add_action(&app, "expand-to-depth", {
+        let win = win.clone();
+        let tree_view = tree_view.clone();
+        move || tree_view::expand_to_depth_action(win.clone(), tree_view.clone())
+    });
+
+    add_action(&app, "zoom-in", {
+        let zoom = zoom.clone();
+        let json_editor = json_editor.clone();
+        let preferences = preferences.clone();
+        move || zoom::zoom_in_action(zoom.clone(), json_editor.clone(), preferences.clone())
+    });
+
+    add_action(&app, "zoom-out", {
+        let zoom = zoom.clone();
+        let json_editor = json_editor.clone();
+        let preferences = preferences.clone();
+        move || zoom::zoom_out_action(zoom.clone(), json_editor.clone(), preferences.clone())
+    });
+
+    add_action(&app, "reset-zoom", {
+        let zoom = zoom.clone();
+        let json_editor = json_editor.clone();
+        let preferences = preferences.clone();
+        move || zoom::reset_zoom_action(zoom.clone(), json_editor.clone(), preferences.clone())
+    });
+
+    add_action(&app, "sort-keys", {
+        let win = win.clone();
+        let json_editor = json_editor.clone();
+        let tree_view = tree_view.clone();
+        let preferences = preferences.clone();
+        move || {
+            sort_keys::sort_keys_action(
+                win.clone(),
+                json_editor.clone(),
+                tree_view.clone(),
+                preferences.clone(),
+            )
+        }
+    });
+
+    add_action(&app, "canonicalize", {
+        let win = win.clone();
+        let json_editor = json_editor.clone();
+        move || json_editor::canonicalize_json_action(win.clone(), json_editor.clone())
+    });
+
+    add_action(&app, "extract-json", {
+        let win = win.clone();
+        let json_editor = json_editor.clone();
+        move || extract_json::extract_json_action(win.clone(), json_editor.clone())
+    });
+
+    add_action(&app, "deep-unescape", {
+        let win = win.clone();
+        let json_editor = json_editor.clone();
+        move || {
+            nested_json::deep_unescape_action(win.clone(), json_editor.clone(), preferences.clone())
+        }
+    });
+
+    add_action(&app, "re-encode", {
+        let win = win.clone();
+        let json_editor = json_editor.clone();
+        let tree_view = tree_view.clone();
+        move || nested_json::re_encode_action(win.clone(), json_editor.clone(), tree_view.clone())
+    });
+
+    add_action(&app, "jwt-inspector", {
+        let win = win.clone();
+        let json_editor = json_editor.clone();
+        let tree_view = tree_view.clone();
+        move || jwt::jwt_inspector_action(win.clone(), json_editor.clone(), tree_view.clone())
+    });
+
+    add_action(&app, "statistics", {
+        let win = win.clone();
+        let json_editor = json_editor.clone();
+        let tree_view = tree_view.clone();
+        move || statistics::statistics_action(win.clone(), json_editor.clone(), tree_view.clone())
+    });
+
+    add_action(&app, "about", {
+        let win = win.clone();
+        let fire_emoji_icon_pb = fire_emoji_icon_pb.clone();
+        move || {
             let p = AboutDialog::new();
             p.set_website_label(Some("github.com/asgrim/arson"));
             p.set_website(Some("https://github.com/asgrim/arson"));
@@ -88,7 +373,7 @@ pub fn attach_listeners(
         }
     });
 
-    menu_bar.help_github_item.connect_activate(|_| {
+    add_action(&app, "github-issues", || {
         let _ = open::that("https://github.com/asgrim/arson/issues");
     });
 }
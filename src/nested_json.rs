@@ -0,0 +1,303 @@
+// JSON that has been encoded into a string, often several times over, as queues and
+// loggers do when they wrap a payload inside their own message
+use crate::cst::FormatOptions;
+use crate::json_editor::JsonEditorState;
+use crate::preferences::PreferencesState;
+use crate::tree_view::TreeViewState;
+use crate::{cst, json_editor, json_pointer, tree_view};
+use gtk::prelude::*;
+use gtk::{ApplicationWindow, ButtonsType, MessageDialog, MessageType, WindowPosition};
+use serde_json::Value;
+use std::ops::Range;
+
+// Levels of string encoding removed before giving up
+const MAX_ENCODING_LEVELS: usize = 8;
+
+// The object or array held by a string, either as it is or escaped one or more times
+pub fn parse_nested_json(value: &str) -> Option<Value> {
+    let is_container = |text: &str| {
+        let text = text.trim_start();
+        text.starts_with('{') || text.starts_with('[')
+    };
+
+    let mut text = value.to_string();
+    for _ in 0..MAX_ENCODING_LEVELS {
+        if is_container(&text) {
+            if let Ok(nested) = serde_json::from_str(&text) {
+                return Some(nested);
+            }
+        }
+        // The same as the Unescape button does
+        let unescaped = json_editor::unescape_json_text(&text).ok()?;
+        if unescaped == text {
+            return None;
+        }
+        text = unescaped;
+    }
+
+    None
+}
+
+// Replace every string holding JSON with what it holds, returning how many were replaced
+pub fn deep_unescape(value: &mut Value) -> usize {
+    match value {
+        Value::Object(map) => map.values_mut().map(deep_unescape).sum(),
+        Value::Array(arr) => arr.iter_mut().map(deep_unescape).sum(),
+        Value::String(s) => match parse_nested_json(s) {
+            Some(mut nested) => {
+                let replaced = 1 + deep_unescape(&mut nested);
+                *value = nested;
+                replaced
+            }
+            None => 0,
+        },
+        _ => 0,
+    }
+}
+
+// Only the strings holding JSON are replaced, so comments and the layout of everything else
+// are kept
+pub fn deep_unescape_action(
+    win: ApplicationWindow,
+    json_editor: JsonEditorState,
+    preferences: PreferencesState,
+) {
+    let buffer = json_editor::retrieve_buffer(json_editor.clone());
+    let (start, end) = buffer.bounds();
+    let current_text = buffer.text(&start, &end, true).unwrap();
+
+    let parsed = json_editor::parse_text(json_editor.clone(), current_text.as_str())
+        .and_then(|_| cst::parse(current_text.as_str()));
+    let document = match parsed {
+        Ok(document) => document,
+        Err(e) => {
+            show_error(
+                &win,
+                &format!("The current text was not valid JSON.\n\n{}", e),
+            );
+            return;
+        }
+    };
+
+    let replacements = nested_replacements(
+        current_text.as_str(),
+        &document,
+        &preferences.borrow().format,
+    );
+    if replacements.is_empty() {
+        show_error(&win, "There are no strings holding JSON objects or arrays.");
+        return;
+    }
+
+    // Character offsets of the spans, as the buffer counts in characters
+    let mut char_offset = 0;
+    let mut byte_offset = 0;
+    let mut char_offset_of = |byte: usize| {
+        char_offset += current_text[byte_offset..byte].chars().count();
+        byte_offset = byte;
+        char_offset as i32
+    };
+    let edits: Vec<(i32, i32, String)> = replacements
+        .into_iter()
+        .map(|(span, replacement)| {
+            (
+                char_offset_of(span.start),
+                char_offset_of(span.end),
+                replacement,
+            )
+        })
+        .collect();
+
+    // From the end, so the earlier offsets stay valid
+    buffer.begin_user_action();
+    for (start, end, replacement) in edits.into_iter().rev() {
+        let mut start = buffer.iter_at_offset(start);
+        let mut end = buffer.iter_at_offset(end);
+        buffer.delete(&mut start, &mut end);
+        buffer.insert(&mut start, &replacement);
+    }
+    buffer.end_user_action();
+}
+
+// Each string in the text holding JSON, and the deep-unescaped JSON to replace it with, laid
+// out like the Pretty button
+fn nested_replacements(
+    text: &str,
+    document: &cst::Document,
+    options: &FormatOptions,
+) -> Vec<(Range<usize>, String)> {
+    document
+        .string_values()
+        .into_iter()
+        .filter_map(|(span, value)| {
+            let mut nested = parse_nested_json(&value)?;
+            deep_unescape(&mut nested);
+            let replacement = cst::prettify_in_place(text, span.start, &nested, options);
+            Some((span, replacement))
+        })
+        .collect()
+}
+
+// Turn the object or array selected in the tree back into a string, in place
+pub fn re_encode_action(
+    win: ApplicationWindow,
+    json_editor: JsonEditorState,
+    tree_view: TreeViewState,
+) {
+    let Some(pointer) = tree_view::selected_pointer(tree_view) else {
+        show_error(&win, "Select the object or array to re-encode in the tree.");
+        return;
+    };
+
+    let buffer = json_editor::retrieve_buffer(json_editor.clone());
+    let (start, end) = buffer.bounds();
+    let current_text = buffer.text(&start, &end, true).unwrap();
+
+    let parsed = json_editor::parse_text(json_editor.clone(), current_text.as_str())
+        .and_then(|v| Ok((v, cst::parse(current_text.as_str())?)));
+    let (v, document) = match parsed {
+        Ok(parsed) => parsed,
+        Err(e) => {
+            show_error(
+                &win,
+                &format!("The current text was not valid JSON.\n\n{}", e),
+            );
+            return;
+        }
+    };
+
+    let selected = v
+        .pointer(&pointer)
+        .filter(|v| v.is_object() || v.is_array());
+    let span = document.value_span(&json_pointer::segments(&pointer));
+    let (Some(selected), Some(span)) = (selected, span) else {
+        show_error(&win, "Only objects and arrays can be re-encoded.");
+        return;
+    };
+
+    let encoded = serde_json::to_string(&selected.to_string()).unwrap();
+    let mut start = buffer.iter_at_offset(current_text[..span.start].chars().count() as i32);
+    let mut end = buffer.iter_at_offset(current_text[..span.end].chars().count() as i32);
+    buffer.begin_user_action();
+    buffer.delete(&mut start, &mut end);
+    buffer.insert(&mut start, &encoded);
+    buffer.end_user_action();
+}
+
+fn show_error(win: &ApplicationWindow, text: &str) {
+    let error_dialog = MessageDialog::builder()
+        .transient_for(win)
+        .window_position(WindowPosition::CenterOnParent)
+        .message_type(MessageType::Warning)
+        .buttons(ButtonsType::Ok)
+        .title("Could not transform JSON")
+        .text(text)
+        .build();
+    error_dialog.connect_response(move |error_dialog, _| {
+        error_dialog.close();
+    });
+    error_dialog.run();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    // A string holding the text, escaped the given number of times over
+    fn encoded(text: &str, times: usize) -> String {
+        let mut s = text.to_string();
+        for _ in 0..times {
+            s = serde_json::to_string(&s).unwrap();
+        }
+        s
+    }
+
+    #[test]
+    fn parses_json_escaped_several_times() {
+        let text = r#"{"a":[1,"two"]}"#;
+        for times in 0..MAX_ENCODING_LEVELS {
+            assert_eq!(
+                parse_nested_json(&encoded(text, times)),
+                Some(json!({"a": [1, "two"]})),
+                "{} times",
+                times
+            );
+        }
+        assert_eq!(parse_nested_json(&encoded(text, MAX_ENCODING_LEVELS)), None);
+
+        // Escaped, but without the quotes around it
+        assert_eq!(
+            parse_nested_json(r#"[{\"b\":null}]"#),
+            Some(json!([{"b": null}]))
+        );
+    }
+
+    #[test]
+    fn leaves_other_strings_alone() {
+        assert_eq!(parse_nested_json("[citation needed]"), None);
+        assert_eq!(parse_nested_json("{not json}"), None);
+        assert_eq!(parse_nested_json("hello"), None);
+        // Only objects and arrays count, not strings holding other values
+        assert_eq!(parse_nested_json("42"), None);
+        assert_eq!(parse_nested_json(&encoded("true", 2)), None);
+    }
+
+    #[test]
+    fn deep_unescape_replaces_strings_within_strings() {
+        let inner = encoded(r#"{"c":[1]}"#, 1);
+        let mut v = json!({
+            "a": format!(r#"{{"b":{}}}"#, inner),
+            "d": ["[citation needed]", encoded("[2]", 2)],
+            "e": "text",
+        });
+        assert_eq!(deep_unescape(&mut v), 3);
+        assert_eq!(
+            v,
+            json!({"a": {"b": {"c": [1]}}, "d": ["[citation needed]", [2]], "e": "text"})
+        );
+    }
+
+    // The text with every replacement made, from the end
+    fn replaced(text: &str) -> String {
+        let document = cst::parse(text).unwrap();
+        let replacements = nested_replacements(text, &document, &FormatOptions::default());
+        let mut result = text.to_string();
+        for (span, replacement) in replacements.into_iter().rev() {
+            result.replace_range(span, &replacement);
+        }
+        result
+    }
+
+    #[test]
+    fn replacements_keep_comments_and_layout() {
+        let text = concat!(
+            "// queue message\n",
+            "{\n",
+            "    \"id\": 7, /* kept */\n",
+            "    \"body\": \"{\\\"x\\\":\\\"[1,2]\\\"}\",\n",
+            "    \"note\": \"[citation needed]\",\n",
+            "}\n",
+        );
+        assert_eq!(
+            replaced(text),
+            concat!(
+                "// queue message\n",
+                "{\n",
+                "    \"id\": 7, /* kept */\n",
+                "    \"body\": {\n",
+                "      \"x\": [\n",
+                "        1,\n",
+                "        2\n",
+                "      ]\n",
+                "    },\n",
+                "    \"note\": \"[citation needed]\",\n",
+                "}\n",
+            )
+        );
+
+        // Keys are strings too, but are never replaced
+        assert_eq!(replaced(r#"{"[1]":"[2]"}"#), "{\"[1]\":[\n  2\n]}");
+        assert_eq!(replaced(r#"["a","b"]"#), r#"["a","b"]"#);
+    }
+}
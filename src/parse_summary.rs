@@ -0,0 +1,171 @@
+// Facts about the text worked out alongside each parse for the tree, so the status bar
+// doesn't need to parse it again
+use crate::json_parser::ParseMode;
+use crate::{cst, json_parser, json_pointer};
+use serde_json::Value;
+
+// Finding the pointer under the cursor needs a syntax tree, which is skipped above this size
+const MAX_SYNTAX_TREE_BYTES: usize = 5_000_000;
+
+pub struct ParseSummary {
+    pub bytes: usize,
+    pub chars: usize,
+    // The parse error, if the text isn't valid
+    pub error: Option<String>,
+    // Nesting of arrays and objects, so 0 for a lone scalar
+    pub max_depth: usize,
+    // Every value, including the root and the values inside arrays and objects
+    pub node_count: usize,
+    syntax_tree: Option<cst::Document>,
+    // Byte offset of the start of each line
+    line_starts: Vec<usize>,
+}
+
+// Parse for the tree, with the syntax tree and the value coming from the same parse when the
+// text is small enough. Whatever the syntax tree has no value for, including text nested too
+// deeply for it, is left to json_parser, which also gives the usual error messages.
+pub fn parse(text: &str, mode: ParseMode) -> (Result<Value, String>, ParseSummary) {
+    let from_syntax_tree = if text.len() <= MAX_SYNTAX_TREE_BYTES {
+        cst::parse(text)
+            .ok()
+            .and_then(|syntax_tree| Some((syntax_tree.to_value(mode)?, syntax_tree)))
+    } else {
+        None
+    };
+    let (parsed, syntax_tree) = match from_syntax_tree {
+        Some((v, syntax_tree)) => (Ok(v), Some(syntax_tree)),
+        None => (json_parser::parse(text, mode), None),
+    };
+    let summary = summarize(text, &parsed, syntax_tree);
+    (parsed, summary)
+}
+
+fn summarize(
+    text: &str,
+    parsed: &Result<Value, String>,
+    syntax_tree: Option<cst::Document>,
+) -> ParseSummary {
+    let (max_depth, node_count) = match parsed {
+        Ok(v) => measure(v),
+        Err(_) => (0, 0),
+    };
+
+    ParseSummary {
+        bytes: text.len(),
+        chars: text.chars().count(),
+        error: parsed.as_ref().err().cloned(),
+        max_depth,
+        node_count,
+        syntax_tree,
+        line_starts: std::iter::once(0)
+            .chain(text.match_indices('\n').map(|(i, _)| i + 1))
+            .collect(),
+    }
+}
+
+// Depth and number of values, without recursing, as documents can be nested deeply
+fn measure(root: &Value) -> (usize, usize) {
+    let mut max_depth = 0;
+    let mut node_count = 0;
+    let mut stack = vec![(root, 0)];
+
+    while let Some((v, depth)) = stack.pop() {
+        node_count += 1;
+        match v {
+            Value::Array(items) => {
+                max_depth = max_depth.max(depth + 1);
+                stack.extend(items.iter().map(|item| (item, depth + 1)));
+            }
+            Value::Object(members) => {
+                max_depth = max_depth.max(depth + 1);
+                stack.extend(members.values().map(|member| (member, depth + 1)));
+            }
+            _ => {}
+        }
+    }
+
+    (max_depth, node_count)
+}
+
+impl ParseSummary {
+    // JSON Pointer of the value at a line and a byte index within that line, if the text was
+    // valid and small enough to have a syntax tree
+    pub fn pointer_at(&self, line: usize, line_index: usize) -> Option<String> {
+        let syntax_tree = self.syntax_tree.as_ref()?;
+        let offset = self.line_starts.get(line)? + line_index;
+        Some(
+            syntax_tree
+                .segments_at(offset)
+                .iter()
+                .fold(String::new(), |pointer, segment| {
+                    json_pointer::append(&pointer, segment)
+                }),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn nested(depth: usize) -> String {
+        "[".repeat(depth) + &"]".repeat(depth)
+    }
+
+    #[test]
+    fn summary_and_pointers() {
+        let text = "{\n  \"a\": [1, {\"b\": null}],\n  \"c\": \"é\"\n}";
+        let (parsed, summary) = parse(text, ParseMode::Strict);
+        assert_eq!(parsed, Ok(json!({"a": [1, {"b": null}], "c": "é"})));
+        assert_eq!((summary.bytes, summary.chars), (text.len(), text.len() - 1));
+        assert_eq!((summary.max_depth, summary.node_count), (3, 6));
+        assert_eq!(summary.pointer_at(1, 14).as_deref(), Some("/a/1/b"));
+        assert_eq!(summary.pointer_at(2, 8).as_deref(), Some("/c"));
+        assert_eq!(summary.pointer_at(9, 0), None);
+    }
+
+    #[test]
+    fn invalid_text() {
+        let (parsed, summary) = parse("{\"a\": 1,}", ParseMode::Strict);
+        assert!(parsed.is_err());
+        assert_eq!(summary.error, parsed.err());
+        assert_eq!(summary.pointer_at(0, 1), None);
+
+        let (parsed, _) = parse("{a: 1,} // JSON5", ParseMode::Lenient);
+        assert_eq!(parsed, Ok(json!({"a": 1})));
+    }
+
+    #[test]
+    fn nesting_as_deep_as_serde_json_allows() {
+        for mode in [ParseMode::Strict, ParseMode::Lenient] {
+            let (parsed, summary) = parse(&nested(127), mode);
+            assert!(parsed.is_ok());
+            assert_eq!(summary.max_depth, 127);
+            assert_eq!(summary.pointer_at(0, 0).as_deref(), Some(""));
+
+            let (parsed, _) = parse(&nested(128), mode);
+            assert_eq!(parsed, json_parser::parse(&nested(128), mode));
+            assert!(parsed.is_err());
+        }
+    }
+
+    // Parsing happens on a worker thread, so with a worker's stack rather than the main one's
+    #[test]
+    fn deep_nesting_on_a_worker_stack() {
+        std::thread::Builder::new()
+            .stack_size(8 * 1024 * 1024)
+            .spawn(|| {
+                for text in [nested(20_000), "{\"a\":".repeat(20_000)] {
+                    for mode in [ParseMode::Strict, ParseMode::Lenient] {
+                        let (parsed, summary) = parse(&text, mode);
+                        assert!(parsed.is_err());
+                        assert!(summary.error.is_some());
+                    }
+                }
+            })
+            .unwrap()
+            .join()
+            .unwrap();
+    }
+}
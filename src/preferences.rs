@@ -0,0 +1,401 @@
+use crate::cst::{FormatOptions, IndentStyle};
+use crate::json_editor::JsonEditorState;
+use crate::json_parser::ParseMode;
+use crate::theme::{ThemeChoice, ThemeState};
+use crate::tree_view::TreeViewState;
+use crate::zoom::ZoomState;
+use crate::{actions, theme, tree_view, zoom};
+use gtk::glib;
+use gtk::glib::Propagation;
+use gtk::pango::FontDescription;
+use gtk::prelude::*;
+use gtk::{
+    Align, ApplicationWindow, Button, ButtonsType, CheckButton, ComboBoxText, FileChooserAction,
+    FileChooserButton, FontButton, Grid, IconSize, Label, MessageDialog, MessageType, Orientation,
+    Paned, ResponseType, SpinButton, WindowPosition,
+};
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+use std::rc::Rc;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Preferences {
+    pub format: FormatOptions,
+    // Keys to put first when sorting keys by priority
+    pub key_priority: Vec<String>,
+    pub show_canonical_hash: bool,
+    // Editor font, as a Pango family name and a size in points
+    pub font_family: String,
+    pub font_size: u32,
+    // Applied on top of the font size, to the tree as well as the editor
+    pub zoom_percent: u32,
+    pub show_tree: bool,
+    // Share of the window's width given to the editor
+    pub pane_position: f64,
+    // 0 sizes the window from the screen height
+    pub window_width: i32,
+    pub window_height: i32,
+    // Set when the size is chosen in the dialog, so closing the window doesn't overwrite it
+    pub window_size_fixed: bool,
+    pub window_maximized: bool,
+    pub default_parse_mode: ParseMode,
+    pub theme: ThemeChoice,
+    // Loaded on top of the GTK theme, to restyle any part of Arson
+    pub user_css_path: Option<PathBuf>,
+    // Shortcuts changed from the defaults, by action name. Empty means no shortcut.
+    pub shortcuts: BTreeMap<String, String>,
+}
+
+impl Default for Preferences {
+    fn default() -> Self {
+        Preferences {
+            format: FormatOptions::default(),
+            key_priority: Vec::new(),
+            show_canonical_hash: false,
+            font_family: "Monospace".to_string(),
+            font_size: 11,
+            zoom_percent: 100,
+            show_tree: true,
+            pane_position: 0.7,
+            window_width: 0,
+            window_height: 0,
+            window_size_fixed: false,
+            window_maximized: false,
+            default_parse_mode: ParseMode::Strict,
+            theme: ThemeChoice::System,
+            user_css_path: None,
+            shortcuts: BTreeMap::new(),
+        }
+    }
+}
+
+pub type PreferencesState = Rc<RefCell<Preferences>>;
+
+fn preferences_path() -> PathBuf {
+    glib::user_config_dir()
+        .join("arson")
+        .join("preferences.toml")
+}
+
+pub fn load() -> PreferencesState {
+    // A missing or unreadable file just means the defaults are used
+    let preferences = fs::read_to_string(preferences_path())
+        .ok()
+        .and_then(|contents| toml::from_str(&contents).ok())
+        .unwrap_or_default();
+
+    Rc::new(RefCell::new(preferences))
+}
+
+pub fn save(preferences: &Preferences) -> Result<(), String> {
+    let path = preferences_path();
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+    }
+    let contents = toml::to_string_pretty(preferences).map_err(|e| e.to_string())?;
+    fs::write(path, contents).map_err(|e| e.to_string())
+}
+
+fn grid_label(text: &str) -> Label {
+    Label::builder().label(text).halign(Align::Start).build()
+}
+
+pub fn preferences_dialog_action(
+    win: ApplicationWindow,
+    json_editor: JsonEditorState,
+    tree_view: TreeViewState,
+    theme: ThemeState,
+    zoom: ZoomState,
+    preferences: PreferencesState,
+) {
+    let dialog = gtk::Dialog::builder()
+        .transient_for(&win)
+        .window_position(WindowPosition::CenterOnParent)
+        .title("Preferences")
+        .modal(true)
+        .build();
+    dialog.add_buttons(&[("Cancel", ResponseType::Cancel), ("Save", ResponseType::Ok)]);
+
+    let grid = Grid::builder()
+        .row_spacing(6)
+        .column_spacing(12)
+        .margin(12)
+        .build();
+    dialog.content_area().add(&grid);
+
+    let current = preferences.borrow().clone();
+
+    let heading = Label::builder()
+        .label("<b>Pretty printing</b>")
+        .use_markup(true)
+        .halign(Align::Start)
+        .build();
+    grid.attach(&heading, 0, 0, 2, 1);
+
+    let indent_style = ComboBoxText::new();
+    indent_style.append(Some("spaces"), "Spaces");
+    indent_style.append(Some("tabs"), "Tabs");
+    indent_style.set_active_id(Some(match current.format.indent_style {
+        IndentStyle::Spaces => "spaces",
+        IndentStyle::Tabs => "tabs",
+    }));
+    grid.attach(&grid_label("Indent with"), 0, 1, 1, 1);
+    grid.attach(&indent_style, 1, 1, 1, 1);
+
+    let indent_width = SpinButton::with_range(1.0, 16.0, 1.0);
+    indent_width.set_value(current.format.indent_width as f64);
+    grid.attach(&grid_label("Indent width"), 0, 2, 1, 1);
+    grid.attach(&indent_width, 1, 2, 1, 1);
+
+    let max_line_width = SpinButton::with_range(0.0, 1000.0, 10.0);
+    max_line_width.set_value(current.format.max_line_width as f64);
+    max_line_width.set_tooltip_text(Some(
+        "Keep short arrays and objects on one line; 0 never does",
+    ));
+    grid.attach(&grid_label("Max line width for inlining"), 0, 3, 1, 1);
+    grid.attach(&max_line_width, 1, 3, 1, 1);
+
+    let space_after_colon = CheckButton::with_label("Space after colon");
+    space_after_colon.set_active(current.format.space_after_colon);
+    grid.attach(&space_after_colon, 0, 4, 2, 1);
+
+    let trailing_newline = CheckButton::with_label("Trailing newline");
+    trailing_newline.set_active(current.format.trailing_newline);
+    grid.attach(&trailing_newline, 0, 5, 2, 1);
+
+    let sort_keys = CheckButton::with_label("Sort object keys");
+    sort_keys.set_active(current.format.sort_keys);
+    grid.attach(&sort_keys, 0, 6, 2, 1);
+
+    let editor_heading = Label::builder()
+        .label("<b>Editor</b>")
+        .use_markup(true)
+        .halign(Align::Start)
+        .build();
+    grid.attach(&editor_heading, 0, 7, 2, 1);
+
+    let font = FontButton::builder()
+        .font(format!("{} {}", current.font_family, current.font_size))
+        .build();
+    font.set_filter_func(Some(Box::new(|family, _| family.is_monospace())));
+    grid.attach(&grid_label("Font"), 0, 8, 1, 1);
+    grid.attach(&font, 1, 8, 1, 1);
+
+    let parse_mode = ComboBoxText::new();
+    parse_mode.append(Some("strict"), "Strict JSON");
+    parse_mode.append(Some("lenient"), "Lenient (JSON5/JSONC)");
+    parse_mode.set_active_id(Some(match current.default_parse_mode {
+        ParseMode::Strict => "strict",
+        ParseMode::Lenient => "lenient",
+    }));
+    grid.attach(&grid_label("Default parse mode"), 0, 9, 1, 1);
+    grid.attach(&parse_mode, 1, 9, 1, 1);
+
+    let show_tree = CheckButton::with_label("Show the tree view");
+    show_tree.set_active(tree_view::is_visible(tree_view.clone()));
+    grid.attach(&show_tree, 0, 10, 2, 1);
+
+    let appearance_heading = Label::builder()
+        .label("<b>Appearance</b>")
+        .use_markup(true)
+        .halign(Align::Start)
+        .build();
+    grid.attach(&appearance_heading, 0, 11, 2, 1);
+
+    let theme_choice = ComboBoxText::new();
+    theme_choice.append(Some("system"), "Follow system");
+    theme_choice.append(Some("light"), "Light");
+    theme_choice.append(Some("dark"), "Dark");
+    theme_choice.set_active_id(Some(match current.theme {
+        ThemeChoice::System => "system",
+        ThemeChoice::Light => "light",
+        ThemeChoice::Dark => "dark",
+    }));
+    grid.attach(&grid_label("Theme"), 0, 12, 1, 1);
+    grid.attach(&theme_choice, 1, 12, 1, 1);
+
+    let user_css = FileChooserButton::new("Custom CSS", FileChooserAction::Open);
+    if let Some(path) = &current.user_css_path {
+        user_css.set_filename(path);
+    }
+    let clear_user_css = Button::from_icon_name(Some("edit-clear-symbolic"), IconSize::Button);
+    clear_user_css.set_tooltip_text(Some("Don't use a custom CSS file"));
+    clear_user_css.connect_clicked({
+        let user_css = user_css.clone();
+        move |_| user_css.unselect_all()
+    });
+    let user_css_box = gtk::Box::new(Orientation::Horizontal, 6);
+    user_css_box.pack_start(&user_css, true, true, 0);
+    user_css_box.add(&clear_user_css);
+    grid.attach(&grid_label("Custom CSS"), 0, 13, 1, 1);
+    grid.attach(&user_css_box, 1, 13, 1, 1);
+
+    let window_heading = Label::builder()
+        .label("<b>Window</b> (remembered when Arson is closed)")
+        .use_markup(true)
+        .halign(Align::Start)
+        .build();
+    grid.attach(&window_heading, 0, 14, 2, 1);
+
+    let pane_position = SpinButton::with_range(10.0, 90.0, 5.0);
+    pane_position.set_value((current.pane_position * 100.0).round());
+    grid.attach(&grid_label("Editor width (% of window)"), 0, 15, 1, 1);
+    grid.attach(&pane_position, 1, 15, 1, 1);
+
+    let window_width = SpinButton::with_range(0.0, 10000.0, 10.0);
+    window_width.set_value(current.window_width as f64);
+    window_width.set_tooltip_text(Some(
+        "0 sizes the window to suit the screen, and remembers the size it is closed at",
+    ));
+    grid.attach(&grid_label("Window width"), 0, 16, 1, 1);
+    grid.attach(&window_width, 1, 16, 1, 1);
+
+    let window_height = SpinButton::with_range(0.0, 10000.0, 10.0);
+    window_height.set_value(current.window_height as f64);
+    window_height.set_tooltip_text(Some(
+        "0 sizes the window to suit the screen, and remembers the size it is closed at",
+    ));
+    grid.attach(&grid_label("Window height"), 0, 17, 1, 1);
+    grid.attach(&window_height, 1, 17, 1, 1);
+
+    let window_maximized = CheckButton::with_label("Start maximized");
+    window_maximized.set_active(current.window_maximized);
+    grid.attach(&window_maximized, 0, 18, 2, 1);
+
+    let shortcuts_heading = Label::builder()
+        .label("<b>Keyboard shortcuts</b> (click a shortcut to change it, Backspace to remove it)")
+        .use_markup(true)
+        .halign(Align::Start)
+        .build();
+    grid.attach(&shortcuts_heading, 0, 19, 2, 1);
+
+    let (shortcut_editor, shortcuts) = actions::shortcut_editor(&current);
+    grid.attach(&shortcut_editor, 0, 20, 2, 1);
+
+    let reset_shortcuts = Button::builder()
+        .label("Reset Shortcuts")
+        .halign(Align::End)
+        .build();
+    reset_shortcuts.connect_clicked({
+        let shortcuts = shortcuts.clone();
+        move |_| actions::reset_shortcuts(&shortcuts)
+    });
+    grid.attach(&reset_shortcuts, 0, 21, 2, 1);
+
+    dialog.connect_response(move |dialog, response| {
+        if response == ResponseType::Ok {
+            let mut updated = preferences.borrow().clone();
+            updated.format = FormatOptions {
+                indent_style: match indent_style.active_id().as_deref() {
+                    Some("tabs") => IndentStyle::Tabs,
+                    _ => IndentStyle::Spaces,
+                },
+                indent_width: indent_width.value_as_int() as u32,
+                max_line_width: max_line_width.value_as_int() as u32,
+                space_after_colon: space_after_colon.is_active(),
+                trailing_newline: trailing_newline.is_active(),
+                sort_keys: sort_keys.is_active(),
+            };
+
+            let font = FontDescription::from_string(&font.font().unwrap_or_default());
+            if let Some(family) = font.family() {
+                updated.font_family = family.to_string();
+            }
+            if font.size() > 0 {
+                updated.font_size = (font.size() / gtk::pango::SCALE) as u32;
+            }
+            updated.default_parse_mode = match parse_mode.active_id().as_deref() {
+                Some("lenient") => ParseMode::Lenient,
+                _ => ParseMode::Strict,
+            };
+            updated.show_tree = show_tree.is_active();
+            updated.theme = match theme_choice.active_id().as_deref() {
+                Some("light") => ThemeChoice::Light,
+                Some("dark") => ThemeChoice::Dark,
+                _ => ThemeChoice::System,
+            };
+            updated.user_css_path = user_css.filename();
+            updated.pane_position = pane_position.value() / 100.0;
+            let size = (window_width.value_as_int(), window_height.value_as_int());
+            if size != (current.window_width, current.window_height) {
+                (updated.window_width, updated.window_height) = size;
+                // Back to remembering the size when it's closed, if both are 0
+                updated.window_size_fixed = size.0 > 0 || size.1 > 0;
+            }
+            updated.window_maximized = window_maximized.is_active();
+            updated.shortcuts = actions::shortcuts_from_editor(&shortcuts);
+
+            if let Err(e) = save(&updated) {
+                let error_dialog = MessageDialog::builder()
+                    .transient_for(dialog)
+                    .window_position(WindowPosition::CenterOnParent)
+                    .message_type(MessageType::Warning)
+                    .buttons(ButtonsType::Ok)
+                    .title("Could not save preferences")
+                    .text(format!(
+                        "The preferences will be used, but could not be saved.\n\n{}",
+                        e
+                    ))
+                    .build();
+                error_dialog.connect_response(move |error_dialog, _| {
+                    error_dialog.close();
+                });
+                error_dialog.run();
+            }
+
+            if let Err(e) = theme::apply_theme(
+                theme.clone(),
+                json_editor.clone(),
+                tree_view.clone(),
+                &updated,
+            ) {
+                let error_dialog = MessageDialog::builder()
+                    .transient_for(dialog)
+                    .window_position(WindowPosition::CenterOnParent)
+                    .message_type(MessageType::Warning)
+                    .buttons(ButtonsType::Ok)
+                    .title("Could not load custom CSS")
+                    .text(format!("The custom CSS file could not be loaded.\n\n{}", e))
+                    .build();
+                error_dialog.connect_response(move |error_dialog, _| {
+                    error_dialog.close();
+                });
+                error_dialog.run();
+            }
+            zoom::apply(zoom.clone(), json_editor.clone(), &updated);
+            if updated.show_tree != tree_view::is_visible(tree_view.clone()) {
+                tree_view::toggle_tree_view_visibility(json_editor.clone(), tree_view.clone());
+            }
+            actions::apply_shortcuts(&win, &updated);
+            preferences.replace(updated);
+        }
+        dialog.close();
+    });
+
+    dialog.show_all();
+}
+
+// Remember the window size, unless it was chosen in the dialog, and how it is split when it
+// is closed
+pub fn remember_layout(win: &ApplicationWindow, paned: &Paned, preferences: PreferencesState) {
+    win.connect_delete_event({
+        let paned = paned.clone();
+        move |win, _| {
+            let mut preferences = preferences.borrow_mut();
+            preferences.window_maximized = win.is_maximized();
+            if !win.is_maximized() && !preferences.window_size_fixed {
+                (preferences.window_width, preferences.window_height) = win.size();
+            }
+            if paned.allocated_width() > 0 {
+                preferences.pane_position =
+                    paned.position() as f64 / paned.allocated_width() as f64;
+            }
+            let _ = save(&preferences);
+            Propagation::Proceed
+        }
+    });
+}
@@ -0,0 +1,197 @@
+// Files and URLs opened recently, for File > Recent. Files are also shared with other
+// applications through the desktop's recently used list.
+use crate::file_mgt;
+use crate::json_editor::JsonEditorState;
+use gtk::glib;
+use gtk::prelude::*;
+use gtk::{ApplicationWindow, Menu, MenuItem, RecentManager, SeparatorMenuItem};
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+use std::rc::Rc;
+
+const MAX_RECENT: usize = 10;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum RecentEntry {
+    File {
+        path: PathBuf,
+    },
+    Url {
+        url: String,
+        // Names of the request headers sent. Their values often hold tokens or cookies, so
+        // aren't saved, and are asked for again when the URL is reopened.
+        #[serde(default)]
+        header_names: Vec<String>,
+    },
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RecentEntries {
+    // Most recent first
+    pub entries: Vec<RecentEntry>,
+}
+
+pub type RecentState = Rc<RefCell<RecentEntries>>;
+
+fn recent_path() -> PathBuf {
+    glib::user_data_dir().join("arson").join("recent.toml")
+}
+
+pub fn load() -> RecentState {
+    // A missing or unreadable file just means nothing was opened before
+    let contents = fs::read_to_string(recent_path()).ok();
+    let recent: RecentEntries = contents
+        .as_deref()
+        .and_then(|contents| toml::from_str(contents).ok())
+        .unwrap_or_default();
+
+    // Older versions saved header values, which are dropped by saving again
+    if contents.is_some_and(|contents| toml::to_string_pretty(&recent).ok() != Some(contents)) {
+        let _ = save(&recent);
+    }
+
+    Rc::new(RefCell::new(recent))
+}
+
+// The "Name" of each "Name: value" line
+pub fn header_names(headers: &str) -> Vec<String> {
+    headers
+        .lines()
+        .filter_map(|line| line.split_once(':'))
+        .map(|(name, _)| name.trim().to_string())
+        .filter(|name| !name.is_empty())
+        .collect()
+}
+
+pub fn save(recent: &RecentEntries) -> Result<(), String> {
+    let path = recent_path();
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+    }
+    let contents = toml::to_string_pretty(recent).map_err(|e| e.to_string())?;
+
+    // Only readable by the user, as URLs can contain tokens too
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+        options.mode(0o600);
+        if path.exists() {
+            fs::set_permissions(&path, fs::Permissions::from_mode(0o600))
+                .map_err(|e| e.to_string())?;
+        }
+    }
+    let mut file = options.open(&path).map_err(|e| e.to_string())?;
+    file.write_all(contents.as_bytes())
+        .map_err(|e| e.to_string())
+}
+
+pub fn add(recent: RecentState, entry: RecentEntry) {
+    if let RecentEntry::File { path } = &entry {
+        if let (Some(manager), Ok(uri)) =
+            (RecentManager::default(), glib::filename_to_uri(path, None))
+        {
+            manager.add_item(&uri);
+        }
+    }
+
+    let mut recent = recent.borrow_mut();
+    recent.entries.retain(|existing| *existing != entry);
+    recent.entries.insert(0, entry);
+    recent.entries.truncate(MAX_RECENT);
+    let _ = save(&recent);
+}
+
+fn remove_missing_files(recent: RecentState) {
+    let mut recent = recent.borrow_mut();
+    let before = recent.entries.len();
+    recent.entries.retain(|entry| match entry {
+        RecentEntry::File { path } => {
+            let exists = path.exists();
+            if !exists {
+                if let (Some(manager), Ok(uri)) =
+                    (RecentManager::default(), glib::filename_to_uri(path, None))
+                {
+                    let _ = manager.remove_item(&uri);
+                }
+            }
+            exists
+        }
+        RecentEntry::Url { .. } => true,
+    });
+    if recent.entries.len() != before {
+        let _ = save(&recent);
+    }
+}
+
+// Fill the Recent submenu, called each time it is about to be shown
+pub fn rebuild_menu(
+    menu: &Menu,
+    win: ApplicationWindow,
+    json_editor: JsonEditorState,
+    recent: RecentState,
+) {
+    for child in menu.children() {
+        menu.remove(&child);
+    }
+    remove_missing_files(recent.clone());
+
+    let entries = recent.borrow().entries.clone();
+    for entry in entries {
+        let label = match &entry {
+            RecentEntry::File { path } => path.display().to_string(),
+            RecentEntry::Url { url, .. } => url.clone(),
+        };
+        let item = MenuItem::builder().label(label).build();
+        item.connect_activate({
+            let win = win.clone();
+            let json_editor = json_editor.clone();
+            let recent = recent.clone();
+            move |_| match &entry {
+                RecentEntry::File { path } => {
+                    file_mgt::open_file(win.clone(), json_editor.clone(), recent.clone(), path)
+                }
+                RecentEntry::Url { url, header_names } if header_names.is_empty() => {
+                    file_mgt::open_url(win.clone(), json_editor.clone(), recent.clone(), url, "")
+                }
+                // The header values weren't saved, so ask for them again
+                RecentEntry::Url { url, header_names } => file_mgt::open_url_dialog(
+                    win.clone(),
+                    json_editor.clone(),
+                    recent.clone(),
+                    url,
+                    &header_names
+                        .iter()
+                        .map(|name| format!("{}: ", name))
+                        .collect::<Vec<String>>()
+                        .join("\n"),
+                ),
+            }
+        });
+        menu.append(&item);
+    }
+
+    if menu.children().is_empty() {
+        let empty_item = MenuItem::builder()
+            .label("No Recent Files")
+            .sensitive(false)
+            .build();
+        menu.append(&empty_item);
+    } else {
+        let clear_item = MenuItem::builder().label("Clear Recent").build();
+        clear_item.connect_activate(move |_| {
+            recent.borrow_mut().entries.clear();
+            let _ = save(&recent.borrow());
+        });
+        menu.append(&SeparatorMenuItem::new());
+        menu.append(&clear_item);
+    }
+
+    menu.show_all();
+}
@@ -0,0 +1,536 @@
+use crate::json_editor::JsonEditorState;
+use crate::tree_view::{TreeFilter, TreeViewState};
+use crate::{json_editor, tree_view};
+use gtk::prelude::*;
+use gtk::{
+    Button, CheckButton, ComboBoxText, Entry, Label, Orientation, SearchBar, SearchEntry,
+    TextBuffer, TextTag,
+};
+use regex::{Regex, RegexBuilder};
+use std::ops::Range;
+
+const MATCH_TAG: &str = "search-match";
+
+#[derive(Clone, Copy, PartialEq)]
+enum SearchScope {
+    Text,
+    Keys,
+    Values,
+}
+
+#[derive(Clone)]
+pub struct SearchBarState {
+    pub search_bar: SearchBar,
+    search_entry: SearchEntry,
+    scope: ComboBoxText,
+    regex_check: CheckButton,
+    match_case_check: CheckButton,
+    whole_word_check: CheckButton,
+    previous_button: Button,
+    next_button: Button,
+    match_label: Label,
+    replace_box: gtk::Box,
+    replace_entry: Entry,
+    replace_button: Button,
+    replace_all_button: Button,
+}
+
+pub fn factory_search_bar() -> SearchBarState {
+    let search_entry = SearchEntry::builder()
+        .placeholder_text("Find")
+        .width_chars(30)
+        .build();
+
+    let scope = ComboBoxText::new();
+    scope.append(Some("text"), "Text");
+    scope.append(Some("keys"), "Keys only");
+    scope.append(Some("values"), "Values only");
+    scope.set_active_id(Some("text"));
+    scope.set_tooltip_text(Some(
+        "Searching only keys or values filters the tree to the matching nodes",
+    ));
+
+    let regex_check = CheckButton::with_label("Regex");
+    let match_case_check = CheckButton::with_label("Match case");
+    let whole_word_check = CheckButton::with_label("Whole word");
+
+    let previous_button = Button::builder()
+        .image(&gtk::Image::from_icon_name(
+            Some("go-up-symbolic"),
+            gtk::IconSize::Button,
+        ))
+        .tooltip_text("Previous match (Ctrl+Shift+G)")
+        .build();
+    let next_button = Button::builder()
+        .image(&gtk::Image::from_icon_name(
+            Some("go-down-symbolic"),
+            gtk::IconSize::Button,
+        ))
+        .tooltip_text("Next match (Enter)")
+        .build();
+
+    let match_label = Label::builder().width_chars(14).build();
+
+    let find_box = gtk::Box::new(Orientation::Horizontal, 6);
+    find_box.pack_start(&search_entry, false, false, 0);
+    find_box.pack_start(&previous_button, false, false, 0);
+    find_box.pack_start(&next_button, false, false, 0);
+    find_box.pack_start(&match_label, false, false, 0);
+    find_box.pack_start(&scope, false, false, 0);
+    find_box.pack_start(&regex_check, false, false, 0);
+    find_box.pack_start(&match_case_check, false, false, 0);
+    find_box.pack_start(&whole_word_check, false, false, 0);
+
+    let replace_entry = Entry::builder()
+        .placeholder_text("Replace")
+        .width_chars(30)
+        .build();
+    let replace_button = Button::with_label("Replace");
+    let replace_all_button = Button::with_label("Replace All");
+
+    // Only shown when opened with Replace...
+    let replace_box = gtk::Box::builder()
+        .orientation(Orientation::Horizontal)
+        .spacing(6)
+        .no_show_all(true)
+        .build();
+    replace_box.pack_start(&replace_entry, false, false, 0);
+    replace_box.pack_start(&replace_button, false, false, 0);
+    replace_box.pack_start(&replace_all_button, false, false, 0);
+
+    let v_box = gtk::Box::new(Orientation::Vertical, 6);
+    v_box.add(&find_box);
+    v_box.add(&replace_box);
+    v_box.show_all();
+
+    let search_bar = SearchBar::builder()
+        .visible(true)
+        .show_close_button(true)
+        .child(&v_box)
+        .build();
+    search_bar.connect_entry(&search_entry);
+
+    SearchBarState {
+        search_bar,
+        search_entry,
+        scope,
+        regex_check,
+        match_case_check,
+        whole_word_check,
+        previous_button,
+        next_button,
+        match_label,
+        replace_box,
+        replace_entry,
+        replace_button,
+        replace_all_button,
+    }
+}
+
+pub fn attach_listeners(
+    search_bar: SearchBarState,
+    json_editor: JsonEditorState,
+    tree_view: TreeViewState,
+) {
+    let buffer = json_editor::retrieve_buffer(json_editor.clone());
+    let match_tag = TextTag::builder()
+        .name(MATCH_TAG)
+        .background("#fce94f")
+        .foreground("#2e3436")
+        .build();
+    buffer.tag_table().unwrap().add(&match_tag);
+
+    let refresh = {
+        let search_bar = search_bar.clone();
+        let json_editor = json_editor.clone();
+        let tree_view = tree_view.clone();
+        move || update_matches(search_bar.clone(), json_editor.clone(), tree_view.clone())
+    };
+
+    search_bar.search_entry.connect_search_changed({
+        let refresh = refresh.clone();
+        move |_| refresh()
+    });
+    search_bar.scope.connect_changed({
+        let refresh = refresh.clone();
+        move |_| refresh()
+    });
+    for check in [
+        &search_bar.regex_check,
+        &search_bar.match_case_check,
+        &search_bar.whole_word_check,
+    ] {
+        check.connect_toggled({
+            let refresh = refresh.clone();
+            move |_| refresh()
+        });
+    }
+
+    // Keep the highlights in step with edits while the bar is open
+    buffer.connect_changed({
+        let search_bar = search_bar.clone();
+        let refresh = refresh.clone();
+        move |_| {
+            if search_bar.search_bar.is_search_mode() {
+                refresh()
+            }
+        }
+    });
+
+    search_bar.search_bar.connect_search_mode_enabled_notify({
+        let refresh = refresh.clone();
+        move |search_bar_widget| {
+            if !search_bar_widget.is_search_mode() {
+                refresh()
+            }
+        }
+    });
+
+    let go_to_match = {
+        let search_bar = search_bar.clone();
+        let json_editor = json_editor.clone();
+        let tree_view = tree_view.clone();
+        move |forward: bool| {
+            select_match(
+                search_bar.clone(),
+                json_editor.clone(),
+                tree_view.clone(),
+                forward,
+            )
+        }
+    };
+
+    search_bar.search_entry.connect_activate({
+        let go_to_match = go_to_match.clone();
+        move |_| go_to_match(true)
+    });
+    search_bar.search_entry.connect_next_match({
+        let go_to_match = go_to_match.clone();
+        move |_| go_to_match(true)
+    });
+    search_bar.search_entry.connect_previous_match({
+        let go_to_match = go_to_match.clone();
+        move |_| go_to_match(false)
+    });
+    search_bar.next_button.connect_clicked({
+        let go_to_match = go_to_match.clone();
+        move |_| go_to_match(true)
+    });
+    search_bar
+        .previous_button
+        .connect_clicked(move |_| go_to_match(false));
+
+    search_bar.search_entry.connect_stop_search({
+        let search_bar = search_bar.clone();
+        move |_| search_bar.search_bar.set_search_mode(false)
+    });
+
+    search_bar.replace_entry.connect_activate({
+        let search_bar = search_bar.clone();
+        let json_editor = json_editor.clone();
+        let tree_view = tree_view.clone();
+        move |_| replace_action(search_bar.clone(), json_editor.clone(), tree_view.clone())
+    });
+    search_bar.replace_button.connect_clicked({
+        let search_bar = search_bar.clone();
+        let json_editor = json_editor.clone();
+        let tree_view = tree_view.clone();
+        move |_| replace_action(search_bar.clone(), json_editor.clone(), tree_view.clone())
+    });
+    search_bar.replace_all_button.connect_clicked({
+        let search_bar = search_bar.clone();
+        move |_| replace_all_action(search_bar.clone(), json_editor.clone())
+    });
+}
+
+pub fn show_search_action(search_bar: SearchBarState, with_replace: bool) {
+    if with_replace {
+        search_bar.replace_box.show_all();
+    } else {
+        search_bar.replace_box.hide();
+    }
+    search_bar.search_bar.set_search_mode(true);
+    search_bar.search_entry.grab_focus();
+}
+
+fn scope(search_bar: &SearchBarState) -> SearchScope {
+    match search_bar.scope.active_id().as_deref() {
+        Some("keys") => SearchScope::Keys,
+        Some("values") => SearchScope::Values,
+        _ => SearchScope::Text,
+    }
+}
+
+// None when there is nothing to search for
+fn build_regex(search_bar: &SearchBarState) -> Option<Result<Regex, String>> {
+    let query = search_bar.search_entry.text();
+    if query.is_empty() {
+        return None;
+    }
+
+    let pattern = if search_bar.regex_check.is_active() {
+        query.to_string()
+    } else {
+        regex::escape(query.as_str())
+    };
+    let pattern = if search_bar.whole_word_check.is_active() {
+        format!(r"\b(?:{})\b", pattern)
+    } else {
+        pattern
+    };
+
+    Some(
+        RegexBuilder::new(&pattern)
+            .case_insensitive(!search_bar.match_case_check.is_active())
+            .multi_line(true)
+            .build()
+            .map_err(|e| e.to_string()),
+    )
+}
+
+fn buffer_text(buffer: &TextBuffer) -> String {
+    let (start, end) = buffer.bounds();
+    buffer.text(&start, &end, true).unwrap().to_string()
+}
+
+// Byte ranges of every non-empty match
+fn find_matches(regex: &Regex, text: &str) -> Vec<Range<usize>> {
+    regex
+        .find_iter(text)
+        .filter(|m| !m.is_empty())
+        .map(|m| m.range())
+        .collect()
+}
+
+// TextIter offsets are in characters
+fn char_offset(text: &str, byte_offset: usize) -> i32 {
+    text[..byte_offset].chars().count() as i32
+}
+
+fn byte_offset(text: &str, char_offset: i32) -> usize {
+    text.char_indices()
+        .nth(char_offset as usize)
+        .map_or(text.len(), |(i, _)| i)
+}
+
+fn update_matches(
+    search_bar: SearchBarState,
+    json_editor: JsonEditorState,
+    tree_view: TreeViewState,
+) {
+    let buffer = json_editor::retrieve_buffer(json_editor);
+    let (start, end) = buffer.bounds();
+    buffer.remove_tag_by_name(MATCH_TAG, &start, &end);
+
+    // Replacing only works on the text itself
+    search_bar
+        .replace_box
+        .set_sensitive(scope(&search_bar) == SearchScope::Text);
+
+    let regex = match build_regex(&search_bar) {
+        Some(Ok(regex)) if search_bar.search_bar.is_search_mode() => regex,
+        Some(Err(e)) if search_bar.search_bar.is_search_mode() => {
+            tree_view::set_filter(tree_view, None);
+            // The error message spans several lines; its last line says what is wrong
+            let reason = e.lines().last().unwrap_or_default().trim().to_string();
+            search_bar.match_label.set_label("Invalid regex");
+            search_bar.match_label.set_tooltip_text(Some(&reason));
+            return;
+        }
+        _ => {
+            tree_view::set_filter(tree_view, None);
+            search_bar.match_label.set_label("");
+            search_bar.match_label.set_tooltip_text(None);
+            return;
+        }
+    };
+    search_bar.match_label.set_tooltip_text(None);
+
+    let match_count = match scope(&search_bar) {
+        SearchScope::Text => {
+            tree_view::set_filter(tree_view, None);
+            let text = buffer_text(&buffer);
+            let matches = find_matches(&regex, &text);
+            for range in &matches {
+                buffer.apply_tag_by_name(
+                    MATCH_TAG,
+                    &buffer.iter_at_offset(char_offset(&text, range.start)),
+                    &buffer.iter_at_offset(char_offset(&text, range.end)),
+                );
+            }
+            matches.len()
+        }
+        scope => tree_view::set_filter(
+            tree_view,
+            Some(TreeFilter {
+                regex,
+                match_keys: scope == SearchScope::Keys,
+            }),
+        ),
+    };
+
+    search_bar
+        .match_label
+        .set_label(&match_count_label(match_count));
+}
+
+fn match_count_label(match_count: usize) -> String {
+    match match_count {
+        0 => "No matches".to_string(),
+        1 => "1 match".to_string(),
+        n => format!("{} matches", n),
+    }
+}
+
+fn select_match(
+    search_bar: SearchBarState,
+    json_editor: JsonEditorState,
+    tree_view: TreeViewState,
+    forward: bool,
+) {
+    let Some(Ok(regex)) = build_regex(&search_bar) else {
+        return;
+    };
+
+    let position = if scope(&search_bar) == SearchScope::Text {
+        let buffer = json_editor::retrieve_buffer(json_editor.clone());
+        let text = buffer_text(&buffer);
+        let matches = find_matches(&regex, &text);
+        if matches.is_empty() {
+            None
+        } else {
+            let (selection_start, selection_end) = selection_bytes(&buffer, &text);
+            let index = if forward {
+                matches
+                    .iter()
+                    .position(|m| {
+                        m.start >= selection_end && *m != (selection_start..selection_end)
+                    })
+                    .unwrap_or(0)
+            } else {
+                matches
+                    .iter()
+                    .rposition(|m| m.end <= selection_start)
+                    .unwrap_or(matches.len() - 1)
+            };
+            let range = &matches[index];
+            json_editor::select_range(
+                json_editor,
+                char_offset(&text, range.start),
+                char_offset(&text, range.end),
+            );
+            Some((index + 1, matches.len()))
+        }
+    } else {
+        tree_view::select_match(tree_view, forward)
+    };
+
+    match position {
+        Some((index, total)) => search_bar
+            .match_label
+            .set_label(&format!("{} of {}", index, total)),
+        None => search_bar.match_label.set_label("No matches"),
+    }
+}
+
+// The selection as byte offsets, or the cursor position twice if nothing is selected
+fn selection_bytes(buffer: &TextBuffer, text: &str) -> (usize, usize) {
+    let (start, end) = buffer.selection_bounds().unwrap_or_else(|| {
+        let cursor = buffer.iter_at_mark(&buffer.get_insert().unwrap());
+        (cursor, cursor)
+    });
+    (
+        byte_offset(text, start.offset()),
+        byte_offset(text, end.offset()),
+    )
+}
+
+// Replace the selected match, then move on to the next one
+fn replace_action(
+    search_bar: SearchBarState,
+    json_editor: JsonEditorState,
+    tree_view: TreeViewState,
+) {
+    if scope(&search_bar) != SearchScope::Text {
+        return;
+    }
+    let Some(Ok(regex)) = build_regex(&search_bar) else {
+        return;
+    };
+
+    let buffer = json_editor::retrieve_buffer(json_editor.clone());
+    let text = buffer_text(&buffer);
+    let (selection_start, selection_end) = selection_bytes(&buffer, &text);
+
+    let selected_match = find_matches(&regex, &text)
+        .into_iter()
+        .find(|m| *m == (selection_start..selection_end));
+
+    if let Some(range) = selected_match {
+        let replacement = expand_replacement(&search_bar, &regex, &text, range.clone());
+        let mut start = buffer.iter_at_offset(char_offset(&text, range.start));
+        let mut end = buffer.iter_at_offset(char_offset(&text, range.end));
+
+        buffer.begin_user_action();
+        buffer.delete(&mut start, &mut end);
+        buffer.insert(&mut start, &replacement);
+        buffer.end_user_action();
+    }
+
+    select_match(search_bar, json_editor, tree_view, true);
+}
+
+fn expand_replacement(
+    search_bar: &SearchBarState,
+    regex: &Regex,
+    text: &str,
+    range: Range<usize>,
+) -> String {
+    let replacement = search_bar.replace_entry.text();
+    if !search_bar.regex_check.is_active() {
+        return replacement.to_string();
+    }
+
+    // Searching from the match start keeps anchors and word boundaries working
+    let mut expanded = String::new();
+    if let Some(captures) = regex.captures_at(text, range.start) {
+        captures.expand(replacement.as_str(), &mut expanded);
+    }
+    expanded
+}
+
+fn replace_all_action(search_bar: SearchBarState, json_editor: JsonEditorState) {
+    if scope(&search_bar) != SearchScope::Text {
+        return;
+    }
+    let Some(Ok(regex)) = build_regex(&search_bar) else {
+        return;
+    };
+
+    let buffer = json_editor::retrieve_buffer(json_editor);
+    let text = buffer_text(&buffer);
+    let replacement = search_bar.replace_entry.text();
+
+    // Only the non-empty matches that Find counts, so e.g. `x*` doesn't insert the
+    // replacement between every character
+    let mut replaced = String::with_capacity(text.len());
+    let mut last_end = 0;
+    for captures in regex.captures_iter(&text) {
+        let whole = captures.get(0).unwrap();
+        if whole.is_empty() {
+            continue;
+        }
+        replaced.push_str(&text[last_end..whole.start()]);
+        if search_bar.regex_check.is_active() {
+            captures.expand(replacement.as_str(), &mut replaced);
+        } else {
+            replaced.push_str(replacement.as_str());
+        }
+        last_end = whole.end();
+    }
+    replaced.push_str(&text[last_end..]);
+
+    if replaced != text {
+        json_editor::replace_buffer_text(&buffer, &replaced);
+    }
+}
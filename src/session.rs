@@ -0,0 +1,217 @@
+// Keeps the text, cursor and tree state between runs, so pasted payloads survive Arson
+// being closed or crashing
+use crate::file_mgt;
+use crate::json_editor::JsonEditorState;
+use crate::recent::RecentState;
+use crate::tree_view::TreeViewState;
+use crate::{json_editor, tree_view};
+use gtk::glib::{ControlFlow, Propagation};
+use gtk::prelude::*;
+use gtk::{gio, glib};
+use gtk::{ApplicationWindow, ButtonsType, MessageDialog, MessageType, ResponseType};
+use serde::{Deserialize, Serialize};
+use std::cell::Cell;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::sync::atomic::{AtomicU64, Ordering as AtomicOrdering};
+use std::sync::Mutex;
+
+const AUTOSAVE_INTERVAL_SECONDS: u32 = 5;
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+struct Session {
+    file_path: Option<PathBuf>,
+    // Whether the text had changes not saved to the file
+    modified: bool,
+    cursor_offset: i32,
+    expanded: Vec<String>,
+    selected: Option<String>,
+}
+
+fn session_dir() -> PathBuf {
+    glib::user_data_dir().join("arson").join("session")
+}
+
+// Replace a file without leaving it truncated if Arson crashes part way through
+fn write_atomically(path: &Path, contents: &[u8]) -> Result<(), String> {
+    let temp_path = path.with_extension("tmp");
+    fs::write(&temp_path, contents).map_err(|e| e.to_string())?;
+    fs::rename(&temp_path, path).map_err(|e| e.to_string())
+}
+
+// Everything but the text, which is small enough to write whenever the cursor moves
+fn save_session(json_editor: JsonEditorState, tree_view: TreeViewState) -> Result<(), String> {
+    let buffer = json_editor::retrieve_buffer(json_editor.clone());
+    let session = Session {
+        file_path: json_editor::file_path(json_editor),
+        modified: buffer.is_modified(),
+        cursor_offset: buffer.cursor_position(),
+        expanded: tree_view::expanded_pointers(tree_view.clone()),
+        selected: tree_view::selected_pointer(tree_view),
+    };
+
+    let dir = session_dir();
+    fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+    let contents = toml::to_string_pretty(&session).map_err(|e| e.to_string())?;
+    write_atomically(&dir.join("session.toml"), contents.as_bytes())
+}
+
+// The text is kept in a file of its own, as it can be far larger than the rest, and written
+// on a worker thread. Each copy of the text is numbered, so an older copy still being
+// written never replaces a newer one.
+static BUFFER_SEQUENCE: AtomicU64 = AtomicU64::new(0);
+// The number of the copy last written, locked while writing
+static BUFFER_WRITTEN: Mutex<u64> = Mutex::new(0);
+
+fn copy_text(json_editor: JsonEditorState) -> (String, u64) {
+    let buffer = json_editor::retrieve_buffer(json_editor);
+    let (start, end) = buffer.bounds();
+    let text = buffer.text(&start, &end, true).unwrap().to_string();
+    (
+        text,
+        BUFFER_SEQUENCE.fetch_add(1, AtomicOrdering::SeqCst) + 1,
+    )
+}
+
+fn write_text(text: &str, sequence: u64) -> Result<(), String> {
+    let mut written = BUFFER_WRITTEN.lock().map_err(|e| e.to_string())?;
+    if *written > sequence {
+        return Ok(());
+    }
+    let dir = session_dir();
+    fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+    write_atomically(&dir.join("buffer.txt"), text.as_bytes())?;
+    *written = sequence;
+    Ok(())
+}
+
+// Show the last session's text, or an empty object the first time
+pub fn restore_on_show(json_editor: JsonEditorState, tree_view: TreeViewState) {
+    let dir = session_dir();
+    let text = fs::read_to_string(dir.join("buffer.txt")).ok();
+    let session: Session = fs::read_to_string(dir.join("session.toml"))
+        .ok()
+        .and_then(|contents| toml::from_str(&contents).ok())
+        .unwrap_or_default();
+
+    let Some(text) = text else {
+        json_editor::init_on_show(json_editor, "{}");
+        return;
+    };
+
+    if !session.expanded.is_empty() {
+        tree_view::restore_state(tree_view, session.expanded, session.selected);
+    }
+    json_editor::init_on_show(json_editor.clone(), &text);
+    json_editor::set_file_path(json_editor.clone(), session.file_path);
+    json_editor::select_range(
+        json_editor.clone(),
+        session.cursor_offset,
+        session.cursor_offset,
+    );
+    json_editor::retrieve_buffer(json_editor).set_modified(session.modified);
+}
+
+pub fn attach_listeners(
+    win: &ApplicationWindow,
+    json_editor: JsonEditorState,
+    tree_view: TreeViewState,
+    recent: RecentState,
+) {
+    // Only write out what has changed since the last time, as the text can be large
+    let text_changed = Rc::new(Cell::new(false));
+    let state_changed = Rc::new(Cell::new(false));
+    let buffer = json_editor::retrieve_buffer(json_editor.clone());
+    buffer.connect_changed({
+        let text_changed = text_changed.clone();
+        move |_| text_changed.set(true)
+    });
+    buffer.connect_mark_set({
+        let state_changed = state_changed.clone();
+        move |_, _, mark| {
+            if mark.name().as_deref() == Some("insert") {
+                state_changed.set(true);
+            }
+        }
+    });
+    tree_view.tree_view.connect_row_expanded({
+        let state_changed = state_changed.clone();
+        move |_, _, _| state_changed.set(true)
+    });
+    tree_view.tree_view.connect_row_collapsed({
+        let state_changed = state_changed.clone();
+        move |_, _, _| state_changed.set(true)
+    });
+    tree_view.tree_view.selection().connect_changed({
+        let state_changed = state_changed.clone();
+        move |_| state_changed.set(true)
+    });
+
+    glib::timeout_add_seconds_local(AUTOSAVE_INTERVAL_SECONDS, {
+        let json_editor = json_editor.clone();
+        let tree_view = tree_view.clone();
+        let text_changed = text_changed.clone();
+        let state_changed = state_changed.clone();
+        move || {
+            let text_changed = text_changed.replace(false);
+            if text_changed {
+                let (text, sequence) = copy_text(json_editor.clone());
+                gio::spawn_blocking(move || {
+                    let _ = write_text(&text, sequence);
+                });
+            }
+            if state_changed.replace(false) || text_changed {
+                let _ = save_session(json_editor.clone(), tree_view.clone());
+            }
+            ControlFlow::Continue
+        }
+    });
+
+    // Also reached from File > Quit, as closing the window sends a delete event
+    win.connect_delete_event(move |win, _| {
+        if json_editor::retrieve_buffer(json_editor.clone()).is_modified() {
+            let dialog = MessageDialog::builder()
+                .transient_for(win)
+                .modal(true)
+                .message_type(MessageType::Question)
+                .buttons(ButtonsType::None)
+                .title("Unsaved changes")
+                .text("Save changes before closing?")
+                .secondary_text(
+                    "The text will be restored next time Arson is opened, but has not been saved to a file.",
+                )
+                .build();
+            dialog.add_buttons(&[
+                ("Close Without Saving", ResponseType::Reject),
+                ("Cancel", ResponseType::Cancel),
+                ("Save", ResponseType::Accept),
+            ]);
+            dialog.set_default_response(ResponseType::Accept);
+            let response = dialog.run();
+            dialog.close();
+
+            let close = match response {
+                ResponseType::Reject => true,
+                ResponseType::Accept => file_mgt::file_save_item_action(
+                    win.clone(),
+                    json_editor.clone(),
+                    recent.clone(),
+                ),
+                _ => false,
+            };
+            if !close {
+                return Propagation::Stop;
+            }
+        }
+
+        // Written here rather than on a worker, as Arson is about to exit
+        if text_changed.replace(false) {
+            let (text, sequence) = copy_text(json_editor.clone());
+            let _ = write_text(&text, sequence);
+        }
+        let _ = save_session(json_editor.clone(), tree_view.clone());
+        Propagation::Proceed
+    });
+}
@@ -0,0 +1,197 @@
+use crate::json_editor::JsonEditorState;
+use crate::preferences::PreferencesState;
+use crate::tree_view::TreeViewState;
+use crate::{cst, json_editor, json_pointer, preferences, tree_view};
+use gtk::prelude::*;
+use gtk::{
+    Align, ApplicationWindow, ButtonsType, CheckButton, Entry, Grid, Label, MessageDialog,
+    MessageType, RadioButton, ResponseType, WindowPosition,
+};
+use std::cmp::Ordering;
+
+#[derive(Clone, Debug)]
+pub enum KeyOrder {
+    Alphabetical,
+    // Runs of digits compare by their numeric value, so `item2` sorts before `item10`
+    Natural,
+    // Listed keys come first in the order given, everything else alphabetically after them
+    Priority(Vec<String>),
+}
+
+impl KeyOrder {
+    pub fn compare(&self, a: &str, b: &str) -> Ordering {
+        match self {
+            KeyOrder::Alphabetical => a.cmp(b),
+            KeyOrder::Natural => natural_cmp(a, b),
+            KeyOrder::Priority(keys) => {
+                let rank = |k: &str| keys.iter().position(|p| p == k).unwrap_or(keys.len());
+                rank(a).cmp(&rank(b)).then_with(|| a.cmp(b))
+            }
+        }
+    }
+}
+
+pub fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let mut a_chars = a.chars().peekable();
+    let mut b_chars = b.chars().peekable();
+
+    loop {
+        match (a_chars.peek().copied(), b_chars.peek().copied()) {
+            (None, None) => return a.cmp(b),
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(ca), Some(cb)) if ca.is_ascii_digit() && cb.is_ascii_digit() => {
+                let a_run = take_digits(&mut a_chars);
+                let b_run = take_digits(&mut b_chars);
+                let a_num = a_run.trim_start_matches('0');
+                let b_num = b_run.trim_start_matches('0');
+                let ordering = a_num.len().cmp(&b_num.len()).then_with(|| a_num.cmp(b_num));
+                if ordering != Ordering::Equal {
+                    return ordering;
+                }
+            }
+            (Some(ca), Some(cb)) => {
+                let ordering = ca.to_lowercase().cmp(cb.to_lowercase());
+                if ordering != Ordering::Equal {
+                    return ordering;
+                }
+                a_chars.next();
+                b_chars.next();
+            }
+        }
+    }
+}
+
+fn take_digits(chars: &mut std::iter::Peekable<std::str::Chars>) -> String {
+    let mut run = String::new();
+    while let Some(c) = chars.next_if(|c| c.is_ascii_digit()) {
+        run.push(c);
+    }
+    run
+}
+
+pub fn sort_keys_action(
+    win: ApplicationWindow,
+    json_editor: JsonEditorState,
+    tree_view: TreeViewState,
+    preferences: PreferencesState,
+) {
+    let dialog = gtk::Dialog::builder()
+        .transient_for(&win)
+        .window_position(WindowPosition::CenterOnParent)
+        .title("Sort Keys")
+        .modal(true)
+        .build();
+    dialog.add_buttons(&[("Cancel", ResponseType::Cancel), ("Sort", ResponseType::Ok)]);
+
+    let grid = Grid::builder()
+        .row_spacing(6)
+        .column_spacing(12)
+        .margin(12)
+        .build();
+    dialog.content_area().add(&grid);
+
+    let order_label = Label::builder()
+        .label("<b>Order</b>")
+        .use_markup(true)
+        .halign(Align::Start)
+        .build();
+    grid.attach(&order_label, 0, 0, 2, 1);
+
+    let alphabetical = RadioButton::with_label("Alphabetical");
+    let natural =
+        RadioButton::with_label_from_widget(&alphabetical, "Natural (item2 before item10)");
+    let priority = RadioButton::with_label_from_widget(&alphabetical, "Priority list:");
+    let priority_keys = Entry::builder()
+        .text(preferences.borrow().key_priority.join(", "))
+        .placeholder_text("id, name, type")
+        .hexpand(true)
+        .build();
+    grid.attach(&alphabetical, 0, 1, 2, 1);
+    grid.attach(&natural, 0, 2, 2, 1);
+    grid.attach(&priority, 0, 3, 1, 1);
+    grid.attach(&priority_keys, 1, 3, 1, 1);
+
+    let scope_label = Label::builder()
+        .label("<b>Scope</b>")
+        .use_markup(true)
+        .halign(Align::Start)
+        .build();
+    grid.attach(&scope_label, 0, 4, 2, 1);
+
+    let whole_document = RadioButton::with_label("Whole document");
+    let selected_node =
+        RadioButton::with_label_from_widget(&whole_document, "Selected node in the tree");
+    let recursive = CheckButton::with_label("Include nested objects");
+    recursive.set_active(true);
+    grid.attach(&whole_document, 0, 5, 2, 1);
+    grid.attach(&selected_node, 0, 6, 2, 1);
+    grid.attach(&recursive, 0, 7, 2, 1);
+
+    if tree_view::selected_pointer(tree_view.clone()).is_some() {
+        selected_node.set_active(true);
+    }
+
+    dialog.connect_response(move |dialog, response| {
+        if response != ResponseType::Ok {
+            dialog.close();
+            return;
+        }
+
+        let key_order = if natural.is_active() {
+            KeyOrder::Natural
+        } else if priority.is_active() {
+            let keys: Vec<String> = priority_keys
+                .text()
+                .split(',')
+                .map(|k| k.trim().to_string())
+                .filter(|k| !k.is_empty())
+                .collect();
+            preferences.borrow_mut().key_priority = keys.clone();
+            let _ = preferences::save(&preferences.borrow());
+            KeyOrder::Priority(keys)
+        } else {
+            KeyOrder::Alphabetical
+        };
+
+        let pointer = if selected_node.is_active() {
+            tree_view::selected_pointer(tree_view.clone()).unwrap_or_default()
+        } else {
+            String::new()
+        };
+
+        let buffer = json_editor::retrieve_buffer(json_editor.clone());
+        let (start, end) = buffer.bounds();
+        let current_text = buffer.text(&start, &end, true).unwrap();
+
+        // Sorting the syntax tree rather than a serde_json::Value keeps comments and formatting
+        match json_editor::parse_text(json_editor.clone(), current_text.as_str())
+            .and_then(|_| cst::parse(current_text.as_str()))
+        {
+            Ok(mut document) => {
+                let segments = json_pointer::segments(&pointer);
+                let compare = |a: &str, b: &str| key_order.compare(a, b);
+                if document.sort_keys(&segments, &compare, recursive.is_active()) {
+                    json_editor::replace_buffer_text(&buffer, &document.to_string());
+                }
+            }
+            Err(e) => {
+                let error_dialog = MessageDialog::builder()
+                    .transient_for(dialog)
+                    .window_position(WindowPosition::CenterOnParent)
+                    .message_type(MessageType::Warning)
+                    .buttons(ButtonsType::Ok)
+                    .title("JSON was invalid")
+                    .text(format!("The current text was not valid JSON.\n\n{}", e))
+                    .build();
+                error_dialog.connect_response(move |error_dialog, _| {
+                    error_dialog.close();
+                });
+                error_dialog.run();
+            }
+        }
+        dialog.close();
+    });
+
+    dialog.show_all();
+}
@@ -0,0 +1,582 @@
+// An overview of an unfamiliar document: what it's made of, where it's deepest and biggest,
+// and which keys its records have. Entries select their value in the tree.
+use crate::json_editor::JsonEditorState;
+use crate::tree_view::TreeViewState;
+use crate::units::{format_bytes, group_thousands};
+use crate::{json_editor, json_parser, json_pointer, tree_view};
+use gtk::pango::EllipsizeMode;
+use gtk::prelude::*;
+use gtk::{gio, glib};
+use gtk::{
+    Align, ApplicationWindow, Button, CellRendererText, Label, Orientation, ScrolledWindow,
+    ShadowType, TreeIter, TreeStore, TreeView, TreeViewColumn, WindowPosition,
+};
+use serde_json::Value;
+use std::collections::HashMap;
+
+// Entries shown for each of the top lists
+const TOP_ENTRIES: usize = 10;
+// Array element paths shown under "Keys in records"
+const MAX_RECORD_PATHS: usize = 20;
+const PREVIEW_CHARS: usize = 40;
+
+const COL_ITEM: u32 = 0;
+const COL_DETAIL: u32 = 1;
+const COL_POINTER: u32 = 2;
+// Whether selecting the row selects COL_POINTER in the tree, as the root pointer is empty
+const COL_NAVIGABLE: u32 = 3;
+
+// A value ranked by some size, with how to describe it
+struct Ranked {
+    size: usize,
+    pointer: String,
+    detail: String,
+}
+
+// Objects found in arrays at the same path, e.g. every element of every `/users/*/orders`
+#[derive(Default)]
+struct Records {
+    // The first array they were found in
+    first_array: String,
+    count: usize,
+    key_counts: HashMap<String, usize>,
+}
+
+#[derive(Default)]
+struct Statistics {
+    // object, array, string, number, bool and null
+    type_counts: [usize; 6],
+    // Deepest leaf for each path, with array indices replaced by `*`
+    deepest: HashMap<String, Ranked>,
+    largest_arrays: Vec<Ranked>,
+    longest_strings: Vec<Ranked>,
+    largest_subtrees: Vec<Ranked>,
+    total_bytes: usize,
+    records: HashMap<String, Records>,
+}
+
+const TYPE_NAMES: [&str; 6] = [
+    "Objects", "Arrays", "Strings", "Numbers", "Booleans", "Nulls",
+];
+
+// Keep the largest few, largest first
+fn push_top(top: &mut Vec<Ranked>, ranked: Ranked) {
+    if top.len() == TOP_ENTRIES && top.last().is_some_and(|last| last.size >= ranked.size) {
+        return;
+    }
+    let index = top.partition_point(|existing| existing.size >= ranked.size);
+    top.insert(index, ranked);
+    top.truncate(TOP_ENTRIES);
+}
+
+fn describe_pointer(pointer: &str) -> &str {
+    if pointer.is_empty() {
+        "(root)"
+    } else {
+        pointer
+    }
+}
+
+impl Statistics {
+    // Returns the size of the value as minified JSON, so parents can add up their children
+    fn walk(&mut self, v: &Value, pointer: &str, path: &str, depth: usize) -> usize {
+        let size = match v {
+            Value::Object(members) => {
+                self.type_counts[0] += 1;
+                let mut size = 2 + members.len().saturating_sub(1);
+                for (key, member) in members {
+                    size += Value::String(key.clone()).to_string().len() + 1;
+                    size += self.walk(
+                        member,
+                        &json_pointer::append(pointer, key),
+                        &json_pointer::append(path, key),
+                        depth + 1,
+                    );
+                }
+                size
+            }
+            Value::Array(items) => {
+                self.type_counts[1] += 1;
+                push_top(
+                    &mut self.largest_arrays,
+                    Ranked {
+                        size: items.len(),
+                        pointer: pointer.to_string(),
+                        detail: format!("{} items", group_thousands(items.len())),
+                    },
+                );
+
+                let item_path = format!("{}/*", path);
+                let mut size = 2 + items.len().saturating_sub(1);
+                for (i, item) in items.iter().enumerate() {
+                    if let Value::Object(members) = item {
+                        let records = self.records.entry(item_path.clone()).or_default();
+                        if records.count == 0 {
+                            records.first_array = pointer.to_string();
+                        }
+                        records.count += 1;
+                        for key in members.keys() {
+                            *records.key_counts.entry(key.clone()).or_default() += 1;
+                        }
+                    }
+                    size += self.walk(
+                        item,
+                        &json_pointer::append(pointer, &i.to_string()),
+                        &item_path,
+                        depth + 1,
+                    );
+                }
+                size
+            }
+            Value::String(s) => {
+                self.type_counts[2] += 1;
+                let chars = s.chars().count();
+                if self.longest_strings.len() < TOP_ENTRIES
+                    || self
+                        .longest_strings
+                        .last()
+                        .is_some_and(|last| last.size < chars)
+                {
+                    let mut preview: String = s.chars().take(PREVIEW_CHARS).collect();
+                    if chars > PREVIEW_CHARS {
+                        preview.push('…');
+                    }
+                    push_top(
+                        &mut self.longest_strings,
+                        Ranked {
+                            size: chars,
+                            pointer: pointer.to_string(),
+                            detail: format!("{} chars: {}", group_thousands(chars), preview),
+                        },
+                    );
+                }
+                v.to_string().len()
+            }
+            Value::Number(_) => {
+                self.type_counts[3] += 1;
+                v.to_string().len()
+            }
+            Value::Bool(_) => {
+                self.type_counts[4] += 1;
+                v.to_string().len()
+            }
+            Value::Null => {
+                self.type_counts[5] += 1;
+                4
+            }
+        };
+
+        let is_leaf = match v {
+            Value::Object(members) => members.is_empty(),
+            Value::Array(items) => items.is_empty(),
+            _ => true,
+        };
+        if is_leaf
+            && self
+                .deepest
+                .get(path)
+                .is_none_or(|existing| existing.size < depth)
+        {
+            self.deepest.insert(
+                path.to_string(),
+                Ranked {
+                    size: depth,
+                    pointer: pointer.to_string(),
+                    detail: format!("Depth {}", depth),
+                },
+            );
+        }
+
+        // The root always contains everything, so isn't worth listing
+        if depth > 0 && matches!(v, Value::Object(_) | Value::Array(_)) {
+            push_top(
+                &mut self.largest_subtrees,
+                Ranked {
+                    size,
+                    pointer: pointer.to_string(),
+                    detail: String::new(),
+                },
+            );
+        }
+
+        size
+    }
+}
+
+fn compute(v: &Value) -> Statistics {
+    let mut statistics = Statistics::default();
+    statistics.total_bytes = statistics.walk(v, "", "", 0);
+    statistics
+}
+
+// The deepest leaf of each path, deepest first
+fn deepest_paths(deepest: HashMap<String, Ranked>) -> Vec<Ranked> {
+    let mut deepest: Vec<Ranked> = deepest.into_values().collect();
+    deepest.sort_by(|a, b| b.size.cmp(&a.size).then_with(|| a.pointer.cmp(&b.pointer)));
+    deepest.truncate(TOP_ENTRIES);
+    deepest
+}
+
+// How many of the records have a key
+fn describe_key_count(count: usize, records: usize) -> String {
+    format!(
+        "{:.0}% ({} of {})",
+        count as f64 * 100.0 / records as f64,
+        group_thousands(count),
+        group_thousands(records)
+    )
+}
+
+fn append_row(
+    model: &TreeStore,
+    parent: Option<&TreeIter>,
+    item: &str,
+    detail: &str,
+    pointer: Option<&str>,
+) -> TreeIter {
+    let iter = model.append(parent);
+    model.set(
+        &iter,
+        &[
+            (COL_ITEM, &item),
+            (COL_DETAIL, &detail),
+            (COL_POINTER, &pointer.unwrap_or_default()),
+            (COL_NAVIGABLE, &pointer.is_some()),
+        ],
+    );
+    iter
+}
+
+fn append_ranked(model: &TreeStore, title: &str, ranked: &[Ranked]) {
+    let section = append_row(model, None, title, "", None);
+    for entry in ranked {
+        append_row(
+            model,
+            Some(&section),
+            describe_pointer(&entry.pointer),
+            &entry.detail,
+            Some(&entry.pointer),
+        );
+    }
+}
+
+fn show_statistics(model: &TreeStore, mut statistics: Statistics) {
+    model.clear();
+
+    let total_values: usize = statistics.type_counts.iter().sum();
+    let section = append_row(
+        model,
+        None,
+        "Value types",
+        &format!("{} values", group_thousands(total_values)),
+        None,
+    );
+    for (name, count) in TYPE_NAMES.iter().zip(statistics.type_counts) {
+        append_row(
+            model,
+            Some(&section),
+            name,
+            &format!(
+                "{} ({:.1}%)",
+                group_thousands(count),
+                count as f64 * 100.0 / total_values.max(1) as f64
+            ),
+            None,
+        );
+    }
+
+    append_ranked(model, "Deepest paths", &deepest_paths(statistics.deepest));
+    append_ranked(model, "Largest arrays", &statistics.largest_arrays);
+    append_ranked(model, "Longest strings", &statistics.longest_strings);
+
+    for subtree in &mut statistics.largest_subtrees {
+        subtree.detail = format!(
+            "{} ({:.1}% of {})",
+            format_bytes(subtree.size),
+            subtree.size as f64 * 100.0 / statistics.total_bytes.max(1) as f64,
+            format_bytes(statistics.total_bytes)
+        );
+    }
+    append_ranked(
+        model,
+        "Largest subtrees (minified)",
+        &statistics.largest_subtrees,
+    );
+
+    // Paths with a single record say nothing about how consistent the records are
+    let mut records: Vec<(String, Records)> = statistics
+        .records
+        .into_iter()
+        .filter(|(_, records)| records.count > 1)
+        .collect();
+    records.sort_by(|(a_path, a), (b_path, b)| b.count.cmp(&a.count).then(a_path.cmp(b_path)));
+    records.truncate(MAX_RECORD_PATHS);
+    let section = append_row(model, None, "Keys in records", "", None);
+    for (path, records) in records {
+        let path_row = append_row(
+            model,
+            Some(&section),
+            &path,
+            &format!("{} records", group_thousands(records.count)),
+            Some(&records.first_array),
+        );
+        let mut keys: Vec<(String, usize)> = records.key_counts.into_iter().collect();
+        keys.sort_by(|(a_key, a), (b_key, b)| b.cmp(a).then(a_key.cmp(b_key)));
+        for (key, count) in keys {
+            append_row(
+                model,
+                Some(&path_row),
+                &key,
+                &describe_key_count(count, records.count),
+                Some(&records.first_array),
+            );
+        }
+    }
+}
+
+// Parse and measure the text on a worker thread, as it walks every value
+fn refresh(model: TreeStore, list: TreeView, status_label: Label, json_editor: JsonEditorState) {
+    let buffer = json_editor::retrieve_buffer(json_editor.clone());
+    let (start, end) = buffer.bounds();
+    let text = buffer.text(&start, &end, true).unwrap().to_string();
+    let parse_mode = json_editor::parse_mode(json_editor);
+
+    status_label.set_label("Counting...");
+    let computing =
+        gio::spawn_blocking(move || json_parser::parse(&text, parse_mode).map(|v| compute(&v)));
+    glib::MainContext::default().spawn_local(async move {
+        match computing.await {
+            Ok(Ok(statistics)) => {
+                show_statistics(&model, statistics);
+                // Open the sections, but not each path's list of keys
+                let mut section = model.iter_first();
+                while let Some(iter) = section {
+                    if let Some(path) = model.path(&iter) {
+                        list.expand_row(&path, false);
+                    }
+                    section = model.iter_next(&iter).then_some(iter);
+                }
+                status_label.set_label("Select an entry to show it in the tree");
+            }
+            Ok(Err(e)) => status_label.set_label(&format!("The text isn't valid JSON: {}", e)),
+            Err(_) => status_label.set_label("Counting failed"),
+        }
+    });
+}
+
+pub fn statistics_action(
+    win: ApplicationWindow,
+    json_editor: JsonEditorState,
+    tree_view: TreeViewState,
+) {
+    let window = gtk::Window::builder()
+        .transient_for(&win)
+        .window_position(WindowPosition::CenterOnParent)
+        .title("Statistics")
+        .default_width(700)
+        .default_height(550)
+        .build();
+
+    let v_box = gtk::Box::builder()
+        .orientation(Orientation::Vertical)
+        .spacing(6)
+        .margin(8)
+        .build();
+    window.add(&v_box);
+
+    let model = TreeStore::new(&[
+        String::static_type(),
+        String::static_type(),
+        String::static_type(),
+        bool::static_type(),
+    ]);
+    let list = TreeView::builder()
+        .model(&model)
+        .activate_on_single_click(true)
+        .build();
+    for (title, column_id) in [("Entry", COL_ITEM), ("Detail", COL_DETAIL)] {
+        let column = TreeViewColumn::new();
+        column.set_title(title);
+        column.set_resizable(true);
+        column.set_expand(true);
+        let cell = CellRendererText::new();
+        cell.set_ellipsize(EllipsizeMode::Middle);
+        gtk::prelude::CellLayoutExt::pack_start(&column, &cell, true);
+        gtk::prelude::TreeViewColumnExt::add_attribute(&column, &cell, "text", column_id as i32);
+        list.append_column(&column);
+    }
+
+    let scroller = ScrolledWindow::builder()
+        .shadow_type(ShadowType::In)
+        .expand(true)
+        .build();
+    scroller.add(&list);
+    v_box.add(&scroller);
+
+    let bottom_box = gtk::Box::new(Orientation::Horizontal, 6);
+    let status_label = Label::builder()
+        .halign(Align::Start)
+        .hexpand(true)
+        .ellipsize(EllipsizeMode::End)
+        .build();
+    let refresh_button = Button::with_label("Refresh");
+    bottom_box.pack_start(&status_label, true, true, 0);
+    bottom_box.pack_end(&refresh_button, false, false, 0);
+    v_box.add(&bottom_box);
+
+    list.connect_row_activated({
+        let status_label = status_label.clone();
+        let json_editor = json_editor.clone();
+        move |list, path, _| {
+            let Some(model) = list.model() else {
+                return;
+            };
+            let Some(iter) = model.iter(path) else {
+                return;
+            };
+            if !model
+                .value(&iter, COL_NAVIGABLE as i32)
+                .get::<bool>()
+                .unwrap()
+            {
+                return;
+            }
+            let pointer = model
+                .value(&iter, COL_POINTER as i32)
+                .get::<String>()
+                .unwrap();
+            if !tree_view::reveal_pointer(json_editor.clone(), tree_view.clone(), &pointer) {
+                status_label.set_label("Not found in the tree, the text may have changed");
+            }
+        }
+    });
+
+    refresh_button.connect_clicked({
+        let model = model.clone();
+        let list = list.clone();
+        let status_label = status_label.clone();
+        let json_editor = json_editor.clone();
+        move |_| {
+            refresh(
+                model.clone(),
+                list.clone(),
+                status_label.clone(),
+                json_editor.clone(),
+            )
+        }
+    });
+
+    refresh(model, list, status_label, json_editor);
+    window.show_all();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn pointers(ranked: &[Ranked]) -> Vec<&str> {
+        ranked.iter().map(|r| r.pointer.as_str()).collect()
+    }
+
+    #[test]
+    fn sizes_are_minified_sizes() {
+        let v = json!({
+            "name": "quote \" and \\ and \n and é",
+            "n": [1, -2.5, 1e100, 0],
+            "empty": {"a": [], "b": {}},
+            "flags": [true, false, null],
+        });
+        let statistics = compute(&v);
+        assert_eq!(
+            statistics.total_bytes,
+            serde_json::to_string(&v).unwrap().len()
+        );
+        for subtree in &statistics.largest_subtrees {
+            let value = v.pointer(&subtree.pointer).unwrap();
+            assert_eq!(subtree.size, serde_json::to_string(value).unwrap().len());
+        }
+        assert_eq!(
+            pointers(&statistics.largest_subtrees),
+            ["/flags", "/n", "/empty", "/empty/a", "/empty/b"]
+        );
+        assert_eq!(statistics.type_counts, [3, 3, 1, 4, 2, 1]);
+    }
+
+    #[test]
+    fn top_lists_keep_the_largest() {
+        let arrays: Vec<Value> = (0..15).map(|n| json!(vec![0; n])).collect();
+        let strings: Vec<Value> = (0..15).map(|n| json!("x".repeat(n))).collect();
+        let statistics = compute(&json!({"arrays": arrays, "strings": strings}));
+
+        // The arrays holding them are the largest of all
+        let mut expected = vec!["/arrays".to_string(), "/strings".to_string()];
+        expected.extend((7..15).rev().map(|n| format!("/arrays/{}", n)));
+        assert_eq!(pointers(&statistics.largest_arrays), expected);
+        assert_eq!(statistics.largest_arrays[0].detail, "15 items");
+        assert_eq!(statistics.largest_arrays[2].detail, "14 items");
+
+        let expected: Vec<String> = (5..15).rev().map(|n| format!("/strings/{}", n)).collect();
+        assert_eq!(pointers(&statistics.longest_strings), expected);
+        assert_eq!(
+            statistics.longest_strings[0].detail,
+            "14 chars: xxxxxxxxxxxxxx"
+        );
+    }
+
+    #[test]
+    fn deepest_leaf_of_each_path() {
+        let v = json!({
+            "a": [{"b": [[1]]}, {"b": [[[2]]]}],
+            "c": {"d": null},
+            "e": [],
+        });
+        let statistics = compute(&v);
+        let deepest = deepest_paths(statistics.deepest);
+        let described: Vec<(&str, &str)> = deepest
+            .iter()
+            .map(|r| (r.pointer.as_str(), r.detail.as_str()))
+            .collect();
+        assert_eq!(
+            described,
+            [
+                ("/a/1/b/0/0/0", "Depth 6"),
+                ("/a/0/b/0/0", "Depth 5"),
+                ("/c/d", "Depth 2"),
+                ("/e", "Depth 1"),
+            ]
+        );
+    }
+
+    #[test]
+    fn keys_in_records() {
+        let v = json!({
+            "users": [
+                {"id": 1, "name": "a", "email": "a@example.com"},
+                {"id": 2, "name": "b"},
+                {"id": 3},
+                "not a record",
+            ],
+            "groups": [{"users": [{"id": 4, "admin": true}]}, {"users": [{"id": 5}]}],
+        });
+        let statistics = compute(&v);
+
+        let users = &statistics.records["/users/*"];
+        assert_eq!(users.first_array, "/users");
+        assert_eq!(users.count, 3);
+        assert_eq!(users.key_counts["id"], 3);
+        assert_eq!(users.key_counts["name"], 2);
+        assert_eq!(users.key_counts["email"], 1);
+
+        // Records at the same path are counted together across arrays
+        let nested = &statistics.records["/groups/*/users/*"];
+        assert_eq!(nested.first_array, "/groups/0/users");
+        assert_eq!(nested.count, 2);
+        assert_eq!(nested.key_counts["admin"], 1);
+
+        assert_eq!(describe_key_count(3, 3), "100% (3 of 3)");
+        assert_eq!(describe_key_count(2, 3), "67% (2 of 3)");
+        assert_eq!(describe_key_count(1, 3), "33% (1 of 3)");
+        assert_eq!(describe_key_count(1, 1500), "0% (1 of 1,500)");
+    }
+}
@@ -0,0 +1,204 @@
+use crate::json_editor::JsonEditorState;
+use crate::parse_summary::ParseSummary;
+use crate::preferences::PreferencesState;
+use crate::tree_view::TreeViewState;
+use crate::units::{format_bytes, group_thousands};
+use crate::{canonical, json_editor, tree_view};
+use gtk::pango::EllipsizeMode;
+use gtk::prelude::*;
+use gtk::{Label, Orientation};
+use serde_json::Value;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+#[derive(Clone)]
+pub struct StatusBarState {
+    pub status_bar: gtk::Box,
+    position_label: Label,
+    pointer_label: Label,
+    size_label: Label,
+    parse_label: Label,
+    depth_label: Label,
+    nodes_label: Label,
+    hash_label: Label,
+    // From the latest parse, used to find the pointer under the cursor as it moves
+    summary: Rc<RefCell<Option<Rc<ParseSummary>>>>,
+}
+
+pub fn factory_status_bar() -> StatusBarState {
+    let status_bar = gtk::Box::builder()
+        .visible(true)
+        .orientation(Orientation::Horizontal)
+        .spacing(12)
+        .margin_start(6)
+        .margin_end(6)
+        .margin_top(2)
+        .margin_bottom(2)
+        .build();
+
+    let position_label = Label::builder()
+        .tooltip_text("Line and column of the cursor")
+        .build();
+    status_bar.pack_start(&position_label, false, false, 0);
+
+    let pointer_label = Label::builder()
+        .no_show_all(true)
+        .selectable(true)
+        .ellipsize(EllipsizeMode::Middle)
+        .max_width_chars(60)
+        .build();
+    status_bar.pack_start(&pointer_label, false, false, 0);
+
+    let hash_label = Label::builder()
+        .no_show_all(true)
+        .selectable(true)
+        .tooltip_text("SHA-256 of the RFC 8785 canonical form of this JSON")
+        .build();
+    status_bar.pack_end(&hash_label, false, false, 0);
+
+    let parse_label = Label::new(None);
+    status_bar.pack_end(&parse_label, false, false, 0);
+
+    let nodes_label = Label::builder()
+        .tooltip_text("Number of values, including those inside arrays and objects")
+        .build();
+    status_bar.pack_end(&nodes_label, false, false, 0);
+
+    let depth_label = Label::builder()
+        .tooltip_text("How deeply arrays and objects are nested")
+        .build();
+    status_bar.pack_end(&depth_label, false, false, 0);
+
+    let size_label = Label::new(None);
+    status_bar.pack_end(&size_label, false, false, 0);
+
+    StatusBarState {
+        status_bar,
+        position_label,
+        pointer_label,
+        size_label,
+        parse_label,
+        depth_label,
+        nodes_label,
+        hash_label,
+        summary: Rc::new(RefCell::new(None)),
+    }
+}
+
+pub fn attach_listeners(
+    status_bar: StatusBarState,
+    json_editor: JsonEditorState,
+    tree_view: TreeViewState,
+    preferences: PreferencesState,
+) {
+    let buffer = json_editor::retrieve_buffer(json_editor.clone());
+    buffer.connect_changed({
+        let status_bar = status_bar.clone();
+        let json_editor = json_editor.clone();
+        move |_| {
+            // Small documents are parsed again before this would be seen
+            if json_editor::is_large_document(json_editor.clone()) {
+                status_bar.parse_label.set_label("Parsing...");
+            }
+        }
+    });
+
+    buffer.connect_cursor_position_notify({
+        let status_bar = status_bar.clone();
+        let json_editor = json_editor.clone();
+        move |_| update_cursor(status_bar.clone(), json_editor.clone())
+    });
+
+    tree_view::connect_parsed(tree_view, move |summary, parsed| {
+        show_summary(&status_bar, &summary);
+        if preferences.borrow().show_canonical_hash {
+            show_canonical_hash(&status_bar, parsed);
+        }
+        status_bar.summary.replace(Some(summary));
+        update_cursor(status_bar.clone(), json_editor.clone());
+    });
+}
+
+fn show_summary(status_bar: &StatusBarState, summary: &ParseSummary) {
+    status_bar.size_label.set_label(&format!(
+        "{}, {} chars",
+        format_bytes(summary.bytes),
+        group_thousands(summary.chars)
+    ));
+    status_bar.size_label.set_tooltip_text(Some(&format!(
+        "{} bytes as UTF-8",
+        group_thousands(summary.bytes)
+    )));
+
+    match &summary.error {
+        None => {
+            status_bar.parse_label.set_label("Valid");
+            status_bar.parse_label.set_tooltip_text(None);
+            status_bar
+                .depth_label
+                .set_label(&format!("Depth {}", summary.max_depth));
+            status_bar
+                .nodes_label
+                .set_label(&format!("{} nodes", group_thousands(summary.node_count)));
+        }
+        Some(e) => {
+            status_bar.parse_label.set_label("Invalid");
+            status_bar.parse_label.set_tooltip_text(Some(e));
+            // Keep the depth and count of the last valid document, like the tree does
+        }
+    }
+}
+
+pub fn update_cursor(status_bar: StatusBarState, json_editor: JsonEditorState) {
+    let buffer = json_editor::retrieve_buffer(json_editor);
+    let cursor = buffer.iter_at_offset(buffer.cursor_position());
+    status_bar.position_label.set_label(&format!(
+        "Ln {}, Col {}",
+        cursor.line() + 1,
+        cursor.line_offset() + 1
+    ));
+
+    let pointer = status_bar.summary.borrow().as_ref().and_then(|summary| {
+        summary.pointer_at(cursor.line() as usize, cursor.line_index() as usize)
+    });
+    match pointer {
+        Some(pointer) => {
+            let shown = if pointer.is_empty() {
+                "(root)"
+            } else {
+                &pointer
+            };
+            status_bar.pointer_label.set_label(shown);
+            status_bar
+                .pointer_label
+                .set_tooltip_text(Some(&format!("JSON Pointer at the cursor: {}", shown)));
+            status_bar.pointer_label.show();
+        }
+        None => status_bar.pointer_label.hide(),
+    }
+}
+
+fn show_canonical_hash(status_bar: &StatusBarState, parsed: Option<&Value>) {
+    match parsed {
+        Some(v) => status_bar.hash_label.set_label(&format!(
+            "SHA-256: {}",
+            canonical::sha256_hex(&canonical::canonicalize(v))
+        )),
+        None => status_bar.hash_label.set_label("SHA-256: (invalid JSON)"),
+    }
+    status_bar.hash_label.show();
+}
+
+// The hash is worked out after each parse, so showing it needs one
+pub fn update_canonical_hash(
+    status_bar: StatusBarState,
+    json_editor: JsonEditorState,
+    tree_view: TreeViewState,
+    preferences: PreferencesState,
+) {
+    if preferences.borrow().show_canonical_hash {
+        tree_view::build_tree_from_text(json_editor, tree_view);
+    } else {
+        status_bar.hash_label.hide();
+    }
+}
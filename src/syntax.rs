@@ -0,0 +1,102 @@
+// Syntax highlighting for the editor, using the same tokenizer as lenient parsing
+use crate::json_parser::{self, TokenKind};
+use crate::theme::Palette;
+use gtk::pango::Style;
+use gtk::prelude::*;
+use gtk::{TextBuffer, TextTag};
+
+// Highlighting more than this is too slow to redo after every pause in typing
+const MAX_HIGHLIGHT_CHARS: i32 = 200_000;
+
+const TAGS: [&str; 6] = [
+    "syntax-key",
+    "syntax-string",
+    "syntax-number",
+    "syntax-literal",
+    "syntax-punctuation",
+    "syntax-comment",
+];
+
+// Created before any other tags, so that e.g. search matches are drawn on top
+pub fn create_tags(buffer: &TextBuffer) {
+    let tag_table = buffer.tag_table().unwrap();
+    for name in TAGS {
+        let tag = TextTag::builder().name(name).build();
+        if name == "syntax-comment" {
+            tag.set_style(Style::Italic);
+        }
+        tag_table.add(&tag);
+    }
+}
+
+pub fn set_palette(buffer: &TextBuffer, palette: &Palette) {
+    let tag_table = buffer.tag_table().unwrap();
+    let colours = [
+        palette.key,
+        palette.string,
+        palette.number,
+        palette.literal,
+        palette.punctuation,
+        palette.comment,
+    ];
+    for (name, colour) in TAGS.iter().zip(colours) {
+        if let Some(tag) = tag_table.lookup(name) {
+            tag.set_foreground(Some(colour));
+        }
+    }
+}
+
+pub fn highlight(buffer: &TextBuffer) {
+    let (start, end) = buffer.bounds();
+    let remove_tags = || {
+        for name in TAGS {
+            buffer.remove_tag_by_name(name, &start, &end);
+        }
+    };
+    if buffer.char_count() > MAX_HIGHLIGHT_CHARS {
+        remove_tags();
+        return;
+    }
+
+    let text = buffer.text(&start, &end, true).unwrap();
+    // Half-typed text is left as it was last highlighted
+    let Ok(tokens) = json_parser::tokenize(text.as_str()) else {
+        return;
+    };
+    remove_tags();
+
+    // Spans are in bytes but the buffer counts characters, so count as we go
+    let mut byte_offset = 0;
+    let mut char_offset = 0;
+    let mut char_offset_at = |byte: usize| {
+        char_offset += text[byte_offset..byte].chars().count() as i32;
+        byte_offset = byte;
+        char_offset
+    };
+
+    for (i, token) in tokens.iter().enumerate() {
+        let is_key = || {
+            tokens[i + 1..]
+                .iter()
+                .find(|next| !next.kind.is_trivia())
+                .is_some_and(|next| next.kind == TokenKind::Colon)
+        };
+        let tag = match token.kind {
+            TokenKind::String | TokenKind::Ident if is_key() => "syntax-key",
+            TokenKind::String => "syntax-string",
+            TokenKind::Number => "syntax-number",
+            TokenKind::Ident => "syntax-literal",
+            TokenKind::LineComment | TokenKind::BlockComment => "syntax-comment",
+            TokenKind::Whitespace => continue,
+            _ => "syntax-punctuation",
+        };
+
+        let start = char_offset_at(token.span.start);
+        let end = char_offset_at(token.span.end);
+        buffer.apply_tag_by_name(
+            tag,
+            &buffer.iter_at_offset(start),
+            &buffer.iter_at_offset(end),
+        );
+    }
+}
@@ -0,0 +1,138 @@
+// Light and dark colours for the editor's syntax highlighting and the tree, following the
+// desktop's dark preference unless one is chosen, plus an optional user CSS file
+use crate::json_editor::JsonEditorState;
+use crate::preferences::{Preferences, PreferencesState};
+use crate::tree_view::TreeViewState;
+use crate::{json_editor, tree_view};
+use gtk::gdk::Screen;
+use gtk::prelude::*;
+use gtk::{CssProvider, Settings, StyleContext, STYLE_PROVIDER_PRIORITY_USER};
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
+pub enum ThemeChoice {
+    // Dark when the GTK theme is, or the desktop prefers dark applications
+    #[default]
+    System,
+    Light,
+    Dark,
+}
+
+pub struct Palette {
+    pub key: &'static str,
+    pub string: &'static str,
+    pub number: &'static str,
+    // true, false and null
+    pub literal: &'static str,
+    pub punctuation: &'static str,
+    pub comment: &'static str,
+    // Tree values without a colour of their own, i.e. null and container summaries
+    pub muted: &'static str,
+}
+
+// Tango colours, darker shades on a light background and lighter ones on a dark background
+pub const LIGHT: Palette = Palette {
+    key: "#204a87",
+    string: "#4e9a06",
+    number: "#3465a4",
+    literal: "#75507b",
+    punctuation: "#555753",
+    comment: "#8f5902",
+    muted: "#888a85",
+};
+
+pub const DARK: Palette = Palette {
+    key: "#fcaf3e",
+    string: "#8ae234",
+    number: "#729fcf",
+    literal: "#ad7fa8",
+    punctuation: "#d3d7cf",
+    comment: "#c4a000",
+    muted: "#babdb6",
+};
+
+#[derive(Clone)]
+pub struct ThemeState {
+    user_css: CssProvider,
+    // Whether the desktop asked for dark applications before any choice was applied
+    system_prefers_dark: bool,
+}
+
+pub fn factory_theme() -> ThemeState {
+    let user_css = CssProvider::new();
+    if let Some(screen) = Screen::default() {
+        StyleContext::add_provider_for_screen(&screen, &user_css, STYLE_PROVIDER_PRIORITY_USER);
+    }
+
+    ThemeState {
+        user_css,
+        system_prefers_dark: Settings::default()
+            .is_some_and(|settings| settings.is_gtk_application_prefer_dark_theme()),
+    }
+}
+
+fn is_dark(theme: &ThemeState, choice: ThemeChoice) -> bool {
+    match choice {
+        ThemeChoice::Light => false,
+        ThemeChoice::Dark => true,
+        ThemeChoice::System => {
+            theme.system_prefers_dark
+                || Settings::default()
+                    .and_then(|settings| settings.gtk_theme_name())
+                    .is_some_and(|name| name.to_lowercase().contains("dark"))
+        }
+    }
+}
+
+// Apply the preferred theme, returning why the user CSS file couldn't be loaded, if it
+// couldn't
+pub fn apply_theme(
+    theme: ThemeState,
+    json_editor: JsonEditorState,
+    tree_view: TreeViewState,
+    preferences: &Preferences,
+) -> Result<(), String> {
+    let dark = is_dark(&theme, preferences.theme);
+    if let Some(settings) = Settings::default() {
+        settings.set_gtk_application_prefer_dark_theme(match preferences.theme {
+            ThemeChoice::System => theme.system_prefers_dark,
+            _ => dark,
+        });
+    }
+
+    let palette = if dark { &DARK } else { &LIGHT };
+    json_editor::set_palette(json_editor, palette);
+    tree_view::set_palette(tree_view, palette);
+
+    match &preferences.user_css_path {
+        Some(path) => theme
+            .user_css
+            .load_from_path(&path.to_string_lossy())
+            .map_err(|e| e.to_string()),
+        None => theme
+            .user_css
+            .load_from_data(b"")
+            .map_err(|e| e.to_string()),
+    }
+}
+
+pub fn attach_listeners(
+    theme: ThemeState,
+    json_editor: JsonEditorState,
+    tree_view: TreeViewState,
+    preferences: PreferencesState,
+) {
+    // Follow the desktop switching between light and dark themes
+    if let Some(settings) = Settings::default() {
+        settings.connect_gtk_theme_name_notify(move |_| {
+            if preferences.borrow().theme == ThemeChoice::System {
+                let _ = apply_theme(
+                    theme.clone(),
+                    json_editor.clone(),
+                    tree_view.clone(),
+                    &preferences.borrow(),
+                );
+            }
+        });
+    }
+}
@@ -1,16 +1,13 @@
+use crate::actions::add_action;
+use crate::json_parser::ParseMode;
+use crate::preferences::PreferencesState;
 use crate::tree_view::TreeViewState;
-use crate::{json_editor, tree_view};
+use crate::{actions, json_editor, preferences, tree_view};
 use gtk::prelude::*;
-use gtk::{ToolButton, Toolbar};
+use gtk::{ToggleToolButton, ToolButton, Toolbar};
 
 pub struct ToolBarState {
     pub toolbar: Toolbar,
-    pretty_button: ToolButton,
-    minify_button: ToolButton,
-    remove_double_newlines: ToolButton,
-    unescape_json_string: ToolButton,
-    escape_json_string: ToolButton,
-    toggle_tree_button: ToolButton,
 }
 
 pub fn factory_tool_bar() -> ToolBarState {
@@ -18,6 +15,7 @@ pub fn factory_tool_bar() -> ToolBarState {
 
     let pretty_button = ToolButton::builder()
         .visible(true)
+        .action_name("app.pretty")
         .label("Pretty")
         .tooltip_text("Format this JSON to be human-readable")
         .is_important(true)
@@ -28,6 +26,7 @@ pub fn factory_tool_bar() -> ToolBarState {
 
     let minify_button = ToolButton::builder()
         .visible(true)
+        .action_name("app.minify")
         .label("Minify")
         .tooltip_text("Remove extra spaces and newlines to compact space")
         .is_important(true)
@@ -38,6 +37,7 @@ pub fn factory_tool_bar() -> ToolBarState {
 
     let remove_double_newlines = ToolButton::builder()
         .visible(true)
+        .action_name("app.remove-double-newlines")
         .label("Remove \\n\\n")
         .tooltip_text("Remove double-newlines, i.e. \\n\\n")
         .is_important(true)
@@ -48,6 +48,7 @@ pub fn factory_tool_bar() -> ToolBarState {
 
     let unescape_json_string = ToolButton::builder()
         .visible(true)
+        .action_name("app.unescape")
         .label("Unescape")
         .tooltip_text("Remove backslashes from escaped characters")
         .is_important(true)
@@ -58,6 +59,7 @@ pub fn factory_tool_bar() -> ToolBarState {
 
     let escape_json_string = ToolButton::builder()
         .visible(true)
+        .action_name("app.escape")
         .label("Escape")
         .tooltip_text("Escape current text into a JSON string (adds quotes and backslashes)")
         .is_important(true)
@@ -66,8 +68,33 @@ pub fn factory_tool_bar() -> ToolBarState {
         .build();
     toolbar.add(&escape_json_string);
 
+    let lenient_toggle = ToggleToolButton::builder()
+        .visible(true)
+        .action_name("app.lenient")
+        .label("Lenient")
+        .tooltip_text(
+            "Accept JSON5/JSONC: comments, trailing commas, single quotes and unquoted keys",
+        )
+        .is_important(true)
+        .use_underline(true)
+        .icon_name("document-edit-symbolic")
+        .build();
+    toolbar.add(&lenient_toggle);
+
+    let normalize_button = ToolButton::builder()
+        .visible(true)
+        .action_name("app.normalize")
+        .label("Normalize")
+        .tooltip_text("Rewrite JSON5/JSONC as strict JSON")
+        .is_important(true)
+        .use_underline(true)
+        .icon_name("edit-clear-all-symbolic")
+        .build();
+    toolbar.add(&normalize_button);
+
     let toggle_tree_button = ToolButton::builder()
         .visible(true)
+        .action_name("app.toggle-tree")
         .label("Toggle Tree")
         .tooltip_text("Show/Hide the tree view panel")
         .is_important(true)
@@ -76,54 +103,75 @@ pub fn factory_tool_bar() -> ToolBarState {
         .build();
     toolbar.add(&toggle_tree_button);
 
-    ToolBarState {
-        toolbar,
-        pretty_button,
-        minify_button,
-        remove_double_newlines,
-        unescape_json_string,
-        escape_json_string,
-        toggle_tree_button,
-    }
+    ToolBarState { toolbar }
 }
 
+// Buttons trigger the application actions of the same name
 pub fn attach_listeners(
-    tool_bar: &ToolBarState,
     win: &gtk::ApplicationWindow,
     json_editor: json_editor::JsonEditorState,
     tree_view: TreeViewState,
+    preferences: PreferencesState,
 ) {
-    tool_bar.pretty_button.connect_clicked({
+    let app = win.application().unwrap();
+
+    add_action(&app, "pretty", {
         let win = win.clone();
         let json_editor = json_editor.clone();
-        move |_| json_editor::prettify_json_action(win.clone(), json_editor.clone())
+        let preferences = preferences.clone();
+        move || {
+            json_editor::prettify_json_action(win.clone(), json_editor.clone(), preferences.clone())
+        }
     });
 
-    tool_bar.minify_button.connect_clicked({
+    add_action(&app, "minify", {
         let win = win.clone();
         let json_editor = json_editor.clone();
-        move |_| json_editor::minify_json_action(win.clone(), json_editor.clone())
+        move || json_editor::minify_json_action(win.clone(), json_editor.clone())
     });
 
-    tool_bar.remove_double_newlines.connect_clicked({
+    add_action(&app, "remove-double-newlines", {
         let json_editor = json_editor.clone();
-        move |_| json_editor::remove_double_newline_action(json_editor.clone())
+        move || json_editor::remove_double_newline_action(json_editor.clone())
     });
 
-    tool_bar.unescape_json_string.connect_clicked({
+    add_action(&app, "unescape", {
         let win = win.clone();
         let json_editor = json_editor.clone();
-        move |_| json_editor::unescape_json_action(win.clone(), json_editor.clone())
+        move || json_editor::unescape_json_action(win.clone(), json_editor.clone())
     });
 
-    tool_bar.escape_json_string.connect_clicked({
+    add_action(&app, "escape", {
         let json_editor = json_editor.clone();
-        move |_| json_editor::escape_json_action(json_editor.clone())
+        move || json_editor::escape_json_action(json_editor.clone())
     });
 
-    tool_bar.toggle_tree_button.connect_clicked({
+    let default_parse_mode = preferences.borrow().default_parse_mode;
+    actions::add_toggle_action(&app, "lenient", default_parse_mode == ParseMode::Lenient, {
         let json_editor = json_editor.clone();
         let tree_view = tree_view.clone();
-        move |_| tree_view::toggle_tree_view_visibility(json_editor.clone(), tree_view.clone())
+        move |lenient| {
+            let parse_mode = if lenient {
+                ParseMode::Lenient
+            } else {
+                ParseMode::Strict
+            };
+            json_editor::set_parse_mode(json_editor.clone(), parse_mode);
+            tree_view::build_tree_from_text(json_editor.clone(), tree_view.clone());
+        }
+    });
+    // The initial state doesn't go through the change handler
+    json_editor::set_parse_mode(json_editor.clone(), default_parse_mode);
+
+    add_action(&app, "normalize", {
+        let win = win.clone();
+        let json_editor = json_editor.clone();
+        move || json_editor::normalize_json_action(win.clone(), json_editor.clone())
+    });
+
+    add_action(&app, "toggle-tree", move || {
+        tree_view::toggle_tree_view_visibility(json_editor.clone(), tree_view.clone());
+        preferences.borrow_mut().show_tree = tree_view::is_visible(tree_view.clone());
+        let _ = preferences::save(&preferences.borrow());
     });
 }
@@ -1,22 +1,98 @@
-use crate::json_editor;
-use gtk::glib::Value;
+use crate::parse_summary::ParseSummary;
+use crate::theme::{self, Palette};
+use crate::{json_editor, json_pointer, large_view, parse_summary, sort_keys};
+use gtk::glib::{Propagation, SourceId, Value};
+use gtk::pango::{EllipsizeMode, Style};
 use gtk::prelude::*;
+use gtk::{gio, glib};
 use gtk::{
-    Align, CellRendererText, CssProvider, Justification, Label, Orientation, Overlay, PolicyType,
-    ScrolledWindow, TreePath, TreeStore, TreeView, TreeViewColumn,
-    STYLE_PROVIDER_PRIORITY_APPLICATION,
+    Align, ApplicationWindow, CellRendererPixbuf, CellRendererText, CssProvider, Entry,
+    Justification, Label, Orientation, Overlay, PolicyType, ResponseType, ScrolledWindow,
+    SortColumn, SpinButton, TreeIter, TreeModel, TreeModelFilter, TreeModelSort, TreePath,
+    TreeStore, TreeView, TreeViewColumn, WindowPosition, STYLE_PROVIDER_PRIORITY_APPLICATION,
 };
+use regex::Regex;
 use serde_json::Value as JsonValue;
-use std::cell::Cell;
+use std::cell::{Cell, RefCell};
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
 use std::rc::Rc;
+use std::sync::atomic::{AtomicU64, Ordering as AtomicOrdering};
+use std::sync::Arc;
+use std::time::Duration;
+
+// Columns of the TreeStore
+const COL_KEY: u32 = 0;
+const COL_VALUE: u32 = 1;
+const COL_POINTER: u32 = 2;
+const COL_EDITABLE: u32 = 3;
+const COL_VISIBLE: u32 = 4;
+const COL_MATCH: u32 = 5;
+const COL_LOADED: u32 = 6;
+const COL_TYPE: u32 = 7;
+// The value as displayed, which is truncated for long strings
+const COL_DISPLAY: u32 = 8;
+// Markup for the tooltip showing a truncated value in full
+const COL_TOOLTIP: u32 = 9;
+
+// Longer strings are truncated in the tree, and shown in full in a tooltip
+const MAX_DISPLAY_CHARS: usize = 120;
+const MAX_TOOLTIP_CHARS: usize = 4000;
+
+// How long to wait for typing to pause before re-parsing
+const PARSE_DELAY: Duration = Duration::from_millis(150);
+const LARGE_DOCUMENT_PARSE_DELAY: Duration = Duration::from_millis(500);
+
+// Shows only rows whose key (or scalar value) matches, along with their ancestors
+#[derive(Clone)]
+pub struct TreeFilter {
+    pub regex: Regex,
+    pub match_keys: bool,
+}
 
 #[derive(Clone)]
 pub struct TreeViewState {
     pub overlay: Overlay,
     pub tree_view: TreeView,
     pub invalid_overlay: gtk::Box,
+    value_cell: CellRendererText,
     visible: Rc<Cell<bool>>,
     model: TreeStore,
+    filter_model: TreeModelFilter,
+    sort_model: TreeModelSort,
+    filter: Rc<RefCell<Option<TreeFilter>>>,
+    // The parsed document, which rows are loaded from lazily as they are expanded
+    document: Rc<RefCell<Option<JsonValue>>>,
+    // Incremented on every change, so that results from outdated parses are discarded
+    parse_generation: Arc<AtomicU64>,
+    pending_parse: Rc<RefCell<Option<SourceId>>>,
+    // JSON Pointers of the expanded rows, so they can be expanded again when recreated
+    expanded: Rc<RefCell<HashSet<String>>>,
+    tracking_expansion: Rc<Cell<bool>>,
+    // Row to select once the next parse has finished, e.g. when restoring a session
+    pending_selection: Rc<RefCell<Option<String>>>,
+    palette: Rc<Cell<&'static Palette>>,
+    // Called after every parse, whether or not the tree is shown
+    parse_listeners: Rc<RefCell<Vec<ParseListener>>>,
+}
+
+// Given the parsed value too, if the text was valid
+type ParseListener = Box<dyn Fn(Rc<ParseSummary>, Option<&JsonValue>)>;
+
+pub fn connect_parsed<F: Fn(Rc<ParseSummary>, Option<&JsonValue>) + 'static>(
+    tree_view: TreeViewState,
+    f: F,
+) {
+    tree_view.parse_listeners.borrow_mut().push(Box::new(f));
+}
+
+pub fn set_palette(tree_view: TreeViewState, palette: &'static Palette) {
+    tree_view.palette.set(palette);
+    tree_view.tree_view.queue_draw();
+}
+
+pub fn is_visible(tree_view: TreeViewState) -> bool {
+    tree_view.visible.get()
 }
 
 pub fn toggle_tree_view_visibility(
@@ -35,69 +111,319 @@ pub fn toggle_tree_view_visibility(
     }
 }
 
-fn append_json_value(model: &TreeStore, parent: Option<&gtk::TreeIter>, key: &str, v: &JsonValue) {
+// Adds a row for the value. Children of arrays and objects are only added once the row is
+// expanded (see load_children), with a placeholder row until then so it can be expanded.
+fn append_json_value(
+    model: &TreeStore,
+    parent: Option<&gtk::TreeIter>,
+    key: &str,
+    pointer: &str,
+    v: &JsonValue,
+) {
+    let iter = model.append(parent);
+    model.set_value(&iter, COL_VISIBLE, &Value::from(true));
+    model.set_value(&iter, COL_MATCH, &Value::from(false));
+    model.set_value(&iter, COL_LOADED, &Value::from(false));
+    update_json_value(model, &iter, key, pointer, v);
+}
+
+// Updates a row to show a new value. Child rows that still exist are kept rather than
+// recreated, so that their expansion and selection survive edits.
+fn update_json_value(model: &TreeStore, iter: &TreeIter, key: &str, pointer: &str, v: &JsonValue) {
+    set_string_if_changed(model, iter, COL_KEY, key);
+    set_string_if_changed(model, iter, COL_POINTER, pointer);
+    // Only scalar values can be edited in place
+    set_bool_if_changed(model, iter, COL_EDITABLE, !v.is_object() && !v.is_array());
+
+    let (value, value_type) = describe_value(v);
+    set_string_if_changed(model, iter, COL_TYPE, value_type);
+
+    let display = truncate_for_display(&value);
+    let tooltip = if display == value {
+        String::new()
+    } else {
+        let full: String = value.chars().take(MAX_TOOLTIP_CHARS).collect();
+        glib::markup_escape_text(&full).to_string()
+    };
+    set_string_if_changed(model, iter, COL_DISPLAY, &display);
+    set_string_if_changed(model, iter, COL_TOOLTIP, &tooltip);
+    set_string_if_changed(model, iter, COL_VALUE, &value);
+
+    let children = child_entries(pointer, v);
+    if children.is_empty() {
+        while let Some(child) = model.iter_children(Some(iter)) {
+            model.remove(&child);
+        }
+        set_bool_if_changed(model, iter, COL_LOADED, true);
+    } else if model.value(iter, COL_LOADED as i32).get::<bool>().unwrap() {
+        update_children(model, iter, children);
+    } else if model.iter_children(Some(iter)).is_none() {
+        let placeholder = model.append(Some(iter));
+        model.set_value(&placeholder, COL_VISIBLE, &Value::from(true));
+    }
+}
+
+// Value as shown in the Value column, with a count of children for arrays and objects, and
+// its type as shown in the Type column
+pub fn describe_value(v: &JsonValue) -> (String, &'static str) {
+    match v {
+        JsonValue::Object(map) => (count_label(map.len(), "{", "key", "keys", "}"), "object"),
+        JsonValue::Array(arr) => (count_label(arr.len(), "[", "item", "items", "]"), "array"),
+        JsonValue::String(s) => (s.clone(), "string"),
+        JsonValue::Number(n) => (n.to_string(), "number"),
+        JsonValue::Bool(b) => (b.to_string(), "bool"),
+        JsonValue::Null => ("null".to_string(), "null"),
+    }
+}
+
+fn count_label(count: usize, open: &str, singular: &str, plural: &str, close: &str) -> String {
+    let noun = if count == 1 { singular } else { plural };
+    format!("{}{} {}{}", open, count, noun, close)
+}
+
+// Only the first line of long or multi-line strings is shown
+pub fn truncate_for_display(value: &str) -> String {
+    let first_line = value.lines().next().unwrap_or_default();
+    let truncated: String = first_line.chars().take(MAX_DISPLAY_CHARS).collect();
+    if truncated.len() < value.len() {
+        format!("{}…", truncated)
+    } else {
+        truncated
+    }
+}
+
+// Colour used for the value and type of each type of value
+fn type_colour(palette: &Palette, value_type: &str) -> Option<&'static str> {
+    match value_type {
+        "string" => Some(palette.string),
+        "number" => Some(palette.number),
+        "bool" => Some(palette.literal),
+        "null" | "object" | "array" => Some(palette.muted),
+        _ => None,
+    }
+}
+
+pub fn type_icon(value_type: &str) -> Option<&'static str> {
+    match value_type {
+        "object" => Some("folder-symbolic"),
+        "array" => Some("view-list-symbolic"),
+        "string" => Some("insert-text-symbolic"),
+        "number" => Some("accessories-calculator-symbolic"),
+        "bool" => Some("object-select-symbolic"),
+        "null" => Some("action-unavailable-symbolic"),
+        _ => None,
+    }
+}
+
+// Key, pointer and value of each child of an array or object
+fn child_entries<'a>(pointer: &str, v: &'a JsonValue) -> Vec<(String, String, &'a JsonValue)> {
     match v {
-        JsonValue::Object(map) => {
-            let iter = model.append(parent);
-            model.set_value(&iter, 0, &Value::from(key));
-            model.set_value(&iter, 1, &Value::from("{object}"));
-            for (k, val) in map.iter() {
-                append_json_value(model, Some(&iter), k.as_str(), val);
+        JsonValue::Object(map) => map
+            .iter()
+            .map(|(k, val)| (k.clone(), json_pointer::append(pointer, k), val))
+            .collect(),
+        JsonValue::Array(arr) => arr
+            .iter()
+            .enumerate()
+            .map(|(i, val)| {
+                let child_pointer = json_pointer::append(pointer, &i.to_string());
+                (format!("[{}]", i), child_pointer, val)
+            })
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+fn update_children(
+    model: &TreeStore,
+    parent: &TreeIter,
+    children: Vec<(String, String, &JsonValue)>,
+) {
+    // Rows are matched up by key, so inserting or removing a member leaves the rows of
+    // its siblings alone
+    let mut existing: HashMap<String, TreeIter> = HashMap::new();
+    if let Some(child) = model.iter_children(Some(parent)) {
+        loop {
+            if let Ok(key) = model.value(&child, COL_KEY as i32).get::<String>() {
+                existing.entry(key).or_insert(child);
             }
-        }
-        JsonValue::Array(arr) => {
-            let iter = model.append(parent);
-            model.set_value(&iter, 0, &Value::from(key));
-            model.set_value(&iter, 1, &Value::from("[list]"));
-            for (i, val) in arr.iter().enumerate() {
-                let idx_key = format!("[{}]", i);
-                append_json_value(model, Some(&iter), &idx_key, val);
+            if !model.iter_next(&child) {
+                break;
             }
         }
-        JsonValue::String(s) => {
-            let iter = model.append(parent);
-            model.set_value(&iter, 0, &Value::from(key));
-            model.set_value(&iter, 1, &Value::from(s.as_str()));
-        }
-        JsonValue::Number(n) => {
-            let iter = model.append(parent);
-            model.set_value(&iter, 0, &Value::from(key));
-            model.set_value(&iter, 1, &Value::from(n.to_string()));
-        }
-        JsonValue::Bool(b) => {
-            let iter = model.append(parent);
-            model.set_value(&iter, 0, &Value::from(key));
-            model.set_value(&iter, 1, &Value::from(b.to_string()));
+    }
+
+    for (position, (key, pointer, v)) in children.iter().enumerate() {
+        let current = model.iter_nth_child(Some(parent), position as i32);
+        match existing.remove(key) {
+            Some(iter) => {
+                if let Some(current) = current.filter(|c| model.path(c) != model.path(&iter)) {
+                    model.move_before(&iter, Some(&current));
+                }
+                update_json_value(model, &iter, key, pointer, v);
+            }
+            None => {
+                let iter = model.insert(Some(parent), position as i32);
+                model.set_value(&iter, COL_VISIBLE, &Value::from(true));
+                model.set_value(&iter, COL_MATCH, &Value::from(false));
+                model.set_value(&iter, COL_LOADED, &Value::from(false));
+                update_json_value(model, &iter, key, pointer, v);
+            }
         }
-        JsonValue::Null => {
-            let iter = model.append(parent);
-            model.set_value(&iter, 0, &Value::from(key));
-            model.set_value(&iter, 1, &Value::from("null"));
+    }
+
+    // Whatever is left over no longer exists, including any placeholder row
+    while let Some(stale) = model.iter_nth_child(Some(parent), children.len() as i32) {
+        model.remove(&stale);
+    }
+}
+
+// Setting a value emits row-changed even when it is the same, which makes the view redraw
+fn set_string_if_changed(model: &TreeStore, iter: &TreeIter, column: u32, value: &str) {
+    let current = model.value(iter, column as i32).get::<String>().ok();
+    if current.as_deref() != Some(value) {
+        model.set_value(iter, column, &Value::from(value));
+    }
+}
+
+fn set_bool_if_changed(model: &TreeStore, iter: &TreeIter, column: u32, value: bool) {
+    if model.value(iter, column as i32).get::<bool>().unwrap() != value {
+        model.set_value(iter, column, &Value::from(value));
+    }
+}
+
+fn load_children(tree_view: &TreeViewState, iter: &TreeIter) {
+    let model = &tree_view.model;
+    if model.value(iter, COL_LOADED as i32).get::<bool>().unwrap() {
+        return;
+    }
+    model.set_value(iter, COL_LOADED, &Value::from(true));
+
+    let document = tree_view.document.borrow();
+    let pointer = model
+        .value(iter, COL_POINTER as i32)
+        .get::<String>()
+        .unwrap();
+    if let Some(v) = document.as_ref().and_then(|d| d.pointer(&pointer)) {
+        update_children(model, iter, child_entries(&pointer, v));
+    }
+}
+
+// Searching needs every row to be present
+fn load_all_children(tree_view: &TreeViewState, iter: &TreeIter) {
+    load_children(tree_view, iter);
+    if let Some(child) = tree_view.model.iter_children(Some(iter)) {
+        loop {
+            load_all_children(tree_view, &child);
+            if !tree_view.model.iter_next(&child) {
+                break;
+            }
         }
     }
 }
 
 pub fn factory_tree_view() -> TreeViewState {
     let tree_view = TreeView::builder().visible(true).expand(true).build();
+    let palette: Rc<Cell<&'static Palette>> = Rc::new(Cell::new(&theme::LIGHT));
 
-    // Two columns: Key and Value
+    // Three columns: Key, Type and Value
     let column = TreeViewColumn::new();
     column.set_title("Key");
+    column.set_resizable(true);
+    column.set_sort_column_id(COL_KEY as i32);
     let cell = CellRendererText::new();
     gtk::prelude::CellLayoutExt::pack_start(&column, &cell, true);
-    gtk::prelude::TreeViewColumnExt::add_attribute(&column, &cell, "text", 0);
+    gtk::prelude::TreeViewColumnExt::add_attribute(&column, &cell, "text", COL_KEY as i32);
+    tree_view.append_column(&column);
+
+    let column = TreeViewColumn::new();
+    column.set_title("Type");
+    column.set_sort_column_id(COL_TYPE as i32);
+    let icon_cell = CellRendererPixbuf::new();
+    let type_cell = CellRendererText::new();
+    gtk::prelude::CellLayoutExt::pack_start(&column, &icon_cell, false);
+    gtk::prelude::CellLayoutExt::pack_start(&column, &type_cell, true);
+    gtk::prelude::TreeViewColumnExt::add_attribute(&column, &type_cell, "text", COL_TYPE as i32);
+    gtk::prelude::TreeViewColumnExt::set_cell_data_func(
+        &column,
+        &icon_cell,
+        Some(Box::new(|_, cell, model, iter| {
+            let value_type = model.value(iter, COL_TYPE as i32).get::<String>().ok();
+            cell.set_property("icon-name", value_type.as_deref().and_then(type_icon));
+        })),
+    );
     tree_view.append_column(&column);
 
     let column = TreeViewColumn::new();
     column.set_title("Value");
-    let cell = CellRendererText::new();
-    gtk::prelude::CellLayoutExt::pack_start(&column, &cell, true);
-    gtk::prelude::TreeViewColumnExt::add_attribute(&column, &cell, "text", 1);
+    column.set_resizable(true);
+    column.set_sort_column_id(COL_VALUE as i32);
+    let value_cell = CellRendererText::new();
+    value_cell.set_ellipsize(EllipsizeMode::End);
+    gtk::prelude::CellLayoutExt::pack_start(&column, &value_cell, true);
+    gtk::prelude::TreeViewColumnExt::add_attribute(
+        &column,
+        &value_cell,
+        "text",
+        COL_DISPLAY as i32,
+    );
+    gtk::prelude::TreeViewColumnExt::set_cell_data_func(
+        &column,
+        &value_cell,
+        Some(Box::new({
+            let palette = palette.clone();
+            move |_, cell, model, iter| {
+                let value_type = model.value(iter, COL_TYPE as i32).get::<String>().ok();
+                let value_type = value_type.as_deref().unwrap_or_default();
+                cell.set_property("foreground", type_colour(palette.get(), value_type));
+                cell.set_property(
+                    "style",
+                    if value_type == "null" {
+                        Style::Italic
+                    } else {
+                        Style::Normal
+                    },
+                );
+            }
+        })),
+    );
+    gtk::prelude::TreeViewColumnExt::add_attribute(
+        &column,
+        &value_cell,
+        "editable",
+        COL_EDITABLE as i32,
+    );
     tree_view.append_column(&column);
 
-    // TreeStore with two string columns
-    let model = TreeStore::new(&[String::static_type(), String::static_type()]);
-    tree_view.set_model(Some(&model));
+    // TreeStore with the key and full value, plus the (hidden) JSON Pointer of each row,
+    // whether its value can be edited, its state when searching, whether its children have
+    // been added yet, and its type, displayed value and tooltip
+    let model = TreeStore::new(&[
+        String::static_type(),
+        String::static_type(),
+        String::static_type(),
+        bool::static_type(),
+        bool::static_type(),
+        bool::static_type(),
+        bool::static_type(),
+        String::static_type(),
+        String::static_type(),
+        String::static_type(),
+    ]);
+    let filter_model = TreeModelFilter::new(&model, None);
+    filter_model.set_visible_column(COL_VISIBLE as i32);
+
+    // Sorting by a column orders array indices and numbers by their numeric value
+    let sort_model = TreeModelSort::new(&filter_model);
+    sort_model.set_sort_func(SortColumn::Index(COL_KEY), |model, a, b| {
+        compare_strings(model, a, b, COL_KEY)
+    });
+    sort_model.set_sort_func(SortColumn::Index(COL_TYPE), |model, a, b| {
+        compare_strings(model, a, b, COL_TYPE)
+    });
+    sort_model.set_sort_func(SortColumn::Index(COL_VALUE), compare_values);
+    tree_view.set_model(Some(&sort_model));
+    tree_view.set_tooltip_column(COL_TOOLTIP as i32);
     tree_view.set_headers_visible(true);
 
     let invalid_overlay = factory_invalid_overlay();
@@ -118,11 +444,123 @@ pub fn factory_tree_view() -> TreeViewState {
         overlay,
         tree_view,
         invalid_overlay,
+        value_cell,
         visible: Rc::new(Cell::new(true)),
         model,
+        filter_model,
+        sort_model,
+        filter: Rc::new(RefCell::new(None)),
+        document: Rc::new(RefCell::new(None)),
+        parse_generation: Arc::new(AtomicU64::new(0)),
+        pending_parse: Rc::new(RefCell::new(None)),
+        // The root row starts off expanded
+        expanded: Rc::new(RefCell::new(HashSet::from([String::new()]))),
+        tracking_expansion: Rc::new(Cell::new(true)),
+        pending_selection: Rc::new(RefCell::new(None)),
+        palette,
+        parse_listeners: Rc::new(RefCell::new(Vec::new())),
     }
 }
 
+// JSON Pointer of the selected row, if any
+pub fn selected_pointer(tree_view: TreeViewState) -> Option<String> {
+    let (model, iter) = tree_view.tree_view.selection().selected()?;
+    model.value(&iter, COL_POINTER as i32).get::<String>().ok()
+}
+
+// The selected value as pretty-printed JSON, if any
+pub fn selected_json(tree_view: TreeViewState) -> Option<String> {
+    let pointer = selected_pointer(tree_view.clone())?;
+    let document = tree_view.document.borrow();
+    let v = document.as_ref()?.pointer(&pointer)?;
+    serde_json::to_string_pretty(v).ok()
+}
+
+// Value and type of the selected row, if any
+pub fn selected_value(tree_view: TreeViewState) -> Option<(String, String)> {
+    let (model, iter) = tree_view.tree_view.selection().selected()?;
+    let value = model.value(&iter, COL_VALUE as i32).get::<String>().ok()?;
+    let value_type = model.value(&iter, COL_TYPE as i32).get::<String>().ok()?;
+    Some((value, value_type))
+}
+
+pub fn attach_listeners(
+    tree_view: TreeViewState,
+    win: &gtk::ApplicationWindow,
+    json_editor: json_editor::JsonEditorState,
+) {
+    tree_view.tree_view.connect_test_expand_row({
+        let tree_view = tree_view.clone();
+        move |_, iter, _| {
+            load_children(&tree_view, &store_iter(&tree_view, iter));
+            Propagation::Proceed
+        }
+    });
+
+    tree_view.tree_view.connect_row_expanded({
+        let tree_view = tree_view.clone();
+        move |view, iter, _| {
+            if let Some(pointer) = tracked_pointer(&tree_view, view, iter) {
+                tree_view.expanded.borrow_mut().insert(pointer);
+            }
+        }
+    });
+
+    // Collapsing a row forgets the expansion of everything below it too
+    tree_view.tree_view.connect_row_collapsed({
+        let tree_view = tree_view.clone();
+        move |view, iter, _| {
+            if let Some(pointer) = tracked_pointer(&tree_view, view, iter) {
+                let descendants = format!("{}/", pointer);
+                tree_view
+                    .expanded
+                    .borrow_mut()
+                    .retain(|p| *p != pointer && !p.starts_with(&descendants));
+            }
+        }
+    });
+
+    // Edit the full value rather than the truncated one shown
+    tree_view.value_cell.connect_editing_started({
+        let view = tree_view.tree_view.clone();
+        move |_, editable, path| {
+            let (Some(model), Some(entry)) = (view.model(), editable.downcast_ref::<Entry>())
+            else {
+                return;
+            };
+            if let Some(iter) = model.iter(&path) {
+                let value = model
+                    .value(&iter, COL_VALUE as i32)
+                    .get::<String>()
+                    .unwrap();
+                entry.set_text(&value);
+            }
+        }
+    });
+
+    tree_view.value_cell.connect_edited({
+        let win = win.clone();
+        let view = tree_view.tree_view.clone();
+        move |_, path, new_text| {
+            let Some(model) = view.model() else {
+                return;
+            };
+            if let Some(iter) = model.iter(&path) {
+                let pointer = model
+                    .value(&iter, COL_POINTER as i32)
+                    .get::<String>()
+                    .unwrap();
+                json_editor::edit_value_action(
+                    win.clone(),
+                    json_editor.clone(),
+                    &pointer,
+                    new_text,
+                );
+            }
+        }
+    });
+}
+
 fn factory_invalid_overlay() -> gtk::Box {
     // Translucent grey overlay message for invalid JSON
     let invalid_overlay = gtk::Box::new(Orientation::Vertical, 8);
@@ -167,29 +605,95 @@ fn factory_invalid_overlay() -> gtk::Box {
     invalid_overlay
 }
 
+// Re-parse the text on a worker thread once typing pauses, then bring the tree up to date
 pub fn build_tree_from_text(json_editor: json_editor::JsonEditorState, tree_view: TreeViewState) {
-    if (tree_view.visible.get()) == false {
-        return;
+    let generation = tree_view
+        .parse_generation
+        .fetch_add(1, AtomicOrdering::SeqCst)
+        + 1;
+    if let Some(pending_parse) = tree_view.pending_parse.take() {
+        pending_parse.remove();
     }
 
-    let buffer = json_editor::retrieve_buffer(json_editor);
-    let (start, end) = buffer.bounds();
-    let text = buffer.text(&start, &end, true).unwrap();
+    let delay = if json_editor::is_large_document(json_editor.clone()) {
+        LARGE_DOCUMENT_PARSE_DELAY
+    } else {
+        PARSE_DELAY
+    };
+
+    let source_id = glib::timeout_add_local_once(delay, {
+        let tree_view = tree_view.clone();
+        move || {
+            tree_view.pending_parse.take();
 
-    let parsed: Result<JsonValue, _> = serde_json::from_str(text.as_str());
+            let buffer = json_editor::retrieve_buffer(json_editor.clone());
+            let (start, end) = buffer.bounds();
+            let text = buffer.text(&start, &end, true).unwrap().to_string();
+            let parse_mode = json_editor::parse_mode(json_editor.clone());
+            let is_large = json_editor::is_large_document(json_editor.clone());
+            let parse_generation = tree_view.parse_generation.clone();
+            let parsing = gio::spawn_blocking(move || {
+                // Skip parses that were superseded while waiting for a worker
+                if parse_generation.load(AtomicOrdering::SeqCst) != generation {
+                    return None;
+                }
+                let (parsed, summary) = parse_summary::parse(&text, parse_mode);
+                // Large documents are shown from this copy of the text
+                let large_text = is_large.then(|| {
+                    let rows = large_view::index_rows(&text);
+                    (text, rows)
+                });
+                Some((parsed, summary, large_text))
+            });
+
+            glib::MainContext::default().spawn_local(async move {
+                let Ok(Some((parsed, summary, large_text))) = parsing.await else {
+                    return;
+                };
+                // The text has changed again since this parse started
+                if tree_view.parse_generation.load(AtomicOrdering::SeqCst) != generation {
+                    return;
+                }
+                if let Some((text, rows)) = large_text {
+                    json_editor::show_large_text(json_editor, text, rows);
+                }
+                let summary = Rc::new(summary);
+                for listener in tree_view.parse_listeners.borrow().iter() {
+                    listener(summary.clone(), parsed.as_ref().ok());
+                }
+                if tree_view.visible.get() {
+                    show_parsed(&tree_view, parsed);
+                }
+            });
+        }
+    });
+    tree_view.pending_parse.replace(Some(source_id));
+}
+
+fn show_parsed(tree_view: &TreeViewState, parsed: Result<JsonValue, String>) {
     match parsed {
         Ok(v) => {
-            // Valid JSON: hide overlay and populate tree
+            // Valid JSON: hide overlay and update the tree in place
             tree_view.invalid_overlay.hide();
-            tree_view.model.clear();
-            append_json_value(&tree_view.model, None, "🔥", &v);
+            match tree_view.model.iter_first() {
+                Some(root) => update_json_value(&tree_view.model, &root, "🔥", "", &v),
+                None => append_json_value(&tree_view.model, None, "🔥", "", &v),
+            }
+            tree_view.document.replace(Some(v));
 
-            // Expand the root node (first top-level row) by default
-            let path = TreePath::new_first();
-            tree_view.tree_view.expand_row(&path, false);
+            if apply_filter(tree_view).is_some() {
+                tree_view.tree_view.expand_all();
+            } else {
+                // Rows that were recreated, e.g. after a key was retyped, are expanded again
+                restore_expansion(tree_view);
+            }
+
+            if let Some(pointer) = tree_view.pending_selection.take() {
+                select_pointer(tree_view.clone(), &pointer);
+            }
         }
         Err(e) => {
-            // Invalid JSON: clear tree, show overlay with message
+            // Invalid JSON: keep the last good tree, and show overlay with message
             if let Some(label) = tree_view
                 .invalid_overlay
                 .children()
@@ -202,3 +706,341 @@ pub fn build_tree_from_text(json_editor: json_editor::JsonEditorState, tree_view
         }
     }
 }
+
+// Rows in the view go through the search filter and then the column sorting
+fn store_iter(tree_view: &TreeViewState, view_iter: &TreeIter) -> TreeIter {
+    let filter_iter = tree_view.sort_model.convert_iter_to_child_iter(view_iter);
+    tree_view
+        .filter_model
+        .convert_iter_to_child_iter(&filter_iter)
+}
+
+fn view_path(tree_view: &TreeViewState, store_iter: &TreeIter) -> Option<TreePath> {
+    let filter_iter = tree_view
+        .filter_model
+        .convert_child_iter_to_iter(store_iter)?;
+    let sort_iter = tree_view
+        .sort_model
+        .convert_child_iter_to_iter(&filter_iter)?;
+    tree_view.sort_model.path(&sort_iter)
+}
+
+fn compare_strings(model: &TreeModel, a: &TreeIter, b: &TreeIter, column: u32) -> Ordering {
+    let a = model
+        .value(a, column as i32)
+        .get::<String>()
+        .unwrap_or_default();
+    let b = model
+        .value(b, column as i32)
+        .get::<String>()
+        .unwrap_or_default();
+    sort_keys::natural_cmp(&a, &b)
+}
+
+// Numbers compare by value, everything else as text
+fn compare_values(model: &TreeModel, a: &TreeIter, b: &TreeIter) -> Ordering {
+    let number = |iter: &TreeIter| {
+        let value_type = model.value(iter, COL_TYPE as i32).get::<String>().ok()?;
+        if value_type != "number" {
+            return None;
+        }
+        let value = model.value(iter, COL_VALUE as i32).get::<String>().ok()?;
+        value.parse::<f64>().ok()
+    };
+
+    match (number(a), number(b)) {
+        (Some(a), Some(b)) => a.total_cmp(&b),
+        _ => compare_strings(model, a, b, COL_VALUE),
+    }
+}
+
+// Pointer of a row being expanded or collapsed by the user, as opposed to by search
+fn tracked_pointer(tree_view: &TreeViewState, view: &TreeView, iter: &TreeIter) -> Option<String> {
+    if !tree_view.tracking_expansion.get() || tree_view.filter.borrow().is_some() {
+        return None;
+    }
+    view.model()?
+        .value(iter, COL_POINTER as i32)
+        .get::<String>()
+        .ok()
+}
+
+// Find the row for a JSON Pointer, loading rows on the way as needed
+fn find_row(tree_view: &TreeViewState, pointer: &str) -> Option<TreeIter> {
+    let model = &tree_view.model;
+    let mut iter = model.iter_first()?;
+    let mut current = String::new();
+
+    for segment in json_pointer::segments(pointer) {
+        load_children(tree_view, &iter);
+        current = json_pointer::append(&current, &segment);
+
+        let child = model.iter_children(Some(&iter))?;
+        loop {
+            if model.value(&child, COL_POINTER as i32).get::<String>().ok() == Some(current.clone())
+            {
+                break;
+            }
+            if !model.iter_next(&child) {
+                return None;
+            }
+        }
+        iter = child;
+    }
+    Some(iter)
+}
+
+fn restore_expansion(tree_view: &TreeViewState) {
+    let mut pointers: Vec<String> = tree_view.expanded.borrow().iter().cloned().collect();
+    // Parents have to be expanded before their children
+    pointers.sort_by_key(String::len);
+
+    for pointer in pointers {
+        let path = find_row(tree_view, &pointer).and_then(|iter| view_path(tree_view, &iter));
+        if let Some(path) = path {
+            tree_view.tree_view.expand_row(&path, false);
+        }
+    }
+}
+
+// Select the row for a JSON Pointer, expanding its ancestors and scrolling to it
+pub fn select_pointer(tree_view: TreeViewState, pointer: &str) -> bool {
+    let Some(path) = find_row(&tree_view, pointer).and_then(|iter| view_path(&tree_view, &iter))
+    else {
+        return false;
+    };
+    tree_view.tree_view.expand_to_path(&path);
+    tree_view.tree_view.selection().select_path(&path);
+    tree_view
+        .tree_view
+        .scroll_to_cell(Some(&path), None::<&TreeViewColumn>, true, 0.5, 0.0);
+    true
+}
+
+// Select the row for a JSON Pointer, showing the tree first if it is hidden. A hidden tree
+// is rebuilt, so the row is selected once that finishes and assumed to exist.
+pub fn reveal_pointer(
+    json_editor: json_editor::JsonEditorState,
+    tree_view: TreeViewState,
+    pointer: &str,
+) -> bool {
+    if tree_view.visible.get() {
+        return select_pointer(tree_view, pointer);
+    }
+    tree_view
+        .pending_selection
+        .replace(Some(pointer.to_string()));
+    toggle_tree_view_visibility(json_editor, tree_view);
+    true
+}
+
+// JSON Pointers of the expanded rows, parents first
+pub fn expanded_pointers(tree_view: TreeViewState) -> Vec<String> {
+    let mut pointers: Vec<String> = tree_view.expanded.borrow().iter().cloned().collect();
+    pointers.sort();
+    pointers
+}
+
+// Expand the row for a JSON Pointer once the document has next been parsed, e.g. when a
+// value is about to be replaced with an object
+pub fn expand_when_parsed(tree_view: TreeViewState, pointer: &str) {
+    tree_view.expanded.borrow_mut().insert(pointer.to_string());
+}
+
+// Expand and select these rows once the document has been parsed
+pub fn restore_state(tree_view: TreeViewState, expanded: Vec<String>, selected: Option<String>) {
+    tree_view.expanded.replace(expanded.into_iter().collect());
+    tree_view.pending_selection.replace(selected);
+}
+
+pub fn expand_all_action(tree_view: TreeViewState) {
+    if let Some(root) = tree_view.model.iter_first() {
+        load_all_children(&tree_view, &root);
+    }
+    tree_view.tree_view.expand_all();
+}
+
+pub fn collapse_all_action(tree_view: TreeViewState) {
+    tree_view.tree_view.collapse_all();
+}
+
+// Expand everything down to the given depth, the root being at depth 0
+fn expand_to_depth(tree_view: &TreeViewState, iter: &TreeIter, depth: u32) {
+    if depth == 0 {
+        return;
+    }
+    load_children(tree_view, iter);
+    if let Some(path) = view_path(tree_view, iter) {
+        tree_view.tree_view.expand_row(&path, false);
+    }
+
+    if let Some(child) = tree_view.model.iter_children(Some(iter)) {
+        loop {
+            expand_to_depth(tree_view, &child, depth - 1);
+            if !tree_view.model.iter_next(&child) {
+                break;
+            }
+        }
+    }
+}
+
+pub fn expand_to_depth_action(win: ApplicationWindow, tree_view: TreeViewState) {
+    let dialog = gtk::Dialog::builder()
+        .transient_for(&win)
+        .window_position(WindowPosition::CenterOnParent)
+        .title("Expand to Depth")
+        .modal(true)
+        .build();
+    dialog.add_buttons(&[
+        ("Cancel", ResponseType::Cancel),
+        ("Expand", ResponseType::Ok),
+    ]);
+    dialog.set_default_response(ResponseType::Ok);
+
+    let depth = SpinButton::with_range(1.0, 50.0, 1.0);
+    depth.set_value(2.0);
+    depth.set_activates_default(true);
+
+    let h_box = gtk::Box::builder()
+        .orientation(Orientation::Horizontal)
+        .spacing(12)
+        .margin(12)
+        .build();
+    h_box.add(&Label::new(Some("Levels below the root")));
+    h_box.add(&depth);
+    dialog.content_area().add(&h_box);
+
+    dialog.connect_response(move |dialog, response| {
+        if response == ResponseType::Ok {
+            tree_view.tree_view.collapse_all();
+            if let Some(root) = tree_view.model.iter_first() {
+                expand_to_depth(&tree_view, &root, depth.value_as_int() as u32 + 1);
+            }
+        }
+        dialog.close();
+    });
+
+    dialog.show_all();
+}
+
+// Filter the tree down to matching rows and their ancestors; None shows everything.
+// Returns the number of matching rows.
+pub fn set_filter(tree_view: TreeViewState, filter: Option<TreeFilter>) -> usize {
+    let had_filter = tree_view.filter.replace(filter).is_some();
+    let match_count = apply_filter(&tree_view);
+
+    if match_count.is_some() {
+        tree_view.tree_view.expand_all();
+    } else if had_filter {
+        // Go back to how the tree was expanded before searching
+        tree_view.tracking_expansion.set(false);
+        tree_view.tree_view.collapse_all();
+        tree_view.tracking_expansion.set(true);
+        restore_expansion(&tree_view);
+    }
+    match_count.unwrap_or(0)
+}
+
+fn apply_filter(tree_view: &TreeViewState) -> Option<usize> {
+    let filter = tree_view.filter.borrow();
+    let mut match_count = 0;
+    if let Some(root) = tree_view.model.iter_first() {
+        if filter.is_some() {
+            load_all_children(tree_view, &root);
+        }
+        mark_matches(&tree_view.model, &root, filter.as_ref(), &mut match_count);
+    }
+    filter.as_ref().map(|_| match_count)
+}
+
+// Marks whether each row matches and whether it should be visible, returning true if the
+// row or any of its descendants matched
+fn mark_matches(
+    model: &TreeStore,
+    iter: &TreeIter,
+    filter: Option<&TreeFilter>,
+    match_count: &mut usize,
+) -> bool {
+    let mut descendant_matches = false;
+    if let Some(child) = model.iter_children(Some(iter)) {
+        loop {
+            descendant_matches |= mark_matches(model, &child, filter, match_count);
+            if !model.iter_next(&child) {
+                break;
+            }
+        }
+    }
+
+    let is_match = filter.is_some_and(|filter| {
+        if filter.match_keys {
+            let key = model.value(iter, COL_KEY as i32).get::<String>().unwrap();
+            filter.regex.is_match(&key)
+        } else {
+            // Containers show a placeholder rather than a value
+            let editable = model
+                .value(iter, COL_EDITABLE as i32)
+                .get::<bool>()
+                .unwrap();
+            let value = model.value(iter, COL_VALUE as i32).get::<String>().unwrap();
+            editable && filter.regex.is_match(&value)
+        }
+    });
+    if is_match {
+        *match_count += 1;
+    }
+
+    let visible = filter.is_none() || is_match || descendant_matches;
+    set_bool_if_changed(model, iter, COL_VISIBLE, visible);
+    set_bool_if_changed(model, iter, COL_MATCH, is_match);
+    is_match || descendant_matches
+}
+
+// Select the next (or previous) matching row after the current selection, returning its
+// position among all matches and the total number of matches
+pub fn select_match(tree_view: TreeViewState, forward: bool) -> Option<(usize, usize)> {
+    let model = tree_view.tree_view.model()?;
+    let mut matches = Vec::new();
+    if let Some(root) = model.iter_first() {
+        collect_match_paths(&model, &root, &mut matches);
+    }
+    if matches.is_empty() {
+        return None;
+    }
+
+    let selected = tree_view
+        .tree_view
+        .selection()
+        .selected()
+        .and_then(|(model, iter)| model.path(&iter));
+    let index = match selected.and_then(|path| matches.iter().position(|m| *m == path)) {
+        Some(current) if forward => (current + 1) % matches.len(),
+        Some(current) => (current + matches.len() - 1) % matches.len(),
+        None if forward => 0,
+        None => matches.len() - 1,
+    };
+
+    let path = &matches[index];
+    tree_view.tree_view.expand_to_path(path);
+    tree_view.tree_view.selection().select_path(path);
+    tree_view
+        .tree_view
+        .scroll_to_cell(Some(path), None::<&TreeViewColumn>, true, 0.5, 0.0);
+
+    Some((index + 1, matches.len()))
+}
+
+fn collect_match_paths(model: &gtk::TreeModel, iter: &TreeIter, matches: &mut Vec<TreePath>) {
+    if model.value(iter, COL_MATCH as i32).get::<bool>().unwrap() {
+        if let Some(path) = model.path(iter) {
+            matches.push(path);
+        }
+    }
+    if let Some(child) = model.iter_children(Some(iter)) {
+        loop {
+            collect_match_paths(model, &child, matches);
+            if !model.iter_next(&child) {
+                break;
+            }
+        }
+    }
+}
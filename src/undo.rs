@@ -0,0 +1,414 @@
+// Undo/redo history for a TextBuffer, which has none of its own in GTK 3.
+//
+// Every insertion and deletion is recorded. Everything done between begin_user_action and
+// end_user_action becomes a single step, so a transform that replaces the whole buffer is
+// undone in one go, while consecutive keystrokes within a word are merged together.
+use gtk::prelude::*;
+use gtk::TextBuffer;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+// Text held by the undo and redo steps, as a transform of the whole buffer holds both the
+// old and new text. The oldest steps are dropped first, but the latest is always kept.
+const MAX_HISTORY_BYTES: usize = 64 * 1024 * 1024;
+
+#[derive(Clone, Debug, PartialEq)]
+enum Edit {
+    // Offsets are in characters, as used by TextIter
+    Insert { offset: i32, text: String },
+    Delete { offset: i32, text: String },
+}
+
+#[derive(Default)]
+pub struct History {
+    undo: Vec<Vec<Edit>>,
+    redo: Vec<Vec<Edit>>,
+    current: Vec<Edit>,
+    user_action_depth: u32,
+    // Set while undoing/redoing so those edits are not recorded themselves
+    applying: bool,
+    // Whether the last step was a single keystroke that the next one may be merged into
+    last_was_typing: bool,
+    // Size of the text in every undo and redo step
+    stored_bytes: usize,
+    // Cleared while editing isn't recorded at all, e.g. for large documents
+    paused: bool,
+}
+
+pub type HistoryState = Rc<RefCell<History>>;
+
+pub fn attach_to_buffer(buffer: &TextBuffer, history: HistoryState) {
+    buffer.connect_insert_text({
+        let history = history.clone();
+        move |_, iter, text| {
+            history.borrow_mut().record(Edit::Insert {
+                offset: iter.offset(),
+                text: text.to_string(),
+            })
+        }
+    });
+
+    buffer.connect_delete_range({
+        let history = history.clone();
+        move |buffer, start, end| {
+            history.borrow_mut().record(Edit::Delete {
+                offset: start.offset(),
+                text: buffer.text(start, end, true).unwrap().to_string(),
+            })
+        }
+    });
+
+    buffer.connect_begin_user_action({
+        let history = history.clone();
+        move |_| history.borrow_mut().user_action_depth += 1
+    });
+
+    buffer.connect_end_user_action(move |_| {
+        let mut history = history.borrow_mut();
+        history.user_action_depth = history.user_action_depth.saturating_sub(1);
+        if history.user_action_depth == 0 {
+            history.commit();
+        }
+    });
+}
+
+impl History {
+    fn record(&mut self, edit: Edit) {
+        if self.applying || self.paused {
+            return;
+        }
+        self.current.push(edit);
+        if self.user_action_depth == 0 {
+            self.commit();
+        }
+    }
+
+    fn commit(&mut self) {
+        if self.current.is_empty() {
+            return;
+        }
+        let step = std::mem::take(&mut self.current);
+        self.stored_bytes -= self
+            .redo
+            .drain(..)
+            .map(|step| step_bytes(&step))
+            .sum::<usize>();
+        self.stored_bytes += step_bytes(&step);
+
+        let is_typing = step.len() == 1 && is_keystroke(&step[0]);
+        let merged = is_typing && self.last_was_typing && self.try_merge(&step[0]);
+        if !merged {
+            self.undo.push(step);
+            while self.stored_bytes > MAX_HISTORY_BYTES && self.undo.len() > 1 {
+                let oldest = self.undo.remove(0);
+                self.stored_bytes -= step_bytes(&oldest);
+            }
+        }
+        self.last_was_typing = is_typing;
+    }
+
+    // Merge a keystroke into the previous one if it continues the same word
+    fn try_merge(&mut self, edit: &Edit) -> bool {
+        let Some([previous]) = self.undo.last_mut().map(Vec::as_mut_slice) else {
+            return false;
+        };
+
+        match (previous, edit) {
+            (
+                Edit::Insert { offset, text },
+                Edit::Insert {
+                    offset: new_offset,
+                    text: new_text,
+                },
+            ) if *new_offset == *offset + text.chars().count() as i32
+                && !starts_new_word(text, new_text) =>
+            {
+                text.push_str(new_text);
+                true
+            }
+            // Backspace
+            (
+                Edit::Delete { offset, text },
+                Edit::Delete {
+                    offset: new_offset,
+                    text: new_text,
+                },
+            ) if *new_offset + 1 == *offset && !starts_new_word(new_text, text) => {
+                text.insert_str(0, new_text);
+                *offset = *new_offset;
+                true
+            }
+            // Delete key
+            (
+                Edit::Delete { offset, text },
+                Edit::Delete {
+                    offset: new_offset,
+                    text: new_text,
+                },
+            ) if *new_offset == *offset && !starts_new_word(text, new_text) => {
+                text.push_str(new_text);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    pub fn set_recording(&mut self, recording: bool) {
+        self.paused = !recording;
+    }
+
+    pub fn clear(&mut self) {
+        self.undo.clear();
+        self.redo.clear();
+        self.current.clear();
+        self.last_was_typing = false;
+        self.stored_bytes = 0;
+    }
+}
+
+fn step_bytes(step: &[Edit]) -> usize {
+    step.iter()
+        .map(|edit| match edit {
+            Edit::Insert { text, .. } | Edit::Delete { text, .. } => text.len(),
+        })
+        .sum()
+}
+
+fn is_keystroke(edit: &Edit) -> bool {
+    match edit {
+        Edit::Insert { text, .. } | Edit::Delete { text, .. } => {
+            text.chars().count() == 1 && text != "\n"
+        }
+    }
+}
+
+fn starts_new_word(before: &str, after: &str) -> bool {
+    let before_space = before.chars().last().is_some_and(char::is_whitespace);
+    let after_space = after.chars().next().is_some_and(char::is_whitespace);
+    before_space && !after_space
+}
+
+pub fn undo(buffer: &TextBuffer, history: HistoryState) {
+    let Some(step) = history.borrow_mut().undo.pop() else {
+        return;
+    };
+    apply(buffer, &history, step.iter().rev().map(invert).collect());
+    history.borrow_mut().redo.push(step);
+}
+
+pub fn redo(buffer: &TextBuffer, history: HistoryState) {
+    let Some(step) = history.borrow_mut().redo.pop() else {
+        return;
+    };
+    apply(buffer, &history, step.clone());
+    history.borrow_mut().undo.push(step);
+}
+
+fn invert(edit: &Edit) -> Edit {
+    match edit.clone() {
+        Edit::Insert { offset, text } => Edit::Delete { offset, text },
+        Edit::Delete { offset, text } => Edit::Insert { offset, text },
+    }
+}
+
+fn apply(buffer: &TextBuffer, history: &HistoryState, edits: Vec<Edit>) {
+    {
+        let mut history = history.borrow_mut();
+        history.applying = true;
+        history.last_was_typing = false;
+    }
+
+    let mut cursor = None;
+    for edit in edits {
+        match edit {
+            Edit::Insert { offset, text } => {
+                let mut iter = buffer.iter_at_offset(offset);
+                buffer.insert(&mut iter, &text);
+                cursor = Some(offset + text.chars().count() as i32);
+            }
+            Edit::Delete { offset, text } => {
+                let mut start = buffer.iter_at_offset(offset);
+                let mut end = buffer.iter_at_offset(offset + text.chars().count() as i32);
+                buffer.delete(&mut start, &mut end);
+                cursor = Some(offset);
+            }
+        }
+    }
+    if let Some(cursor) = cursor {
+        buffer.place_cursor(&buffer.iter_at_offset(cursor));
+    }
+
+    history.borrow_mut().applying = false;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn insert(offset: i32, text: &str) -> Edit {
+        Edit::Insert {
+            offset,
+            text: text.to_string(),
+        }
+    }
+
+    fn delete(offset: i32, text: &str) -> Edit {
+        Edit::Delete {
+            offset,
+            text: text.to_string(),
+        }
+    }
+
+    fn type_text(history: &mut History, offset: i32, text: &str) {
+        for (i, c) in text.chars().enumerate() {
+            history.record(insert(offset + i as i32, &c.to_string()));
+        }
+    }
+
+    // What stored_bytes should be, counted from scratch
+    fn counted_bytes(history: &History) -> usize {
+        history
+            .undo
+            .iter()
+            .chain(&history.redo)
+            .map(|step| step_bytes(step))
+            .sum()
+    }
+
+    #[test]
+    fn typing_is_merged_by_word() {
+        let mut history = History::default();
+        type_text(&mut history, 0, "ab  cd");
+        assert_eq!(
+            history.undo,
+            [vec![insert(0, "ab  ")], vec![insert(4, "cd")]]
+        );
+        assert_eq!(history.stored_bytes, 6);
+    }
+
+    #[test]
+    fn newlines_and_jumps_start_new_steps() {
+        let mut history = History::default();
+        type_text(&mut history, 0, "ab\ncd");
+        // Typing somewhere else
+        type_text(&mut history, 0, "x");
+        assert_eq!(
+            history.undo,
+            [
+                vec![insert(0, "ab")],
+                vec![insert(2, "\n")],
+                vec![insert(3, "cd")],
+                vec![insert(0, "x")],
+            ]
+        );
+    }
+
+    #[test]
+    fn backspace_is_merged() {
+        let mut history = History::default();
+        // Backspacing over "ab cd" from the end
+        for (offset, c) in [(4, "d"), (3, "c"), (2, " "), (1, "b"), (0, "a")] {
+            history.record(delete(offset, c));
+        }
+        // Split where typing it would have been
+        assert_eq!(
+            history.undo,
+            [vec![delete(3, "cd")], vec![delete(0, "ab ")]]
+        );
+        assert_eq!(history.stored_bytes, 5);
+    }
+
+    #[test]
+    fn delete_key_is_merged() {
+        let mut history = History::default();
+        // Deleting "ab cd" forwards from the start
+        for c in ["a", "b", " ", "c", "d"] {
+            history.record(delete(0, c));
+        }
+        assert_eq!(
+            history.undo,
+            [vec![delete(0, "ab ")], vec![delete(0, "cd")]]
+        );
+    }
+
+    #[test]
+    fn user_actions_are_one_step() {
+        let mut history = History::default();
+        type_text(&mut history, 0, "a");
+        history.user_action_depth = 1;
+        history.record(delete(0, "a"));
+        history.record(insert(0, "[1]"));
+        history.user_action_depth = 0;
+        history.commit();
+        // Not merged with the typing before or after it
+        type_text(&mut history, 3, "b");
+        assert_eq!(
+            history.undo,
+            [
+                vec![insert(0, "a")],
+                vec![delete(0, "a"), insert(0, "[1]")],
+                vec![insert(3, "b")],
+            ]
+        );
+        assert_eq!(history.stored_bytes, counted_bytes(&history));
+    }
+
+    #[test]
+    fn new_edits_clear_redo() {
+        let mut history = History::default();
+        type_text(&mut history, 0, "a b");
+        // As undo does
+        let step = history.undo.pop().unwrap();
+        history.redo.push(step);
+        assert_eq!(history.stored_bytes, counted_bytes(&history));
+
+        type_text(&mut history, 2, "c");
+        assert!(history.redo.is_empty());
+        assert_eq!(history.undo, [vec![insert(0, "a ")], vec![insert(2, "c")]]);
+        assert_eq!(history.stored_bytes, 3);
+    }
+
+    #[test]
+    fn oldest_steps_are_dropped_past_the_limit() {
+        let mut history = History::default();
+        let half = "x".repeat(MAX_HISTORY_BYTES / 2);
+        type_text(&mut history, 0, "a");
+        history.record(insert(1, &half));
+        assert_eq!(history.undo.len(), 2);
+        assert_eq!(history.stored_bytes, half.len() + 1);
+
+        history.record(insert(0, &half));
+        assert_eq!(
+            history.undo,
+            [vec![insert(1, &half)], vec![insert(0, &half)]]
+        );
+        assert_eq!(history.stored_bytes, MAX_HISTORY_BYTES);
+
+        history.record(insert(0, "b"));
+        assert_eq!(history.undo, [vec![insert(0, &half)], vec![insert(0, "b")]]);
+        assert_eq!(history.stored_bytes, counted_bytes(&history));
+
+        // The latest step is kept however big it is
+        let too_big = "y".repeat(MAX_HISTORY_BYTES + 1);
+        history.record(insert(0, &too_big));
+        assert_eq!(history.undo.len(), 1);
+        assert_eq!(history.stored_bytes, too_big.len());
+        assert_eq!(history.stored_bytes, counted_bytes(&history));
+    }
+
+    #[test]
+    fn paused_and_cleared() {
+        let mut history = History::default();
+        type_text(&mut history, 0, "a");
+        history.set_recording(false);
+        type_text(&mut history, 1, "b");
+        assert_eq!(history.undo, [vec![insert(0, "a")]]);
+        history.set_recording(true);
+        history.clear();
+        assert!(history.undo.is_empty());
+        assert_eq!(history.stored_bytes, 0);
+        // Nothing to merge into after clearing
+        type_text(&mut history, 1, "b");
+        assert_eq!(history.undo, [vec![insert(1, "b")]]);
+    }
+}
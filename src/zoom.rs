@@ -0,0 +1,186 @@
+// Zooming the editor and tree together, as a percentage of the preferred font size
+use crate::json_editor::JsonEditorState;
+use crate::preferences::{Preferences, PreferencesState};
+use crate::tree_view::TreeViewState;
+use crate::{json_editor, preferences};
+use gtk::gdk::{EventScroll, ModifierType, ScrollDirection};
+use gtk::glib;
+use gtk::glib::{Propagation, SourceId};
+use gtk::pango::FontDescription;
+use gtk::prelude::*;
+use gtk::{Button, CssProvider, ReliefStyle, STYLE_PROVIDER_PRIORITY_APPLICATION};
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+use std::time::Duration;
+
+const ZOOM_STEP: u32 = 10;
+const MIN_ZOOM: u32 = 30;
+const MAX_ZOOM: u32 = 400;
+// How long the zoom must stay the same before it's saved, as a touchpad pinch or scroll
+// changes it many times in a row
+const SAVE_DELAY: Duration = Duration::from_millis(500);
+
+#[derive(Clone)]
+pub struct ZoomState {
+    // Shared by the editor and the tree, and replaced each time the zoom or font changes
+    provider: CssProvider,
+    // Shown in the status bar while zoomed, and resets the zoom when clicked
+    pub indicator: Button,
+    // Smooth scrolling not yet added up to a whole step
+    scroll_delta: Rc<Cell<f64>>,
+    pending_save: Rc<RefCell<Option<SourceId>>>,
+}
+
+pub fn factory_zoom(json_editor: JsonEditorState, tree_view: TreeViewState) -> ZoomState {
+    let provider = CssProvider::new();
+    json_editor::add_style_provider(json_editor, &provider);
+    tree_view
+        .tree_view
+        .style_context()
+        .add_provider(&provider, STYLE_PROVIDER_PRIORITY_APPLICATION);
+
+    let indicator = Button::builder()
+        .no_show_all(true)
+        .relief(ReliefStyle::None)
+        .focus_on_click(false)
+        .tooltip_text("Reset zoom (Ctrl+0)")
+        .build();
+
+    ZoomState {
+        provider,
+        indicator,
+        scroll_delta: Rc::new(Cell::new(0.0)),
+        pending_save: Rc::new(RefCell::new(None)),
+    }
+}
+
+pub fn attach_listeners(
+    zoom: ZoomState,
+    json_editor: JsonEditorState,
+    preferences: PreferencesState,
+) {
+    zoom.indicator.connect_clicked({
+        let zoom = zoom.clone();
+        move |_| reset_zoom_action(zoom.clone(), json_editor.clone(), preferences.clone())
+    });
+}
+
+// Use the font and zoom for the editor, the read-only view of large documents and the tree
+pub fn apply(zoom: ZoomState, json_editor: JsonEditorState, preferences: &Preferences) {
+    let size = preferences.font_size as f64 * preferences.zoom_percent as f64 / 100.0;
+    // The tree keeps the GTK theme's font, so it's only zoomed when the zoom isn't 100%
+    let tree_css = if preferences.zoom_percent == 100 {
+        String::new()
+    } else {
+        format!(
+            "treeview {{ font-size: {:.1}pt; }}",
+            default_font_size() * preferences.zoom_percent as f64 / 100.0
+        )
+    };
+    let _ = zoom.provider.load_from_data(
+        format!(
+            "textview {{ font-family: \"{}\"; font-size: {:.1}pt; }} {}",
+            preferences.font_family.replace('"', ""),
+            size,
+            tree_css
+        )
+        .as_bytes(),
+    );
+
+    let mut font = FontDescription::new();
+    font.set_family(&preferences.font_family);
+    font.set_size((size * gtk::pango::SCALE as f64).round() as i32);
+    json_editor::set_large_view_font(json_editor, font);
+
+    zoom.indicator
+        .set_label(&format!("{}%", preferences.zoom_percent));
+    zoom.indicator.set_visible(preferences.zoom_percent != 100);
+}
+
+// Size of the GTK theme's font in points, which the tree is otherwise shown in
+fn default_font_size() -> f64 {
+    gtk::Settings::default()
+        .and_then(|settings| settings.gtk_font_name())
+        .map(|name| FontDescription::from_string(&name).size())
+        .filter(|size| *size > 0)
+        .map_or(10.0, |size| size as f64 / gtk::pango::SCALE as f64)
+}
+
+fn set_zoom(
+    zoom: ZoomState,
+    json_editor: JsonEditorState,
+    preferences: PreferencesState,
+    percent: u32,
+) {
+    preferences.borrow_mut().zoom_percent = percent.clamp(MIN_ZOOM, MAX_ZOOM);
+    apply(zoom.clone(), json_editor, &preferences.borrow());
+
+    if let Some(pending_save) = zoom.pending_save.take() {
+        pending_save.remove();
+    }
+    let source_id = glib::timeout_add_local_once(SAVE_DELAY, {
+        let pending_save = zoom.pending_save.clone();
+        move || {
+            pending_save.take();
+            let _ = preferences::save(&preferences.borrow());
+        }
+    });
+    zoom.pending_save.replace(Some(source_id));
+}
+
+pub fn zoom_in_action(
+    zoom: ZoomState,
+    json_editor: JsonEditorState,
+    preferences: PreferencesState,
+) {
+    let percent = preferences.borrow().zoom_percent + ZOOM_STEP;
+    set_zoom(zoom, json_editor, preferences, percent);
+}
+
+pub fn zoom_out_action(
+    zoom: ZoomState,
+    json_editor: JsonEditorState,
+    preferences: PreferencesState,
+) {
+    let percent = preferences.borrow().zoom_percent.saturating_sub(ZOOM_STEP);
+    set_zoom(zoom, json_editor, preferences, percent);
+}
+
+pub fn reset_zoom_action(
+    zoom: ZoomState,
+    json_editor: JsonEditorState,
+    preferences: PreferencesState,
+) {
+    set_zoom(zoom, json_editor, preferences, 100);
+}
+
+pub fn ctrl_scroll_zoom_action(
+    event: &EventScroll,
+    zoom: ZoomState,
+    json_editor: JsonEditorState,
+    preferences: PreferencesState,
+) -> Propagation {
+    if !event.state().contains(ModifierType::CONTROL_MASK) {
+        return Propagation::Proceed;
+    }
+
+    // Touchpads send many small smooth scrolls rather than steps up and down, so those are
+    // added up until they make a whole step
+    let delta_y = match event.direction() {
+        ScrollDirection::Up => -1.0,
+        ScrollDirection::Down => 1.0,
+        ScrollDirection::Smooth => zoom.scroll_delta.get() + event.delta().1,
+        _ => return Propagation::Proceed,
+    };
+    if delta_y <= -1.0 {
+        zoom.scroll_delta.set(0.0);
+        zoom_in_action(zoom, json_editor, preferences);
+    } else if delta_y >= 1.0 {
+        zoom.scroll_delta.set(0.0);
+        zoom_out_action(zoom, json_editor, preferences);
+    } else {
+        zoom.scroll_delta.set(delta_y);
+    }
+
+    Propagation::Stop
+}